@@ -0,0 +1,232 @@
+//! # OmniBox macros
+//! Procedural macros re-exported by the `omni-box` crate.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{bracketed, parse_macro_input, FnArg, Ident, ItemFn, LitBool, LitStr, Token, Type};
+
+/// Runs an async test against a fresh OmniBox
+///
+/// ```ignore
+/// #[omni_box::test(modules = [evm, bitcoin], contract = "./contracts/vault")]
+/// async fn test_vault(omni_box: &OmniBox, evm: &EVMTestContext) {
+///     // ...
+/// }
+/// ```
+///
+/// Arguments may be `&OmniBox`, `&BTCTestContext`, `&EVMTestContext` or `&NearTestContext`.
+/// The test may return `()` or a `Result`, on failure the state of the chains is logged.
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as TestArgs);
+    let test_fn = parse_macro_input!(item as ItemFn);
+
+    expand_test(args, test_fn).unwrap_or_else(|err| err.to_compile_error().into())
+}
+
+/// Options of the `test` attribute
+#[derive(Default)]
+struct TestArgs {
+    modules: Option<Vec<Ident>>,
+    contract: Option<LitStr>,
+    force_redeploy: Option<LitBool>,
+}
+
+impl Parse for TestArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = Self::default();
+
+        while !input.is_empty() {
+            let name: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+
+            match name.to_string().as_str() {
+                "modules" => {
+                    let content;
+                    bracketed!(content in input);
+                    let modules = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+                    args.modules = Some(modules.into_iter().collect());
+                }
+                "contract" => args.contract = Some(input.parse()?),
+                "force_redeploy" => args.force_redeploy = Some(input.parse()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        "Unknown option, expected `modules`, `contract` or `force_redeploy`",
+                    ))
+                }
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+fn expand_test(args: TestArgs, mut test_fn: ItemFn) -> syn::Result<TokenStream> {
+    if test_fn.sig.asyncness.is_none() {
+        return Err(syn::Error::new(
+            test_fn.sig.fn_token.span(),
+            "The test function must be async",
+        ));
+    }
+
+    let options = expand_options(&args)?;
+    let injected_args = test_fn
+        .sig
+        .inputs
+        .iter()
+        .map(expand_injected_arg)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    // The original function is kept as is and called with the injected arguments
+    let attrs = std::mem::take(&mut test_fn.attrs);
+    let vis = test_fn.vis.clone();
+    let name = test_fn.sig.ident.clone();
+
+    Ok(quote! {
+        #[::core::prelude::v1::test]
+        #(#attrs)*
+        #vis fn #name() {
+            #test_fn
+
+            ::omni_box::test_support::run(
+                ::core::stringify!(#name),
+                #options,
+                |__omni_box: ::std::sync::Arc<::omni_box::OmniBox>| async move {
+                    #name(#(#injected_args),*).await
+                },
+            );
+        }
+    }
+    .into())
+}
+
+fn expand_options(args: &TestArgs) -> syn::Result<TokenStream2> {
+    let mut fields = Vec::new();
+
+    if let Some(modules) = &args.modules {
+        let modules = modules
+            .iter()
+            .map(|module| {
+                let variant = match module.to_string().as_str() {
+                    "evm" => Ident::new("EVM", module.span()),
+                    "bitcoin" | "btc" => Ident::new("Bitcoin", module.span()),
+                    "near" => Ident::new("Near", module.span()),
+                    _ => {
+                        return Err(syn::Error::new(
+                            module.span(),
+                            "Unknown module, expected `evm`, `bitcoin` or `near`",
+                        ))
+                    }
+                };
+                Ok(quote! { ::omni_box::Network::#variant })
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        fields.push(quote! { modules: ::std::vec![#(#modules),*], });
+    }
+    if let Some(contract) = &args.contract {
+        fields.push(quote! { contract: ::omni_box::ContractSource::from(#contract), });
+    }
+    if let Some(force_redeploy) = &args.force_redeploy {
+        fields.push(quote! { force_redeploy: #force_redeploy, });
+    }
+
+    Ok(quote! {
+        ::omni_box::OmniBoxOptions {
+            #(#fields)*
+            ..::core::default::Default::default()
+        }
+    })
+}
+
+/// Argument passed for a test parameter: the OmniBox or one of its contexts, based on its type
+fn expand_injected_arg(arg: &FnArg) -> syn::Result<TokenStream2> {
+    let FnArg::Typed(arg) = arg else {
+        return Err(syn::Error::new(
+            arg.span(),
+            "Test functions can not take self",
+        ));
+    };
+
+    let type_name = match arg.ty.as_ref() {
+        Type::Reference(reference) => match reference.elem.as_ref() {
+            Type::Path(path) => path.path.segments.last().map(|segment| &segment.ident),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    match type_name.map(ToString::to_string).as_deref() {
+        Some("OmniBox") => Ok(quote! { &__omni_box }),
+        Some("BTCTestContext") => Ok(quote! {
+            __omni_box.btc_context().expect("The bitcoin module is not enabled for this test")
+        }),
+        Some("EVMTestContext") => Ok(quote! {
+            __omni_box.evm_context().expect("The evm module is not enabled for this test")
+        }),
+        Some("NearTestContext") => Ok(quote! {
+            __omni_box.near_context().expect("The near module is not enabled for this test")
+        }),
+        _ => Err(syn::Error::new(
+            arg.ty.span(),
+            "Expected `&OmniBox`, `&BTCTestContext`, `&EVMTestContext` or `&NearTestContext`",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // A glob import would shadow the built-in `test` attribute with the one of this crate
+    use super::{expand_injected_arg, expand_options, TestArgs};
+    use quote::quote;
+    use syn::FnArg;
+
+    #[test]
+    fn test_expand_options() {
+        let args: TestArgs = syn::parse_quote!(
+            modules = [evm, btc],
+            contract = "./vault",
+            force_redeploy = true
+        );
+
+        assert_eq!(
+            expand_options(&args).unwrap().to_string(),
+            quote! {
+                ::omni_box::OmniBoxOptions {
+                    modules: ::std::vec![::omni_box::Network::EVM, ::omni_box::Network::Bitcoin],
+                    contract: ::omni_box::ContractSource::from("./vault"),
+                    force_redeploy: true,
+                    ..::core::default::Default::default()
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_expand_injected_arg() {
+        let near: FnArg = syn::parse_quote!(near: &omni_box::NearTestContext);
+        let omni_box: FnArg = syn::parse_quote!(omni_box: &OmniBox);
+        let owned: FnArg = syn::parse_quote!(omni_box: OmniBox);
+
+        assert_eq!(
+            expand_injected_arg(&near).unwrap().to_string(),
+            quote! {
+                __omni_box.near_context().expect("The near module is not enabled for this test")
+            }
+            .to_string()
+        );
+        assert_eq!(
+            expand_injected_arg(&omni_box).unwrap().to_string(),
+            quote! { &__omni_box }.to_string()
+        );
+        assert!(expand_injected_arg(&owned).is_err());
+    }
+}
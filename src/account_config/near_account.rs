@@ -1,6 +1,7 @@
 use near_crypto::{PublicKey, SecretKey};
 use near_sdk::AccountId;
 
+/// Credentials of a NEAR account, used to sign its transactions
 #[derive(Debug, Clone)]
 pub struct NearAccount {
     pub account_id: AccountId,
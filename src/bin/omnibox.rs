@@ -0,0 +1,402 @@
+//! Command line interface to run an OmniBox outside of Rust tests
+//!
+//! - `omnibox up [--detach] [--modules evm,bitcoin,near]` starts the chains and deploys the contracts
+//! - `omnibox status` prints the state of the background instance
+//! - `omnibox down` stops the background instance
+//! - `omnibox doctor` checks the node binaries
+//!
+//! The running instance holds a lock file until its nodes are stopped, `status` and `down` check
+//! that lock before trusting its pid and stop it with signals. `--detach`, `status` and `down` are
+//! only supported on unix.
+use omni_box::prerequisites::{self, BinaryPaths};
+use omni_box::{Network, OmniBox, OmniBoxOptions};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tracing_subscriber::EnvFilter;
+
+const STATE_DIR: &str = ".omnibox";
+const STATE_FILE: &str = ".omnibox/instance.json";
+const LOCK_FILE: &str = ".omnibox/instance.lock";
+const LOG_FILE: &str = ".omnibox/omnibox.log";
+const MANIFEST_FILE: &str = ".omnibox/manifest.json";
+
+/// Time given to a background instance to start the nodes and deploy the contracts
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Time given to a background instance to stop its nodes
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(60);
+
+const USAGE: &str =
+    "Usage: omnibox <up [--detach] [--modules evm,bitcoin,near] | status | down | doctor>";
+
+#[tokio::main]
+async fn main() {
+    // Progress is logged at info level unless RUST_LOG says otherwise
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("omni_box=info")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("up") => up(&args[1..]).await,
+        Some("status") => status(),
+        Some("down") => down(),
+        Some("doctor") => doctor(),
+        _ => Err(USAGE.into()),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn up(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if let Some(state) = read_state()? {
+        if is_running() {
+            return Err(format!("OmniBox is already running with pid {}", state["pid"]).into());
+        }
+    }
+
+    let mut options = OmniBoxOptions::default();
+    let mut detach = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--detach" => detach = true,
+            "--modules" => {
+                let modules = args.next().ok_or("--modules expects a list of chains")?;
+                options.modules = parse_modules(modules)?;
+            }
+            _ => return Err(format!("Unknown argument {}\n{}", arg, USAGE).into()),
+        }
+    }
+
+    if detach {
+        return up_detached();
+    }
+
+    let lock = lock_instance(Path::new(LOCK_FILE))?;
+    let omni_box = OmniBox::new_with_conf(Some(options)).await;
+    let manifest = omni_box.export_manifest(MANIFEST_FILE)?;
+
+    write_state(
+        Path::new(STATE_FILE),
+        &json!({
+            "pid": std::process::id(),
+            "environment": manifest,
+        }),
+    )?;
+
+    println!("{}", serde_json::to_string_pretty(&manifest)?);
+    println!(
+        "Manifest written to {} and {}",
+        MANIFEST_FILE,
+        Path::new(MANIFEST_FILE).with_extension("env").display()
+    );
+    println!("OmniBox is running, press Ctrl-C to stop it.");
+
+    wait_for_shutdown_signal().await?;
+
+    println!("Stopping OmniBox.");
+    drop(omni_box);
+    fs::remove_file(STATE_FILE).ok();
+    drop(lock);
+
+    Ok(())
+}
+
+/// Runs `omnibox up` in the background and waits until the environment is ready
+fn up_detached() -> Result<(), Box<dyn Error>> {
+    ensure_background_supported()?;
+    fs::create_dir_all(STATE_DIR)?;
+    fs::remove_file(STATE_FILE).ok();
+
+    let log = File::create(LOG_FILE)?;
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| arg != "--detach")
+        .collect();
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .spawn()?;
+
+    let started_at = Instant::now();
+    loop {
+        if let Some(state) = read_state()? {
+            println!("{}", serde_json::to_string_pretty(&state["environment"])?);
+            println!(
+                "OmniBox is running in the background with pid {}.",
+                state["pid"]
+            );
+            return Ok(());
+        }
+        if let Some(exit_status) = child.try_wait()? {
+            return Err(format!(
+                "OmniBox failed to start ({}), see {}",
+                exit_status, LOG_FILE
+            )
+            .into());
+        }
+        if started_at.elapsed() > STARTUP_TIMEOUT {
+            child.kill().ok();
+            return Err(format!("OmniBox did not start in time, see {}", LOG_FILE).into());
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+}
+
+fn status() -> Result<(), Box<dyn Error>> {
+    ensure_background_supported()?;
+
+    match read_state()? {
+        Some(state) if is_running() => {
+            println!("{}", serde_json::to_string_pretty(&state["environment"])?);
+            println!("OmniBox is running with pid {}.", state["pid"]);
+        }
+        Some(_) => {
+            fs::remove_file(STATE_FILE).ok();
+            println!("OmniBox is not running, removed a stale instance file.");
+        }
+        None => println!("OmniBox is not running."),
+    }
+
+    Ok(())
+}
+
+fn down() -> Result<(), Box<dyn Error>> {
+    ensure_background_supported()?;
+
+    let state = match read_state()? {
+        Some(state) if is_running() => state,
+        _ => {
+            fs::remove_file(STATE_FILE).ok();
+            println!("OmniBox is not running.");
+            return Ok(());
+        }
+    };
+
+    // The instance removes its state file and releases its lock once the nodes are stopped
+    terminate(&state)?;
+    let stopping_at = Instant::now();
+    while is_running() {
+        if stopping_at.elapsed() > SHUTDOWN_TIMEOUT {
+            return Err(format!("OmniBox with pid {} did not stop in time", state["pid"]).into());
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+    fs::remove_file(STATE_FILE).ok();
+
+    println!("OmniBox stopped.");
+
+    Ok(())
+}
+
+fn doctor() -> Result<(), Box<dyn Error>> {
+    let report = prerequisites::doctor(&BinaryPaths::default());
+    print!("{}", report);
+
+    if !report.is_ok() {
+        return Err("Some prerequisites are missing or unsupported".into());
+    }
+
+    Ok(())
+}
+
+fn parse_modules(modules: &str) -> Result<Vec<Network>, Box<dyn Error>> {
+    modules
+        .split(',')
+        .map(|module| match module.trim() {
+            #[cfg(feature = "evm")]
+            "evm" => Ok(Network::EVM),
+            #[cfg(feature = "bitcoin")]
+            "bitcoin" | "btc" => Ok(Network::Bitcoin),
+            "near" => Ok(Network::Near),
+            #[cfg(not(feature = "evm"))]
+            "evm" => Err("The evm module needs omnibox built with the `evm` feature".into()),
+            #[cfg(not(feature = "bitcoin"))]
+            "bitcoin" | "btc" => {
+                Err("The bitcoin module needs omnibox built with the `bitcoin` feature".into())
+            }
+            other => Err(format!("Unknown module {}, expected evm, bitcoin or near", other).into()),
+        })
+        .collect()
+}
+
+async fn wait_for_shutdown_signal() -> Result<(), Box<dyn Error>> {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
+
+fn read_state() -> Result<Option<Value>, Box<dyn Error>> {
+    read_state_at(Path::new(STATE_FILE))
+}
+
+fn read_state_at(path: &Path) -> Result<Option<Value>, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+}
+
+/// Writes through a temporary file, so `up --detach` polling the state never reads a partial one
+fn write_state(path: &Path, state: &Value) -> Result<(), Box<dyn Error>> {
+    let partial = path.with_extension(format!("{}.partial", std::process::id()));
+    fs::write(&partial, serde_json::to_string_pretty(state)?)?;
+    fs::rename(partial, path)?;
+
+    Ok(())
+}
+
+/// Background instances are found and stopped with signals
+fn ensure_background_supported() -> Result<(), Box<dyn Error>> {
+    if cfg!(unix) {
+        Ok(())
+    } else {
+        Err("Background instances (up --detach, status and down) are only supported on unix".into())
+    }
+}
+
+/// Locks the instance until the returned file is dropped
+fn lock_instance(path: &Path) -> Result<File, Box<dyn Error>> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let lock = File::create(path)?;
+
+    #[cfg(unix)]
+    if !try_lock(&lock, libc::LOCK_EX) {
+        return Err("Another OmniBox instance is running".into());
+    }
+
+    Ok(lock)
+}
+
+fn is_running() -> bool {
+    is_running_at(Path::new(LOCK_FILE))
+}
+
+/// Whether an instance holds the lock, unlike the pid of an exited instance it can not be reused
+#[cfg(unix)]
+fn is_running_at(path: &Path) -> bool {
+    File::open(path).is_ok_and(|lock| !try_lock(&lock, libc::LOCK_SH))
+}
+
+#[cfg(not(unix))]
+fn is_running_at(_path: &Path) -> bool {
+    false
+}
+
+/// Takes a lock without waiting, it is released when the file is closed
+#[cfg(unix)]
+fn try_lock(file: &File, operation: libc::c_int) -> bool {
+    use std::os::unix::io::AsRawFd;
+
+    // Safety: flock only reads its arguments
+    unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) == 0 }
+}
+
+#[cfg(unix)]
+fn terminate(state: &Value) -> Result<(), Box<dyn Error>> {
+    // The pid is only signalled while its instance still holds the lock
+    if !is_running() {
+        return Err(format!("OmniBox with pid {} is not running", state["pid"]).into());
+    }
+    kill(state, libc::SIGTERM)
+}
+
+#[cfg(not(unix))]
+fn terminate(_state: &Value) -> Result<(), Box<dyn Error>> {
+    ensure_background_supported()
+}
+
+#[cfg(unix)]
+fn kill(state: &Value, signal: libc::c_int) -> Result<(), Box<dyn Error>> {
+    let pid = state["pid"]
+        .as_u64()
+        .and_then(|pid| libc::pid_t::try_from(pid).ok())
+        .ok_or("Invalid instance file")?;
+
+    // Safety: kill only reads its arguments
+    if unsafe { libc::kill(pid, signal) } != 0 {
+        return Err(format!(
+            "Failed to signal OmniBox with pid {}: {}",
+            pid,
+            std::io::Error::last_os_error()
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_modules() {
+        assert_eq!(parse_modules(" near").unwrap(), [Network::Near]);
+        #[cfg(all(feature = "evm", feature = "bitcoin"))]
+        assert_eq!(
+            parse_modules("evm, btc,near").unwrap(),
+            [Network::EVM, Network::Bitcoin, Network::Near]
+        );
+        assert!(parse_modules("evm,solana")
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown module solana"));
+    }
+
+    #[test]
+    fn test_state_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("instance.json");
+        assert!(read_state_at(&path).unwrap().is_none());
+
+        let state = json!({ "pid": 42, "environment": { "version": 1 } });
+        write_state(&path, &state).unwrap();
+
+        assert_eq!(read_state_at(&path).unwrap(), Some(state));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_instance_runs_while_it_holds_the_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("instance.lock");
+        assert!(!is_running_at(&path));
+
+        let lock = lock_instance(&path).unwrap();
+        assert!(is_running_at(&path));
+        assert!(lock_instance(&path).is_err());
+
+        drop(lock);
+        assert!(!is_running_at(&path));
+    }
+}
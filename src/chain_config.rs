@@ -1,13 +1,17 @@
 use crate::network::Network;
 use crate::Account;
+#[cfg(feature = "evm")]
 use alloy::node_bindings::AnvilInstance;
+#[cfg(feature = "bitcoin")]
 use bitcoind::BitcoinD;
 use near_workspaces::network::Sandbox;
 use near_workspaces::Worker;
 
 #[derive(Debug)]
 pub enum NodeInstance {
+    #[cfg(feature = "evm")]
     Anvil(AnvilInstance),
+    #[cfg(feature = "bitcoin")]
     Bitcoin(BitcoinD),
     Workspaces(Worker<Sandbox>),
 }
@@ -27,6 +31,7 @@ pub struct ChainOverrides {
 impl ChainConfig {
     pub fn default(network: Network) -> Self {
         match network {
+            #[cfg(feature = "evm")]
             Network::EVM => Self {
                 node_url: "http://localhost:8545".to_string(),
                 node_instance: None,
@@ -37,6 +42,7 @@ impl ChainConfig {
                 node_instance: None,
                 accounts: vec![None],
             },
+            #[cfg(feature = "bitcoin")]
             Network::Bitcoin => Self {
                 node_url: "http://localhost:18443".to_string(),
                 node_instance: None,
@@ -0,0 +1,113 @@
+//! Chains plugged into an OmniBox
+//!
+//! The Bitcoin, EVM and NEAR sandbox chains are built-in modules, other networks are added by
+//! implementing `ChainModule` and passing a factory in `OmniBoxOptions::chain_modules`, or by
+//! registering a module with `OmniBox::register_module`.
+use crate::manifest::ManifestAccount;
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Future returned by the operations of a `ChainModule`
+pub type ModuleFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, Box<dyn Error + Send + Sync>>> + Send + 'a>>;
+
+/// Snapshot taken by a `ChainModule`, only read back by the module that took it
+pub type ModuleSnapshot = Arc<dyn Any + Send + Sync>;
+
+/// A chain run by an OmniBox
+///
+/// Amounts are in the smallest unit of the chain, e.g. satoshis, wei or yoctoNEAR.
+pub trait ChainModule: Send + Sync {
+    /// Name the module is registered under, unique within an OmniBox
+    fn name(&self) -> &str;
+
+    /// Spawns the node of the chain, or attaches to a running one
+    fn start(&mut self) -> ModuleFuture<'_, ()>;
+
+    /// Whether the node answers requests
+    fn is_ready(&self) -> ModuleFuture<'_, bool>;
+
+    /// Creates a new account holding `balance`, its key is encoded as in the manifest
+    fn create_funded_account(&self, balance: u128) -> ModuleFuture<'_, ManifestAccount>;
+
+    /// Sends `amount` to `address`
+    fn fund<'a>(&'a self, address: &'a str, amount: u128) -> ModuleFuture<'a, ()>;
+
+    /// Broadcasts a raw signed transaction, waits until it is confirmed and returns its hash
+    fn broadcast_raw_transaction<'a>(
+        &'a self,
+        raw_transaction: &'a [u8],
+    ) -> ModuleFuture<'a, String>;
+
+    /// Current height of the chain
+    fn height(&self) -> ModuleFuture<'_, u64>;
+
+    /// Unix timestamp in seconds of the chain, as seen by its time locks
+    fn timestamp(&self) -> ModuleFuture<'_, u64>;
+
+    /// Moves the chain time forward by at least `duration`
+    fn advance_time(&self, duration: Duration) -> ModuleFuture<'_, ()>;
+
+    /// Produces `blocks` blocks
+    fn advance_blocks(&self, blocks: u64) -> ModuleFuture<'_, ()>;
+
+    /// Takes a snapshot of the chain state
+    fn snapshot(&self) -> ModuleFuture<'_, ModuleSnapshot>;
+
+    /// Reverts the chain state to a snapshot, which stays valid for later reverts
+    fn revert<'a>(&'a self, snapshot: &'a ModuleSnapshot) -> ModuleFuture<'a, ()>;
+
+    /// Endpoints and accounts of the chain, exported in the OmniBox manifest
+    fn manifest(&self) -> serde_json::Value;
+}
+
+/// Builds a chain module started with the OmniBox, set in `OmniBoxOptions::chain_modules`
+#[derive(Clone)]
+pub struct ChainModuleFactory(Arc<dyn Fn() -> Box<dyn ChainModule> + Send + Sync>);
+
+impl ChainModuleFactory {
+    pub fn new<F: Fn() -> Box<dyn ChainModule> + Send + Sync + 'static>(build: F) -> Self {
+        Self(Arc::new(build))
+    }
+
+    pub(crate) fn build(&self) -> Box<dyn ChainModule> {
+        (self.0)()
+    }
+}
+
+impl fmt::Debug for ChainModuleFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ChainModuleFactory")
+    }
+}
+
+/// Reads back a snapshot taken by the module `name`
+pub fn downcast_snapshot<'a, T: 'static>(
+    name: &str,
+    snapshot: &'a ModuleSnapshot,
+) -> Result<&'a T, Box<dyn Error + Send + Sync>> {
+    snapshot
+        .downcast_ref::<T>()
+        .ok_or_else(|| format!("Snapshot was not taken by the {} module", name).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downcast_snapshot() {
+        let snapshot: ModuleSnapshot = Arc::new(42u64);
+
+        assert_eq!(downcast_snapshot::<u64>("evm", &snapshot).unwrap(), &42);
+        assert!(downcast_snapshot::<String>("evm", &snapshot)
+            .unwrap_err()
+            .to_string()
+            .contains("evm module"));
+    }
+}
@@ -0,0 +1,185 @@
+use crate::chain_module::{downcast_snapshot, ChainModule, ModuleFuture, ModuleSnapshot};
+use crate::clients::get_bitcoin_instance;
+use crate::contexts::{BTCTestContext, BtcSnapshot, ChainContext};
+use crate::manifest::{BitcoinManifest, ManifestAccount};
+use crate::prerequisites::BinaryPaths;
+use crate::warm_start::BitcoinTemplate;
+use bitcoin::{Address, Amount, Network};
+use std::error::Error;
+use std::str::FromStr as _;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Bitcoin regtest chain run by bitcoind
+#[derive(Debug)]
+pub struct BitcoinModule {
+    binaries: BinaryPaths,
+    seed: Option<u64>,
+    template: Option<BitcoinTemplate>, // Warm start data directory of the node
+    context: Option<Arc<BTCTestContext>>,
+}
+
+impl BitcoinModule {
+    pub const NAME: &'static str = "bitcoin";
+
+    /// Module spawning bitcoind when started
+    pub const fn new(binaries: BinaryPaths, seed: Option<u64>) -> Self {
+        Self {
+            binaries,
+            seed,
+            template: None,
+            context: None,
+        }
+    }
+
+    /// Module attached to a running node
+    pub fn attach(context: Arc<BTCTestContext>) -> Self {
+        Self {
+            binaries: BinaryPaths::default(),
+            seed: None,
+            template: None,
+            context: Some(context),
+        }
+    }
+
+    pub(crate) fn with_template(self, template: BitcoinTemplate) -> Self {
+        Self {
+            template: Some(template),
+            ..self
+        }
+    }
+
+    /// Context of the node, once started
+    pub const fn context(&self) -> Option<&Arc<BTCTestContext>> {
+        self.context.as_ref()
+    }
+
+    fn started(&self) -> Result<&BTCTestContext, Box<dyn Error + Send + Sync>> {
+        self.context
+            .as_deref()
+            .ok_or_else(|| "bitcoind is not started".into())
+    }
+}
+
+impl ChainModule for BitcoinModule {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn start(&mut self) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            if self.context.is_some() {
+                return Ok(());
+            }
+
+            // bitcoind and its RPC client are blocking, start them on the blocking pool
+            let binaries = self.binaries.clone();
+            let template = self.template.clone();
+            let seed = self.seed;
+            let context = tokio::task::spawn_blocking(move || {
+                let bitcoind = template.as_ref().map_or_else(
+                    || get_bitcoin_instance(&binaries, None),
+                    |template| template.start(&binaries),
+                );
+                bitcoind
+                    .and_then(|bitcoind| BTCTestContext::new_with_seed(bitcoind, seed))
+                    .map_err(|err| err.to_string())
+            })
+            .await??;
+
+            self.context = Some(Arc::new(context));
+            Ok(())
+        })
+    }
+
+    fn is_ready(&self) -> ModuleFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(match &self.context {
+                Some(context) => context.height().await.is_ok(),
+                None => false,
+            })
+        })
+    }
+
+    fn create_funded_account(&self, balance: u128) -> ModuleFuture<'_, ManifestAccount> {
+        Box::pin(async move {
+            let balance = Amount::from_sat(u64::try_from(balance)?);
+            let btc = self.started()?;
+            let account = btc.create_funded_account(balance).await?;
+
+            Ok(ManifestAccount::bitcoin(&account))
+        })
+    }
+
+    fn fund<'a>(&'a self, address: &'a str, amount: u128) -> ModuleFuture<'a, ()> {
+        Box::pin(async move {
+            let address = Address::from_str(address)?.require_network(Network::Regtest)?;
+            let amount = Amount::from_sat(u64::try_from(amount)?);
+
+            self.started()?.fund_address(&address, &[amount])
+        })
+    }
+
+    fn broadcast_raw_transaction<'a>(
+        &'a self,
+        raw_transaction: &'a [u8],
+    ) -> ModuleFuture<'a, String> {
+        Box::pin(async move {
+            let btc = self.started()?;
+            let txid = btc.broadcast_raw_transaction(raw_transaction).await?;
+
+            Ok(txid.to_string())
+        })
+    }
+
+    fn height(&self) -> ModuleFuture<'_, u64> {
+        Box::pin(async move {
+            let btc = self.started()?;
+            btc.height().await
+        })
+    }
+
+    fn timestamp(&self) -> ModuleFuture<'_, u64> {
+        Box::pin(async move {
+            let btc = self.started()?;
+            btc.timestamp().await
+        })
+    }
+
+    fn advance_time(&self, duration: Duration) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            let btc = self.started()?;
+            btc.advance_time(duration).await
+        })
+    }
+
+    fn advance_blocks(&self, blocks: u64) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            let btc = self.started()?;
+            btc.advance_blocks(blocks).await
+        })
+    }
+
+    fn snapshot(&self) -> ModuleFuture<'_, ModuleSnapshot> {
+        Box::pin(async move {
+            let btc = self.started()?;
+            let snapshot: ModuleSnapshot = Arc::new(btc.snapshot().await?);
+            Ok(snapshot)
+        })
+    }
+
+    fn revert<'a>(&'a self, snapshot: &'a ModuleSnapshot) -> ModuleFuture<'a, ()> {
+        Box::pin(async move {
+            let snapshot = downcast_snapshot::<BtcSnapshot>(Self::NAME, snapshot)?;
+            let btc = self.started()?;
+            btc.revert(snapshot.clone()).await
+        })
+    }
+
+    fn manifest(&self) -> serde_json::Value {
+        self.context
+            .as_ref()
+            .and_then(|context| serde_json::to_value(BitcoinManifest::new(context)).ok())
+            .unwrap_or_default()
+    }
+}
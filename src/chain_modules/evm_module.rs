@@ -0,0 +1,196 @@
+use crate::chain_module::{downcast_snapshot, ChainModule, ModuleFuture, ModuleSnapshot};
+use crate::clients::get_anvil_instance;
+use crate::contexts::{ChainContext, EVMTestContext};
+use crate::manifest::{EvmManifest, ManifestAccount};
+use crate::prerequisites::BinaryPaths;
+use crate::warm_start::restore_evm_state;
+use alloy::primitives::{Address, U256};
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr as _;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// EVM chain run by Anvil
+#[derive(Debug)]
+pub struct EvmModule {
+    binaries: BinaryPaths,
+    seed: Option<u64>,
+    state_template: Option<PathBuf>, // Anvil state loaded once started, if saved
+    context: Option<Arc<EVMTestContext>>,
+}
+
+impl EvmModule {
+    pub const NAME: &'static str = "evm";
+
+    /// Module spawning Anvil when started
+    pub const fn new(binaries: BinaryPaths, seed: Option<u64>) -> Self {
+        Self {
+            binaries,
+            seed,
+            state_template: None,
+            context: None,
+        }
+    }
+
+    /// Module attached to a running node
+    pub fn attach(context: Arc<EVMTestContext>) -> Self {
+        Self {
+            binaries: BinaryPaths::default(),
+            seed: None,
+            state_template: None,
+            context: Some(context),
+        }
+    }
+
+    pub(crate) fn with_state_template(self, state_template: PathBuf) -> Self {
+        Self {
+            state_template: Some(state_template),
+            ..self
+        }
+    }
+
+    /// Context of the node, once started
+    pub const fn context(&self) -> Option<&Arc<EVMTestContext>> {
+        self.context.as_ref()
+    }
+
+    fn started(&self) -> Result<&EVMTestContext, Box<dyn Error + Send + Sync>> {
+        self.context
+            .as_deref()
+            .ok_or_else(|| "Anvil is not started".into())
+    }
+}
+
+impl ChainModule for EvmModule {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn start(&mut self) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            if self.context.is_some() {
+                return Ok(());
+            }
+
+            let binaries = self.binaries.clone();
+            let seed = self.seed;
+            let context = tokio::task::spawn_blocking(move || {
+                get_anvil_instance(&binaries, seed)
+                    .map(|anvil| EVMTestContext::new_with_seed(anvil, seed))
+                    .map_err(|err| err.to_string())
+            })
+            .await??;
+
+            if let Some(state_template) = &self.state_template {
+                restore_evm_state(state_template, &context).await?;
+            }
+
+            self.context = Some(Arc::new(context));
+            Ok(())
+        })
+    }
+
+    fn is_ready(&self) -> ModuleFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(match &self.context {
+                Some(context) => context.height().await.is_ok(),
+                None => false,
+            })
+        })
+    }
+
+    fn create_funded_account(&self, balance: u128) -> ModuleFuture<'_, ManifestAccount> {
+        Box::pin(async move {
+            let evm = self.started()?;
+            let signer = evm.create_funded_account(U256::from(balance)).await?;
+
+            Ok(ManifestAccount {
+                address: signer.address().to_string(),
+                private_key: format!("0x{}", hex::encode(signer.to_bytes())),
+            })
+        })
+    }
+
+    fn fund<'a>(&'a self, address: &'a str, amount: u128) -> ModuleFuture<'a, ()> {
+        Box::pin(async move {
+            let evm = self.started()?;
+            let address = Address::from_str(address)?;
+
+            // Anvil sets balances, add the amount to the current one
+            let balance = evm.balance(&address).await? + U256::from(amount);
+            evm.fund(address, Some(balance), &[]).await
+        })
+    }
+
+    fn broadcast_raw_transaction<'a>(
+        &'a self,
+        raw_transaction: &'a [u8],
+    ) -> ModuleFuture<'a, String> {
+        Box::pin(async move {
+            let evm = self.started()?;
+            let transaction_hash = evm.broadcast_raw_transaction(raw_transaction).await?;
+
+            Ok(transaction_hash.to_string())
+        })
+    }
+
+    fn height(&self) -> ModuleFuture<'_, u64> {
+        Box::pin(async move {
+            let evm = self.started()?;
+            evm.height().await
+        })
+    }
+
+    fn timestamp(&self) -> ModuleFuture<'_, u64> {
+        Box::pin(async move {
+            let evm = self.started()?;
+            evm.timestamp().await
+        })
+    }
+
+    fn advance_time(&self, duration: Duration) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            let evm = self.started()?;
+            evm.advance_time(duration).await
+        })
+    }
+
+    fn advance_blocks(&self, blocks: u64) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            let evm = self.started()?;
+            evm.advance_blocks(blocks).await
+        })
+    }
+
+    fn snapshot(&self) -> ModuleFuture<'_, ModuleSnapshot> {
+        Box::pin(async move {
+            let evm = self.started()?;
+            let snapshot: ModuleSnapshot = Arc::new(Mutex::new(evm.snapshot().await?));
+            Ok(snapshot)
+        })
+    }
+
+    fn revert<'a>(&'a self, snapshot: &'a ModuleSnapshot) -> ModuleFuture<'a, ()> {
+        Box::pin(async move {
+            let evm = self.started()?;
+            let snapshot = downcast_snapshot::<Mutex<U256>>(Self::NAME, snapshot)?;
+
+            let id = *snapshot.lock().unwrap();
+            evm.revert(id).await?;
+
+            // Anvil consumes its snapshot on revert, take a new one of the same state
+            let id = evm.snapshot().await?;
+            *snapshot.lock().unwrap() = id;
+
+            Ok(())
+        })
+    }
+
+    fn manifest(&self) -> serde_json::Value {
+        self.context
+            .as_ref()
+            .and_then(|context| serde_json::to_value(EvmManifest::new(context)).ok())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,15 @@
+//! Built-in chain modules of an OmniBox
+#[cfg(feature = "bitcoin")]
+mod bitcoin_module;
+#[cfg(feature = "evm")]
+mod evm_module;
+mod near_module;
+
+#[cfg(feature = "bitcoin")]
+pub use bitcoin_module::BitcoinModule;
+#[cfg(feature = "evm")]
+pub use evm_module::EvmModule;
+pub use near_module::NearModule;
+
+/// Names of the built-in modules, reserved even when their network or feature is not enabled
+pub const BUILTIN_MODULES: [&str; 3] = ["bitcoin", "evm", "near"];
@@ -0,0 +1,175 @@
+use crate::chain_module::{downcast_snapshot, ChainModule, ModuleFuture, ModuleSnapshot};
+use crate::contexts::{ChainContext, NearSnapshot, NearTestContext};
+use crate::manifest::{ManifestAccount, NearManifest};
+use crate::prerequisites::BinaryPaths;
+use near_sdk::AccountId;
+use near_workspaces::types::NearToken;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// NEAR sandbox chain
+#[derive(Debug)]
+pub struct NearModule {
+    binaries: BinaryPaths,
+    seed: Option<u64>,
+    rpc_url: String, // Network the contracts are deployed to, exported in the manifest
+    deployer: String, // Account deploying the contracts, exported in the manifest
+    context: Option<Arc<NearTestContext>>,
+}
+
+impl NearModule {
+    pub const NAME: &'static str = "near";
+
+    /// Module spawning the sandbox when started
+    pub const fn new(
+        binaries: BinaryPaths,
+        seed: Option<u64>,
+        rpc_url: String,
+        deployer: String,
+    ) -> Self {
+        Self {
+            binaries,
+            seed,
+            rpc_url,
+            deployer,
+            context: None,
+        }
+    }
+
+    /// Module attached to a running sandbox
+    pub fn attach(context: Arc<NearTestContext>, rpc_url: String, deployer: String) -> Self {
+        Self {
+            binaries: BinaryPaths::default(),
+            seed: None,
+            rpc_url,
+            deployer,
+            context: Some(context),
+        }
+    }
+
+    /// Context of the sandbox, once started
+    pub const fn context(&self) -> Option<&Arc<NearTestContext>> {
+        self.context.as_ref()
+    }
+
+    fn started(&self) -> Result<&NearTestContext, Box<dyn Error + Send + Sync>> {
+        self.context
+            .as_deref()
+            .ok_or_else(|| "The NEAR sandbox is not started".into())
+    }
+}
+
+impl ChainModule for NearModule {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn start(&mut self) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            if self.context.is_none() {
+                let context = NearTestContext::new_with_conf(&self.binaries, self.seed).await;
+                self.context = Some(Arc::new(context));
+            }
+
+            Ok(())
+        })
+    }
+
+    fn is_ready(&self) -> ModuleFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(match &self.context {
+                Some(context) => context.height().await.is_ok(),
+                None => false,
+            })
+        })
+    }
+
+    fn create_funded_account(&self, balance: u128) -> ModuleFuture<'_, ManifestAccount> {
+        Box::pin(async move {
+            let near = self.started()?;
+            let account = near
+                .create_funded_account(NearToken::from_yoctonear(balance))
+                .await?;
+
+            Ok(ManifestAccount::near(&account))
+        })
+    }
+
+    fn fund<'a>(&'a self, address: &'a str, amount: u128) -> ModuleFuture<'a, ()> {
+        Box::pin(async move {
+            let account_id: AccountId = address.parse()?;
+
+            let near = self.started()?;
+            near.fund(&account_id, NearToken::from_yoctonear(amount))
+                .await
+        })
+    }
+
+    fn broadcast_raw_transaction<'a>(
+        &'a self,
+        raw_transaction: &'a [u8],
+    ) -> ModuleFuture<'a, String> {
+        Box::pin(async move {
+            let near = self.started()?;
+            let transaction_hash = near.broadcast_raw_transaction(raw_transaction).await?;
+
+            Ok(transaction_hash.to_string())
+        })
+    }
+
+    fn height(&self) -> ModuleFuture<'_, u64> {
+        Box::pin(async move {
+            let near = self.started()?;
+            near.height().await
+        })
+    }
+
+    fn timestamp(&self) -> ModuleFuture<'_, u64> {
+        Box::pin(async move {
+            let near = self.started()?;
+            near.timestamp().await
+        })
+    }
+
+    fn advance_time(&self, duration: Duration) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            let near = self.started()?;
+            near.advance_time(duration).await
+        })
+    }
+
+    fn advance_blocks(&self, blocks: u64) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            let near = self.started()?;
+            near.advance_blocks(blocks).await
+        })
+    }
+
+    fn snapshot(&self) -> ModuleFuture<'_, ModuleSnapshot> {
+        Box::pin(async move {
+            let near = self.started()?;
+            let snapshot: ModuleSnapshot = Arc::new(near.snapshot().await?);
+            Ok(snapshot)
+        })
+    }
+
+    fn revert<'a>(&'a self, snapshot: &'a ModuleSnapshot) -> ModuleFuture<'a, ()> {
+        Box::pin(async move {
+            let snapshot = downcast_snapshot::<NearSnapshot>(Self::NAME, snapshot)?;
+            let near = self.started()?;
+            near.revert(snapshot.clone()).await
+        })
+    }
+
+    fn manifest(&self) -> serde_json::Value {
+        self.context
+            .as_ref()
+            .and_then(|context| {
+                let manifest =
+                    NearManifest::new(context, self.rpc_url.clone(), self.deployer.clone());
+                serde_json::to_value(manifest).ok()
+            })
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,47 @@
+//! Heights and timestamps of every chain run by an OmniBox
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Index;
+
+/// Height and time of a chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainTime {
+    pub height: u64,
+    pub timestamp: u64, // Unix timestamp in seconds, the median time past on Bitcoin
+}
+
+/// Heights and times of every chain module, reported after moving them forward
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainTimes {
+    pub chains: BTreeMap<String, ChainTime>, // By chain module name
+}
+
+impl ChainTimes {
+    /// Time of the chain module `name`
+    pub fn get(&self, name: &str) -> Option<ChainTime> {
+        self.chains.get(name).copied()
+    }
+}
+
+impl Index<&str> for ChainTimes {
+    type Output = ChainTime;
+
+    fn index(&self, name: &str) -> &ChainTime {
+        self.chains
+            .get(name)
+            .unwrap_or_else(|| panic!("No chain module named {}", name))
+    }
+}
+
+impl fmt::Display for ChainTimes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (chain, time) in &self.chains {
+            writeln!(
+                f,
+                "{:<8} height {:>8}  timestamp {}",
+                chain, time.height, time.timestamp
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,20 @@
+use crate::prerequisites::{self, BinaryPaths, Prerequisite};
 use alloy::node_bindings::Anvil;
 use alloy::node_bindings::AnvilInstance;
 
-pub fn get_anvil_instance() -> Result<AnvilInstance, Box<dyn std::error::Error>> {
+/// Spawns Anvil, deriving its dev accounts from `seed` instead of the default test mnemonic
+pub fn get_anvil_instance(
+    binaries: &BinaryPaths,
+    seed: Option<u64>,
+) -> Result<AnvilInstance, Box<dyn std::error::Error + Send + Sync>> {
+    let exe_path = prerequisites::resolve(Prerequisite::Anvil, binaries)?
+        .ok_or("anvil was not resolved to a path")?;
+
     // Spin up a local Anvil node.
-    let anvil = Anvil::new().block_time(1).try_spawn()?;
+    let mut anvil = Anvil::at(exe_path).block_time(1);
+    if let Some(seed) = seed {
+        anvil = anvil.args(["--mnemonic-seed-unsafe".to_string(), seed.to_string()]);
+    }
 
-    Ok(anvil)
+    Ok(anvil.try_spawn()?)
 }
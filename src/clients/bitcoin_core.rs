@@ -1,14 +1,19 @@
-pub fn get_bitcoin_instance() -> Result<bitcoind::BitcoinD, Box<dyn std::error::Error>> {
-    bitcoind::exe_path().map_or_else(
-        |_| {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "BitcoinD executable not found",
-            )) as Box<dyn std::error::Error>)
-        },
-        |exe_path| {
-            let bitcoind = bitcoind::BitcoinD::new(exe_path).unwrap();
-            Ok(bitcoind)
-        },
-    )
+use crate::prerequisites::{self, BinaryPaths, Prerequisite};
+use std::path::Path;
+
+/// Spawns bitcoind, in a temporary data directory unless `datadir` is given
+pub fn get_bitcoin_instance(
+    binaries: &BinaryPaths,
+    datadir: Option<&Path>,
+) -> Result<bitcoind::BitcoinD, Box<dyn std::error::Error + Send + Sync>> {
+    let exe_path = prerequisites::resolve(Prerequisite::Bitcoind, binaries)?
+        .ok_or("bitcoind was not resolved to a path")?;
+
+    let mut conf = bitcoind::Conf::default();
+    conf.staticdir = datadir.map(Path::to_path_buf);
+
+    let bitcoind = bitcoind::BitcoinD::with_conf(&exe_path, &conf)
+        .map_err(|err| format!("Failed to start {}: {}", exe_path.display(), err))?;
+
+    Ok(bitcoind)
 }
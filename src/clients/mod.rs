@@ -1,7 +1,13 @@
+#[cfg(feature = "evm")]
 mod anvil;
+#[cfg(feature = "bitcoin")]
 mod bitcoin_core;
+#[cfg(feature = "near-sandbox")]
 mod near;
 
+#[cfg(feature = "evm")]
 pub use anvil::get_anvil_instance;
+#[cfg(feature = "bitcoin")]
 pub use bitcoin_core::get_bitcoin_instance;
+#[cfg(feature = "near-sandbox")]
 pub use near::get_near_instance;
@@ -1,6 +1,18 @@
+use crate::prerequisites::{self, BinaryPaths, Prerequisite, NEAR_SANDBOX_ENV};
 use near_workspaces::{network::Sandbox, sandbox, Worker};
 
-pub async fn get_near_instance() -> Result<Worker<Sandbox>, Box<dyn std::error::Error>> {
+/// Starts the NEAR sandbox from the configured binary, or the one downloaded by near-workspaces
+pub async fn get_near_instance(
+    binaries: &BinaryPaths,
+) -> Result<Worker<Sandbox>, Box<dyn std::error::Error>> {
+    // near-workspaces only reads the sandbox binary from the environment, as near-sandbox-utils
+    // does when it installs one, so a resolved binary is passed through the same variable
+    if let Some(exe_path) = prerequisites::resolve(Prerequisite::NearSandbox, binaries)? {
+        if std::env::var_os(NEAR_SANDBOX_ENV).as_deref() != Some(exe_path.as_os_str()) {
+            std::env::set_var(NEAR_SANDBOX_ENV, exe_path);
+        }
+    }
+
     // Spin up a local Near node.
     let sandbox_worker: Worker<Sandbox> = sandbox().await?;
 
@@ -1,15 +1,42 @@
 use crate::clients::get_bitcoin_instance;
 
+use crate::contexts::{whole_seconds, ChainContext};
+use crate::prerequisites::BinaryPaths;
+use crate::seed::derive_secret;
+#[cfg(feature = "mpc-utils")]
 use crate::utils::address::DerivedAddress;
 use bitcoin::bip32::DerivationPath;
+use bitcoin::consensus::encode::{deserialize_hex, serialize_hex};
 use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
-use bitcoin::{bip32::Xpriv, Address, Network, ScriptBuf};
+use bitcoin::{bip32::Xpriv, Address, Amount, BlockHash, Network, ScriptBuf, Transaction, Txid};
 use bitcoin::{CompressedPublicKey, PublicKey as BitcoinPublicKey, WPubkeyHash};
 use bitcoind::AddressType;
 use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
 use std::str::FromStr as _;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
 
-use super::types::{ListUnspentResult, ScanTxOutSetResult, UnspentOutput};
+#[cfg(feature = "mpc-utils")]
+use super::types::UnspentOutput;
+use super::types::{ListUnspentResult, ScanTxOutSetResult};
+
+/// Fee reserved for each funding transaction sent by the node wallet
+const FEE_MARGIN_SAT: u64 = 10_000;
+
+/// Number of blocks whose median timestamp is the median time past checked by time locks
+const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Default `-mempoolexpiry` of bitcoind, entries older than it are dropped from the mempool
+const MEMPOOL_EXPIRY: Duration = Duration::from_secs(336 * 3600);
+
+/// Anyone can spend output paying the coinbase of the blocks mined only to expire the mempool
+const EXPIRY_BLOCK_OUTPUT: &str = "raw(51)";
+
+/// Number of addresses of each descriptor imported from the seed
+const SEEDED_DESCRIPTOR_RANGE: u32 = 1000;
 
 #[derive(Debug, Clone)]
 pub struct UserInfo {
@@ -22,6 +49,14 @@ pub struct UserInfo {
     pub wpkh: WPubkeyHash,
 }
 
+/// Chain tip and mempool captured by a snapshot
+#[derive(Debug, Clone)]
+pub struct BtcSnapshot {
+    pub height: u64,
+    pub tip: BlockHash,
+    pub mempool: Vec<Transaction>, // Parents before their children
+}
+
 #[derive(Debug)]
 pub struct BTCTestContext {
     pub bitcoind_instance: bitcoind::BitcoinD,
@@ -31,18 +66,36 @@ pub struct BTCTestContext {
     pub alice_segwit: UserInfo,
     pub bob_legacy: UserInfo,
     pub bob_segwit: UserInfo,
+    time_offset: AtomicU64, // Seconds the node clock runs ahead of the wall clock
+    seed: Option<u64>,      // Mined blocks go to addresses derived from it
+    mining_addresses: AtomicU32,
 }
 
 impl Default for BTCTestContext {
     fn default() -> Self {
-        let bitcoind: bitcoind::BitcoinD = get_bitcoin_instance().unwrap();
+        let bitcoind: bitcoind::BitcoinD = get_bitcoin_instance(&BinaryPaths::default(), None)
+            .unwrap_or_else(|err| panic!("{}", err));
         Self::new(bitcoind).unwrap()
     }
 }
 
 impl BTCTestContext {
-    pub fn new(bitcoind_instance: bitcoind::BitcoinD) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new(
+        bitcoind_instance: bitcoind::BitcoinD,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::new_with_seed(bitcoind_instance, None)
+    }
+
+    /// Sets up the accounts, from a wallet seed derived from `seed` when one is given
+    pub fn new_with_seed(
+        bitcoind_instance: bitcoind::BitcoinD,
+        seed: Option<u64>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        info!(url = %bitcoind_instance.rpc_url(), "bitcoind started");
         let client = &bitcoind_instance.client;
+        if let Some(seed) = seed {
+            Self::import_seed(client, seed)?;
+        }
         let master_key_p2pkh = Self::get_master_key_of_regtest_node_p2pkh(client)?;
         let master_key_p2wpkh = Self::get_master_key_of_regtest_node_p2wpkh(client)?;
 
@@ -63,6 +116,9 @@ impl BTCTestContext {
             alice_segwit,
             bob_legacy,
             bob_segwit,
+            time_offset: AtomicU64::new(0),
+            seed,
+            mining_addresses: AtomicU32::new(0),
         })
     }
 
@@ -74,7 +130,7 @@ impl BTCTestContext {
         client: &bitcoind::Client,
         master_key_p2pkh_or_p2wpkh: Xpriv,
         address_type: AddressType,
-    ) -> Result<UserInfo, Box<dyn std::error::Error>> {
+    ) -> Result<UserInfo, Box<dyn std::error::Error + Send + Sync>> {
         let address = client
             .get_new_address_with_type(address_type.clone())
             .unwrap()
@@ -154,7 +210,7 @@ impl BTCTestContext {
     pub fn create_account(
         &self,
         address_type: AddressType,
-    ) -> Result<UserInfo, Box<dyn std::error::Error>> {
+    ) -> Result<UserInfo, Box<dyn std::error::Error + Send + Sync>> {
         let client = self.client();
         let master_key = if address_type == AddressType::Bech32 {
             self.master_key_p2wpkh
@@ -164,32 +220,239 @@ impl BTCTestContext {
         Self::setup_account(client, master_key, address_type)
     }
 
+    #[cfg(feature = "mpc-utils")]
     pub fn generate_to_derived_address(
         &self,
         derived_address: &DerivedAddress,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let near_contract_address =
             bitcoin::Address::from_str(&derived_address.address.to_string())?;
         let near_contract_address = near_contract_address
             .require_network(Network::Regtest)
             .unwrap();
 
+        self.sync_mock_time()?;
         self.client()
             .generate_to_address(101, &near_contract_address)?;
 
         Ok(())
     }
 
+    /// Create one confirmed UTXO of each amount for the given address, paid by the node wallet
+    pub fn fund_address(
+        &self,
+        address: &Address,
+        utxos: &[Amount],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if utxos.is_empty() {
+            return Ok(());
+        }
+
+        let client = self.client();
+
+        // The node wallet pays for the UTXOs and the fees
+        self.mine_mature_coins(
+            utxos.iter().copied().sum::<Amount>()
+                + Amount::from_sat(utxos.len() as u64 * FEE_MARGIN_SAT),
+        )?;
+
+        for amount in utxos {
+            let txid: String = client.call(
+                "sendtoaddress",
+                &[json!(address.to_string()), json!(amount.to_btc())],
+            )?;
+            debug!(%address, %amount, txid = %txid, "Created BTC UTXO");
+        }
+
+        // Confirm the new UTXOs
+        self.mine(1)?;
+
+        Ok(())
+    }
+
+    /// Mines until the node wallet holds at least `needed` in mature coins
+    pub fn mine_mature_coins(
+        &self,
+        needed: Amount,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let client = self.client();
+
+        loop {
+            let balance: f64 = client.call("getbalance", &[])?;
+            if Amount::from_btc(balance)? >= needed {
+                return Ok(());
+            }
+            self.mine(101)?;
+        }
+    }
+
+    /// Mines blocks to the node wallet, at the clock of the node
+    fn mine(&self, blocks: usize) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.sync_mock_time()?;
+        self.client()
+            .generate_to_address(blocks, &self.wallet_address()?)?;
+
+        Ok(())
+    }
+
+    /// Sets the mock time of the node to the wall clock, shifted by the time advanced so far
+    ///
+    /// A fixed mock time would stop the clock of the node, every later block would get the same
+    /// timestamp.
+    fn sync_mock_time(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let offset = self.time_offset.load(Ordering::SeqCst);
+        if offset > 0 {
+            let _: Value = self
+                .client()
+                .call("setmocktime", &[json!(unix_time()? + offset)])?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops every mempool entry
+    ///
+    /// bitcoind has no RPC removing mempool entries, but a reorg expires the ones older than the
+    /// mempool expiry. An empty block is mined at a mock time past the expiry, then invalidated.
+    fn clear_mempool(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let client = self.client();
+        let expired_at = unix_time()?
+            + self.time_offset.load(Ordering::SeqCst)
+            + MEMPOOL_EXPIRY.as_secs()
+            + 3600;
+
+        let _: Value = client.call("setmocktime", &[json!(expired_at)])?;
+        let reorg = client
+            .call::<Value>("generateblock", &[json!(EXPIRY_BLOCK_OUTPUT), json!([])])
+            .and_then(|block| client.call::<Value>("invalidateblock", &[block["hash"].clone()]));
+
+        // Put the clock of the node back before reporting a failed reorg
+        let offset = self.time_offset.load(Ordering::SeqCst);
+        let mock_time = if offset > 0 { unix_time()? + offset } else { 0 };
+        let _: Value = client.call("setmocktime", &[json!(mock_time)])?;
+        reorg?;
+
+        let mempool: Vec<String> = client.call("getrawmempool", &[])?;
+        if !mempool.is_empty() {
+            return Err(format!("{} transactions are left in the mempool", mempool.len()).into());
+        }
+
+        Ok(())
+    }
+
+    /// Abandons the wallet transactions since `block` that are neither mined nor in the mempool
+    ///
+    /// Their coins can then be spent again and they no longer count in the wallet balance.
+    fn abandon_dropped_transactions(
+        &self,
+        block: &BlockHash,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let client = self.client();
+        let since: Value = client.call("listsinceblock", &[json!(block.to_string())])?;
+        let mempool: HashSet<String> = client
+            .call::<Vec<String>>("getrawmempool", &[])?
+            .into_iter()
+            .collect();
+
+        let dropped: BTreeSet<&str> = since["transactions"]
+            .as_array()
+            .ok_or("Missing transactions in listsinceblock")?
+            .iter()
+            .filter(|transaction| {
+                transaction["confirmations"] == 0
+                    && transaction["generated"] != true
+                    && transaction["abandoned"] != true
+            })
+            .filter_map(|transaction| transaction["txid"].as_str())
+            .filter(|txid| !mempool.contains(*txid))
+            .collect();
+
+        for txid in dropped {
+            debug!(txid, "Abandoning BTC transaction sent after the snapshot");
+            let _: Value = client.call("abandontransaction", &[json!(txid)])?;
+        }
+
+        Ok(())
+    }
+
+    /// Address of the node wallet receiving mined blocks
+    ///
+    /// With a seed, it is derived from the change descriptor and a counter, so mining leaves the
+    /// receiving addresses handed out to the accounts untouched.
+    fn wallet_address(&self) -> Result<Address, Box<dyn std::error::Error + Send + Sync>> {
+        if self.seed.is_none() {
+            return Ok(self
+                .client()
+                .get_new_address_with_type(AddressType::Bech32)?
+                .address()?
+                .require_network(Network::Regtest)?);
+        }
+
+        let index = self.mining_addresses.fetch_add(1, Ordering::SeqCst) % SEEDED_DESCRIPTOR_RANGE;
+        seeded_mining_address(&self.master_key_p2wpkh, index)
+    }
+
+    /// Replaces the active descriptors of the node wallet with ones derived from the seed
+    fn import_seed(
+        client: &bitcoind::Client,
+        seed: u64,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let master_key = Xpriv::new_master(Network::Regtest, &derive_secret(seed, "bitcoin"))?;
+
+        let descriptors = [
+            (format!("pkh({}/44h/1h/0h/0/*)", master_key), false),
+            (format!("pkh({}/44h/1h/0h/1/*)", master_key), true),
+            (format!("wpkh({}/84h/1h/0h/0/*)", master_key), false),
+            (format!("wpkh({}/84h/1h/0h/1/*)", master_key), true),
+        ]
+        .into_iter()
+        .map(|(desc, internal)| {
+            // The node only accepts descriptors with their checksum
+            let info: Value = client.call("getdescriptorinfo", &[json!(desc)])?;
+            let checksum = info["checksum"]
+                .as_str()
+                .ok_or("Missing descriptor checksum")?;
+
+            Ok(json!({
+                "desc": format!("{}#{}", desc, checksum),
+                "active": true,
+                "internal": internal,
+                "range": [0, SEEDED_DESCRIPTOR_RANGE],
+                "timestamp": "now",
+            }))
+        })
+        .collect::<Result<Vec<Value>, Box<dyn std::error::Error + Send + Sync>>>()?;
+
+        let results: Value = client.call("importdescriptors", &[json!(descriptors)])?;
+        if let Some(failed) = results
+            .as_array()
+            .and_then(|results| results.iter().find(|result| result["success"] != true))
+        {
+            return Err(format!("Failed to import the seeded descriptors: {}", failed).into());
+        }
+        debug!(seed, "Imported the seeded wallet descriptors");
+
+        Ok(())
+    }
+
+    /// Whether a descriptor listed by the node is the active one for receiving addresses
+    fn is_active_receive_descriptor(descriptor: &Value) -> bool {
+        descriptor["active"] == true && descriptor["internal"] != true
+    }
+
     fn get_master_key_of_regtest_node_p2pkh(
         client: &bitcoind::Client,
-    ) -> Result<Xpriv, Box<dyn std::error::Error>> {
+    ) -> Result<Xpriv, Box<dyn std::error::Error + Send + Sync>> {
         let descriptors: Value = client.call("listdescriptors", &[true.into()])?;
 
         let p2pkh_descriptor = descriptors["descriptors"]
             .as_array()
             .unwrap()
             .iter()
-            .find(|descriptor| descriptor["desc"].as_str().unwrap().contains("pkh"))
+            .find(|descriptor| {
+                Self::is_active_receive_descriptor(descriptor)
+                    && descriptor["desc"].as_str().unwrap().starts_with("pkh(")
+            })
             .expect("No P2PKH descriptor found");
 
         let desc = p2pkh_descriptor["desc"].as_str().unwrap();
@@ -203,7 +466,7 @@ impl BTCTestContext {
 
     fn get_master_key_of_regtest_node_p2wpkh(
         client: &bitcoind::Client,
-    ) -> Result<Xpriv, Box<dyn std::error::Error>> {
+    ) -> Result<Xpriv, Box<dyn std::error::Error + Send + Sync>> {
         let descriptors: Value = client.call("listdescriptors", &[true.into()])?;
 
         let p2wpkh_descriptor = descriptors["descriptors"]
@@ -211,8 +474,9 @@ impl BTCTestContext {
             .unwrap()
             .iter()
             .find(|descriptor| {
+                // Exclude descriptors for taproot and nested segwit
                 let desc = descriptor["desc"].as_str().unwrap();
-                desc.contains("wpkh") && !desc.starts_with("tr(") // Exclude descriptors for taproot
+                Self::is_active_receive_descriptor(descriptor) && desc.starts_with("wpkh(")
             })
             .expect("No P2WPKH or nested P2WPKH descriptor found");
 
@@ -241,10 +505,11 @@ impl BTCTestContext {
         Ok(master_key)
     }
 
+    #[cfg(feature = "mpc-utils")]
     pub fn scan_utxo_for_address(
         &self,
         address: &DerivedAddress,
-    ) -> Result<Vec<UnspentOutput>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<UnspentOutput>, Box<dyn std::error::Error + Send + Sync>> {
         let near_contract_address = bitcoin::Address::from_str(&address.address.to_string())?;
         let near_contract_address = near_contract_address
             .require_network(Network::Regtest)
@@ -261,11 +526,12 @@ impl BTCTestContext {
         Ok(scan_txout_set_result.unspents)
     }
 
+    #[cfg(feature = "mpc-utils")]
     pub fn scan_utxo_for_address_with_count(
         &self,
         address: &DerivedAddress,
         count: usize,
-    ) -> Result<Vec<UnspentOutput>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<UnspentOutput>, Box<dyn std::error::Error + Send + Sync>> {
         let unspents = self.scan_utxo_for_address(address)?;
 
         // Obtener la cantidad solicitada de elementos
@@ -277,7 +543,7 @@ impl BTCTestContext {
     pub fn get_utxo_for_address(
         &self,
         address: &Address,
-    ) -> Result<Vec<ListUnspentResult>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<ListUnspentResult>, Box<dyn std::error::Error + Send + Sync>> {
         let min_conf = 1;
         let max_conf = 9999999;
         let include_unsafe = true;
@@ -334,3 +600,269 @@ impl BTCTestContext {
         &self.bob_segwit
     }
 }
+
+impl ChainContext for BTCTestContext {
+    type Account = UserInfo;
+    type Address = Address;
+    type Balance = Amount;
+    type TxHash = Txid;
+    type SnapshotId = BtcSnapshot;
+
+    async fn create_funded_account(
+        &self,
+        balance: Amount,
+    ) -> Result<UserInfo, Box<dyn Error + Send + Sync>> {
+        let account = self.create_account(AddressType::Bech32)?;
+        if balance > Amount::ZERO {
+            self.fund_address(&account.address, &[balance])?;
+        }
+
+        Ok(account)
+    }
+
+    fn account_address(&self, account: &UserInfo) -> Address {
+        account.address.clone()
+    }
+
+    async fn balance(&self, address: &Address) -> Result<Amount, Box<dyn Error + Send + Sync>> {
+        let scan_txout_set_result: ScanTxOutSetResult = self.client().call(
+            "scantxoutset",
+            &[
+                json!("start"),
+                json!([{ "desc": format!("addr({})", address) }]),
+            ],
+        )?;
+
+        Ok(Amount::from_btc(scan_txout_set_result.total_amount)?)
+    }
+
+    async fn advance_blocks(&self, blocks: u64) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.mine(blocks as usize)?;
+
+        Ok(())
+    }
+
+    async fn broadcast_raw_transaction(
+        &self,
+        raw_transaction: &[u8],
+    ) -> Result<Txid, Box<dyn Error + Send + Sync>> {
+        let raw_transaction_hex: String = raw_transaction
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let txid: String = self
+            .client()
+            .call("sendrawtransaction", &[json!(raw_transaction_hex)])?;
+
+        // Confirm the transaction
+        self.mine(1)?;
+        debug!(txid = %txid, "BTC transaction confirmed");
+
+        Ok(Txid::from_str(&txid)?)
+    }
+
+    async fn height(&self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        Ok(self.client().call("getblockcount", &[])?)
+    }
+
+    /// Median time past of the tip, the time compared against time locks
+    async fn timestamp(&self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let info: Value = self.client().call("getblockchaininfo", &[])?;
+
+        Ok(info["mediantime"]
+            .as_u64()
+            .ok_or("Missing median time in the blockchain info")?)
+    }
+
+    async fn advance_time(&self, duration: Duration) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let info: Value = self.client().call("getblockchaininfo", &[])?;
+        let tip_time = info["time"]
+            .as_u64()
+            .ok_or("Missing tip time in the blockchain info")?;
+        let now = unix_time()?;
+        let clock = now + self.time_offset.load(Ordering::SeqCst);
+        let target = tip_time.max(clock) + whole_seconds(duration);
+
+        // Blocks mined at the new time move the median time past to it
+        self.time_offset.store(target - now, Ordering::SeqCst);
+        self.mine(MEDIAN_TIME_SPAN)?;
+        debug!(target, "Advanced BTC time");
+
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> Result<BtcSnapshot, Box<dyn Error + Send + Sync>> {
+        let client = self.client();
+        let height = self.height().await?;
+        let tip: String = client.call("getblockhash", &[json!(height)])?;
+
+        // Entries with fewer ancestors in the mempool come first, so parents are submitted first
+        let entries: serde_json::Map<String, Value> =
+            client.call("getrawmempool", &[json!(true)])?;
+        let mut entries: Vec<(u64, &String)> = entries
+            .iter()
+            .map(|(txid, entry)| (entry["ancestorcount"].as_u64().unwrap_or(1), txid))
+            .collect();
+        entries.sort();
+
+        let mempool = entries
+            .into_iter()
+            .map(|(_, txid)| {
+                let raw_transaction: String = client.call("getrawtransaction", &[json!(txid)])?;
+                Ok(deserialize_hex(&raw_transaction)?)
+            })
+            .collect::<Result<_, Box<dyn Error + Send + Sync>>>()?;
+
+        Ok(BtcSnapshot {
+            height,
+            tip: BlockHash::from_str(&tip)?,
+            mempool,
+        })
+    }
+
+    /// Invalidates the blocks mined after the snapshot and drops the transactions sent since
+    ///
+    /// The mempool is cleared and refilled with the transactions of the snapshot, the wallet
+    /// transactions left out of both are abandoned so their coins can be spent again.
+    async fn revert(&self, snapshot: BtcSnapshot) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let client = self.client();
+
+        if self.height().await? > snapshot.height {
+            let first_block: String = client.call("getblockhash", &[json!(snapshot.height + 1)])?;
+            let _: Value = client.call("invalidateblock", &[json!(first_block)])?;
+        }
+
+        let tip: String = client.call("getbestblockhash", &[])?;
+        if BlockHash::from_str(&tip)? != snapshot.tip {
+            return Err(format!(
+                "Failed to revert Bitcoin to block {}, tip is {}",
+                snapshot.tip, tip
+            )
+            .into());
+        }
+
+        // Transactions of the invalidated blocks are back in the mempool
+        let snapshot_txids: HashSet<Txid> = snapshot
+            .mempool
+            .iter()
+            .map(Transaction::compute_txid)
+            .collect();
+        let mempool: Vec<String> = client.call("getrawmempool", &[])?;
+        if mempool
+            .iter()
+            .any(|txid| Txid::from_str(txid).map_or(true, |txid| !snapshot_txids.contains(&txid)))
+        {
+            self.clear_mempool()?;
+            for transaction in &snapshot.mempool {
+                let _: String = client.call(
+                    "sendrawtransaction",
+                    &[json!(serialize_hex(transaction)), json!(0)],
+                )?;
+            }
+        }
+
+        self.abandon_dropped_transactions(&snapshot.tip)?;
+
+        Ok(())
+    }
+}
+
+/// P2WPKH address at `index` of the change descriptor imported from the seed
+fn seeded_mining_address(
+    master_key: &Xpriv,
+    index: u32,
+) -> Result<Address, Box<dyn Error + Send + Sync>> {
+    let secp = Secp256k1::new();
+    let path = DerivationPath::from_str(&format!("m/84h/1h/0h/1/{}", index))?;
+    let child = master_key.derive_priv(&secp, &path)?;
+    let public_key = CompressedPublicKey::from_private_key(&secp, &child.to_priv())?;
+
+    Ok(Address::p2wpkh(&public_key, Network::Regtest))
+}
+
+fn unix_time() -> Result<u64, Box<dyn Error + Send + Sync>> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_mining_address() {
+        let master_key = Xpriv::new_master(Network::Regtest, &derive_secret(7, "bitcoin")).unwrap();
+
+        assert_eq!(
+            seeded_mining_address(&master_key, 0).unwrap(),
+            seeded_mining_address(&master_key, 0).unwrap()
+        );
+        assert_ne!(
+            seeded_mining_address(&master_key, 0).unwrap(),
+            seeded_mining_address(&master_key, 1).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs bitcoind"]
+    async fn test_fund_address_mines_until_the_coins_are_mature() {
+        let btc = BTCTestContext::default();
+        let account = btc.create_account(AddressType::Bech32).unwrap();
+
+        // A block reward of 50 BTC is not enough, more coinbases have to mature
+        let utxos = [
+            Amount::from_btc(40.0).unwrap(),
+            Amount::from_btc(40.0).unwrap(),
+        ];
+        btc.fund_address(&account.address, &utxos).unwrap();
+
+        assert_eq!(
+            btc.balance(&account.address).await.unwrap(),
+            Amount::from_btc(80.0).unwrap()
+        );
+        let unspent: Vec<Value> = btc
+            .client()
+            .call(
+                "listunspent",
+                &[
+                    json!(1),
+                    json!(9_999_999),
+                    json!([account.address.to_string()]),
+                ],
+            )
+            .unwrap();
+        assert_eq!(unspent.len(), 2);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs bitcoind"]
+    async fn test_same_seed_creates_the_same_accounts() {
+        let binaries = BinaryPaths::default();
+        let first =
+            BTCTestContext::new_with_seed(get_bitcoin_instance(&binaries, None).unwrap(), Some(7))
+                .unwrap();
+        let second =
+            BTCTestContext::new_with_seed(get_bitcoin_instance(&binaries, None).unwrap(), Some(7))
+                .unwrap();
+
+        assert_eq!(first.alice_segwit.address, second.alice_segwit.address);
+        assert_eq!(first.bob_legacy.address, second.bob_legacy.address);
+
+        // Only the first context mines in between, the accounts must not depend on it
+        let account = first.create_funded_account(Amount::ZERO).await.unwrap();
+        let other = second.create_funded_account(Amount::ZERO).await.unwrap();
+        assert_eq!(account.address, other.address);
+
+        first.advance_blocks(5).await.unwrap();
+        let account = first
+            .create_funded_account(Amount::from_sat(10_000))
+            .await
+            .unwrap();
+        let other = second.create_funded_account(Amount::ZERO).await.unwrap();
+        assert_eq!(account.address, other.address);
+        assert_eq!(account.private_key, other.private_key);
+
+        // The node wallet owns the mined coins
+        let balance: f64 = first.client().call("getbalance", &[]).unwrap();
+        assert!(balance > 0.0);
+    }
+}
@@ -1,4 +1,4 @@
 pub mod btc_context;
 pub mod types;
 
-pub use btc_context::BTCTestContext;
+pub use btc_context::{BTCTestContext, BtcSnapshot, UserInfo};
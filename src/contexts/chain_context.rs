@@ -0,0 +1,155 @@
+use std::error::Error;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Common operations offered by the BTC, EVM and NEAR test contexts
+///
+/// Chain agnostic helpers and parametrized tests can be written once against this trait,
+/// each context keeps its own account, address, balance and transaction types. The returned
+/// futures are `Send`, so they can be spawned on a multi-threaded runtime.
+pub trait ChainContext {
+    type Account;
+    type Address;
+    type Balance;
+    type TxHash;
+    type SnapshotId;
+
+    /// Create a new account holding the given balance
+    fn create_funded_account(
+        &self,
+        balance: Self::Balance,
+    ) -> impl Future<Output = Result<Self::Account, Box<dyn Error + Send + Sync>>> + Send;
+
+    /// Address of an account created by this context
+    fn account_address(&self, account: &Self::Account) -> Self::Address;
+
+    /// Current balance of an address
+    fn balance(
+        &self,
+        address: &Self::Address,
+    ) -> impl Future<Output = Result<Self::Balance, Box<dyn Error + Send + Sync>>> + Send;
+
+    /// Advance the chain by the given number of blocks
+    fn advance_blocks(
+        &self,
+        blocks: u64,
+    ) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send;
+
+    /// Broadcast a raw signed transaction and wait until it is confirmed
+    fn broadcast_raw_transaction(
+        &self,
+        raw_transaction: &[u8],
+    ) -> impl Future<Output = Result<Self::TxHash, Box<dyn Error + Send + Sync>>> + Send;
+
+    /// Current height of the chain
+    fn height(&self) -> impl Future<Output = Result<u64, Box<dyn Error + Send + Sync>>> + Send;
+
+    /// Unix timestamp in seconds of the chain, as seen by its time locks
+    fn timestamp(&self) -> impl Future<Output = Result<u64, Box<dyn Error + Send + Sync>>> + Send;
+
+    /// Move the chain time forward by at least the given duration
+    fn advance_time(
+        &self,
+        duration: Duration,
+    ) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send;
+
+    /// Take a snapshot of the chain state
+    fn snapshot(
+        &self,
+    ) -> impl Future<Output = Result<Self::SnapshotId, Box<dyn Error + Send + Sync>>> + Send;
+
+    /// Revert the chain state to a previous snapshot
+    fn revert(
+        &self,
+        snapshot: Self::SnapshotId,
+    ) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send;
+}
+
+/// Contexts shared behind an `Arc`, as handed out by `OmniBox`, forward to the context
+impl<C: ChainContext> ChainContext for Arc<C> {
+    type Account = C::Account;
+    type Address = C::Address;
+    type Balance = C::Balance;
+    type TxHash = C::TxHash;
+    type SnapshotId = C::SnapshotId;
+
+    fn create_funded_account(
+        &self,
+        balance: Self::Balance,
+    ) -> impl Future<Output = Result<Self::Account, Box<dyn Error + Send + Sync>>> + Send {
+        (**self).create_funded_account(balance)
+    }
+
+    fn account_address(&self, account: &Self::Account) -> Self::Address {
+        (**self).account_address(account)
+    }
+
+    fn balance(
+        &self,
+        address: &Self::Address,
+    ) -> impl Future<Output = Result<Self::Balance, Box<dyn Error + Send + Sync>>> + Send {
+        (**self).balance(address)
+    }
+
+    fn advance_blocks(
+        &self,
+        blocks: u64,
+    ) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send {
+        (**self).advance_blocks(blocks)
+    }
+
+    fn broadcast_raw_transaction(
+        &self,
+        raw_transaction: &[u8],
+    ) -> impl Future<Output = Result<Self::TxHash, Box<dyn Error + Send + Sync>>> + Send {
+        (**self).broadcast_raw_transaction(raw_transaction)
+    }
+
+    fn height(&self) -> impl Future<Output = Result<u64, Box<dyn Error + Send + Sync>>> + Send {
+        (**self).height()
+    }
+
+    fn timestamp(&self) -> impl Future<Output = Result<u64, Box<dyn Error + Send + Sync>>> + Send {
+        (**self).timestamp()
+    }
+
+    fn advance_time(
+        &self,
+        duration: Duration,
+    ) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send {
+        (**self).advance_time(duration)
+    }
+
+    fn snapshot(
+        &self,
+    ) -> impl Future<Output = Result<Self::SnapshotId, Box<dyn Error + Send + Sync>>> + Send {
+        (**self).snapshot()
+    }
+
+    fn revert(
+        &self,
+        snapshot: Self::SnapshotId,
+    ) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send {
+        (**self).revert(snapshot)
+    }
+}
+
+/// Whole seconds covering the duration, chains counting in seconds advance by at least it
+#[cfg(any(feature = "bitcoin", feature = "evm"))]
+pub const fn whole_seconds(duration: Duration) -> u64 {
+    duration.as_nanos().div_ceil(1_000_000_000) as u64
+}
+
+#[cfg(all(test, any(feature = "bitcoin", feature = "evm")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whole_seconds_rounds_up() {
+        assert_eq!(whole_seconds(Duration::ZERO), 0);
+        assert_eq!(whole_seconds(Duration::from_millis(1)), 1);
+        assert_eq!(whole_seconds(Duration::from_secs(2)), 2);
+        assert_eq!(whole_seconds(Duration::from_millis(2500)), 3);
+    }
+}
@@ -1,18 +1,29 @@
 use alloy::{
+    eips::BlockNumberOrTag,
     network::{Ethereum, EthereumWallet},
     node_bindings::AnvilInstance,
+    primitives::{keccak256, Address, Bytes, TxHash, B256, U256},
     providers::{
+        ext::AnvilApi,
         fillers::{
             BlobGasFiller, ChainIdFiller, FillProvider, GasFiller, JoinFill, NonceFiller,
             WalletFiller,
         },
-        Identity, ProviderBuilder, RootProvider,
+        Identity, Provider as _, ProviderBuilder, RootProvider,
     },
+    rpc::types::BlockTransactionsKind,
     signers::local::PrivateKeySigner,
     transports::http::{Client, Http},
 };
 
 use crate::clients::get_anvil_instance;
+use crate::contexts::{whole_seconds, ChainContext};
+use crate::prerequisites::BinaryPaths;
+use crate::seed::derive_secret;
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tracing::{debug, info};
 
 type Provider = FillProvider<
     JoinFill<
@@ -37,23 +48,43 @@ type DefaultProvider = FillProvider<
     Ethereum,
 >;
 
+/// Balance of an ERC-20 token, written straight into the token storage
+///
+/// `balance_slot` is the storage slot of the `balances` mapping of the token contract
+#[derive(Debug, Clone)]
+pub struct Erc20Balance {
+    pub token: Address,
+    pub balance_slot: U256,
+    pub amount: U256,
+}
+
 #[derive(Debug)]
 pub struct EVMTestContext {
     pub anvil: AnvilInstance,
     pub provider: DefaultProvider,
     pub alice: EthereumWallet,
     pub bob: EthereumWallet,
+    seed: Option<u64>, // Derives the keys of the created accounts
+    created_accounts: AtomicUsize,
 }
 
 impl Default for EVMTestContext {
     fn default() -> Self {
-        let anvil = get_anvil_instance().unwrap();
+        let anvil = get_anvil_instance(&BinaryPaths::default(), None)
+            .unwrap_or_else(|err| panic!("{}", err));
         Self::new(anvil)
     }
 }
 
 impl EVMTestContext {
     pub fn new(anvil: AnvilInstance) -> Self {
+        Self::new_with_seed(anvil, None)
+    }
+
+    /// Sets up the accounts, the created ones get keys derived from `seed` when one is given
+    ///
+    /// Pass the seed Anvil was started with, so its dev accounts are reproducible too.
+    pub fn new_with_seed(anvil: AnvilInstance, seed: Option<u64>) -> Self {
         // Configure the signers for the first two Anvil accounts (Alice and Bob).
         let alice_signer: PrivateKeySigner = anvil.keys()[0].clone().into();
         let bob_signer: PrivateKeySigner = anvil.keys()[1].clone().into();
@@ -63,12 +94,15 @@ impl EVMTestContext {
         let provider = ProviderBuilder::new()
             .with_recommended_fillers()
             .on_http(anvil.endpoint_url());
+        info!(url = %anvil.endpoint(), chain_id = anvil.chain_id(), "Anvil started");
 
         Self {
             anvil,
             alice,
             bob,
             provider,
+            seed,
+            created_accounts: AtomicUsize::new(0),
         }
     }
 
@@ -88,4 +122,242 @@ impl EVMTestContext {
             .wallet(wallet)
             .on_http(rpc_url)
     }
+
+    /// Set the ETH balance and the ERC-20 token balances of an address
+    pub async fn fund(
+        &self,
+        address: Address,
+        eth: Option<U256>,
+        erc20: &[Erc20Balance],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(eth) = eth {
+            self.provider.anvil_set_balance(address, eth).await?;
+            debug!(%address, %eth, "Set EVM balance");
+        }
+
+        for balance in erc20 {
+            let storage_slot = erc20_balance_slot(address, balance.balance_slot);
+            self.provider
+                .anvil_set_storage_at(
+                    balance.token,
+                    storage_slot,
+                    B256::from(balance.amount.to_be_bytes::<32>()),
+                )
+                .await?;
+            debug!(%address, token = %balance.token, amount = %balance.amount, "Set ERC-20 balance");
+        }
+
+        Ok(())
+    }
+
+    /// Serialized state of the Anvil chain, accepted by `load_state`
+    pub async fn dump_state(&self) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.provider.anvil_dump_state().await?)
+    }
+
+    /// Merges a state returned by `dump_state` into the Anvil chain
+    pub async fn load_state(
+        &self,
+        state: Bytes,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.provider.anvil_load_state(state).await?;
+        Ok(())
+    }
+}
+
+impl ChainContext for EVMTestContext {
+    type Account = PrivateKeySigner;
+    type Address = Address;
+    type Balance = U256;
+    type TxHash = TxHash;
+    type SnapshotId = U256;
+
+    async fn create_funded_account(
+        &self,
+        balance: U256,
+    ) -> Result<PrivateKeySigner, Box<dyn Error + Send + Sync>> {
+        let signer = match self.seed {
+            Some(seed) => {
+                let index = self.created_accounts.fetch_add(1, Ordering::SeqCst);
+                seeded_signer(seed, index)?
+            }
+            None => PrivateKeySigner::random(),
+        };
+        self.provider
+            .anvil_set_balance(signer.address(), balance)
+            .await?;
+
+        Ok(signer)
+    }
+
+    fn account_address(&self, account: &PrivateKeySigner) -> Address {
+        account.address()
+    }
+
+    async fn balance(&self, address: &Address) -> Result<U256, Box<dyn Error + Send + Sync>> {
+        Ok(self.provider.get_balance(*address).await?)
+    }
+
+    async fn advance_blocks(&self, blocks: u64) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.provider
+            .anvil_mine(Some(U256::from(blocks)), None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn broadcast_raw_transaction(
+        &self,
+        raw_transaction: &[u8],
+    ) -> Result<TxHash, Box<dyn Error + Send + Sync>> {
+        let receipt = self
+            .provider
+            .send_raw_transaction(raw_transaction)
+            .await?
+            .get_receipt()
+            .await?;
+        debug!(transaction_hash = %receipt.transaction_hash, "EVM transaction confirmed");
+
+        Ok(receipt.transaction_hash)
+    }
+
+    async fn height(&self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        Ok(self.provider.get_block_number().await?)
+    }
+
+    async fn timestamp(&self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let block = self
+            .provider
+            .get_block_by_number(BlockNumberOrTag::Latest, BlockTransactionsKind::Hashes)
+            .await?
+            .ok_or("Missing latest EVM block")?;
+
+        Ok(block.header.timestamp)
+    }
+
+    async fn advance_time(&self, duration: Duration) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let seconds = whole_seconds(duration);
+        self.provider
+            .anvil_increase_time(U256::from(seconds))
+            .await?;
+
+        // The new time applies from the next block on
+        self.provider.evm_mine(None).await?;
+        debug!(seconds, "Advanced EVM time");
+
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> Result<U256, Box<dyn Error + Send + Sync>> {
+        Ok(self.provider.anvil_snapshot().await?)
+    }
+
+    async fn revert(&self, snapshot: U256) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.provider.anvil_revert(snapshot).await? {
+            return Err(format!("Failed to revert to EVM snapshot {}", snapshot).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Storage slot of `balances[address]`, Solidity stores it at keccak256(abi.encode(address, slot))
+fn erc20_balance_slot(address: Address, balance_slot: U256) -> U256 {
+    let mut key = [0u8; 64];
+    key[12..32].copy_from_slice(address.as_slice());
+    key[32..].copy_from_slice(&balance_slot.to_be_bytes::<32>());
+
+    U256::from_be_bytes(keccak256(key).0)
+}
+
+/// Signer of the `index`-th account created from the seed
+fn seeded_signer(
+    seed: u64,
+    index: usize,
+) -> Result<PrivateKeySigner, Box<dyn Error + Send + Sync>> {
+    let secret = derive_secret(seed, &format!("evm/account-{}", index));
+
+    Ok(PrivateKeySigner::from_bytes(&B256::from(secret))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::sol_types::SolValue;
+
+    #[test]
+    fn test_erc20_balance_slot_matches_the_abi_encoding() {
+        let address = Address::repeat_byte(0xab);
+        let balance_slot = U256::from(3);
+
+        assert_eq!(
+            erc20_balance_slot(address, balance_slot),
+            U256::from_be_bytes(keccak256((address, balance_slot).abi_encode()).0)
+        );
+        assert_ne!(
+            erc20_balance_slot(address, balance_slot),
+            erc20_balance_slot(address, U256::ZERO)
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs anvil"]
+    async fn test_fund_sets_the_eth_and_erc20_balances() {
+        let evm = EVMTestContext::default();
+        let address = Address::repeat_byte(0x11);
+        let token = Address::repeat_byte(0x22);
+        let erc20 = Erc20Balance {
+            token,
+            balance_slot: U256::from(3),
+            amount: U256::from(1_000_000),
+        };
+
+        evm.fund(address, Some(U256::from(5)), std::slice::from_ref(&erc20))
+            .await
+            .unwrap();
+
+        assert_eq!(evm.balance(&address).await.unwrap(), U256::from(5));
+        let stored = evm
+            .provider
+            .get_storage_at(token, erc20_balance_slot(address, erc20.balance_slot))
+            .await
+            .unwrap();
+        assert_eq!(stored, erc20.amount);
+    }
+
+    #[test]
+    fn test_seeded_signer() {
+        assert_eq!(
+            seeded_signer(7, 0).unwrap().address(),
+            seeded_signer(7, 0).unwrap().address()
+        );
+        assert_ne!(
+            seeded_signer(7, 0).unwrap().address(),
+            seeded_signer(7, 1).unwrap().address()
+        );
+        assert_ne!(
+            seeded_signer(7, 0).unwrap().address(),
+            seeded_signer(8, 0).unwrap().address()
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs anvil"]
+    async fn test_same_seed_creates_the_same_accounts() {
+        let binaries = BinaryPaths::default();
+        let first =
+            EVMTestContext::new_with_seed(get_anvil_instance(&binaries, Some(7)).unwrap(), Some(7));
+        let second =
+            EVMTestContext::new_with_seed(get_anvil_instance(&binaries, Some(7)).unwrap(), Some(7));
+
+        assert_eq!(first.anvil.addresses(), second.anvil.addresses());
+        for _ in 0..2 {
+            let balance = U256::from(1_000);
+            let account = first.create_funded_account(balance).await.unwrap();
+            let other = second.create_funded_account(balance).await.unwrap();
+
+            assert_eq!(account.address(), other.address());
+            assert_eq!(first.balance(&account.address()).await.unwrap(), balance);
+        }
+    }
 }
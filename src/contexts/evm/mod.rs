@@ -1,3 +1,3 @@
 mod evm_context;
 
-pub use evm_context::EVMTestContext;
+pub use evm_context::{EVMTestContext, Erc20Balance};
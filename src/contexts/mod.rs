@@ -1,7 +1,17 @@
+#[cfg(feature = "bitcoin")]
 pub mod btc;
+mod chain_context;
+#[cfg(feature = "evm")]
 mod evm;
+#[cfg(feature = "near-sandbox")]
 mod near;
 
-pub use btc::BTCTestContext;
-pub use evm::EVMTestContext;
-pub use near::NearTestContext;
+#[cfg(feature = "bitcoin")]
+pub use btc::{BTCTestContext, BtcSnapshot, UserInfo};
+#[cfg(any(feature = "bitcoin", feature = "evm"))]
+pub use chain_context::whole_seconds;
+pub use chain_context::ChainContext;
+#[cfg(feature = "evm")]
+pub use evm::{EVMTestContext, Erc20Balance};
+#[cfg(feature = "near-sandbox")]
+pub use near::{NearAccountSnapshot, NearSnapshot, NearTestContext};
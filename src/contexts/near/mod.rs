@@ -1,3 +1,3 @@
 mod near_context;
 
-pub use near_context::NearTestContext;
+pub use near_context::{NearAccountSnapshot, NearSnapshot, NearTestContext};
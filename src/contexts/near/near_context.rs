@@ -1,13 +1,66 @@
+use near_jsonrpc_client::methods::send_tx::RpcSendTransactionRequest;
+use near_jsonrpc_client::JsonRpcClient;
+use near_primitives::hash::CryptoHash;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::views::TxExecutionStatus;
+use near_sdk::borsh::BorshDeserialize;
+use near_sdk::AccountId;
 use near_workspaces::network::Sandbox;
+use near_workspaces::types::{
+    AccessKey, AccountDetailsPatch, KeyType, NearToken, PublicKey, SecretKey,
+};
 use near_workspaces::{Account, Worker};
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, info};
 
 use crate::clients::get_near_instance;
+use crate::contexts::ChainContext;
+use crate::friendly_near_json_rpc_client::outcome::TransactionOutcome;
+use crate::prerequisites::BinaryPaths;
+use crate::seed::derive_secret;
+
+/// Fast forwards tried before giving up on reaching a time
+const MAX_FAST_FORWARDS: usize = 8;
+
+/// Balance of the accounts created from a seed, matching the dev accounts of near-workspaces
+const SEEDED_ACCOUNT_BALANCE: NearToken = NearToken::from_near(10);
 
 #[derive(Debug)]
 pub struct NearTestContext {
     pub client: Worker<Sandbox>,
     pub alice: Account,
     pub bob: Account,
+    seed: Option<u64>, // Derives the names and keys of the created accounts
+    created_accounts: AtomicUsize,
+    tracked_accounts: Mutex<Vec<AccountId>>,
+}
+
+/// State of an account captured by a snapshot
+#[derive(Debug, Clone)]
+pub struct NearAccountSnapshot {
+    pub account_id: AccountId,
+    pub balance: NearToken,
+    pub locked: NearToken,
+    pub code_hash: near_workspaces::types::CryptoHash,
+    pub storage_usage: u64,
+    pub code: Option<Vec<u8>>,
+    pub state: Vec<(Vec<u8>, Vec<u8>)>,
+    pub access_keys: Vec<(PublicKey, AccessKey)>,
+}
+
+/// Snapshot of the tracked sandbox accounts
+///
+/// Only the accounts known to the context are captured, reverting it partially restores the
+/// sandbox: accounts created or funded without the context after the snapshot are left as they
+/// are, and so is the rest of the chain state.
+#[derive(Debug, Clone)]
+pub struct NearSnapshot {
+    pub height: u64,
+    pub accounts: Vec<NearAccountSnapshot>,
 }
 
 /// The NearTestContext is a struct that contains the Near client and two accounts for testing.
@@ -16,16 +69,44 @@ pub struct NearTestContext {
 /// Additionally you can use the client() method to interact with the Near client.
 impl NearTestContext {
     pub async fn new() -> Self {
-        let sandbox_worker: near_workspaces::Worker<Sandbox> = get_near_instance().await.unwrap();
+        Self::new_with_conf(&BinaryPaths::default(), None).await
+    }
+
+    /// Starts the sandbox from the configured binary, or the one downloaded by near-workspaces
+    ///
+    /// With a seed, Alice and Bob are the `alice` and `bob` subaccounts of the root account
+    /// and every account gets a key derived from the seed.
+    pub async fn new_with_conf(binaries: &BinaryPaths, seed: Option<u64>) -> Self {
+        let sandbox_worker = get_near_instance(binaries)
+            .await
+            .unwrap_or_else(|err| panic!("{}", err));
 
         // Configure sandbox accounts
-        let alice = sandbox_worker.dev_create_account().await.unwrap();
-        let bob = sandbox_worker.dev_create_account().await.unwrap();
+        let (alice, bob) = match seed {
+            Some(seed) => (
+                Self::create_seeded_account(&sandbox_worker, seed, "alice", SEEDED_ACCOUNT_BALANCE)
+                    .await
+                    .unwrap(),
+                Self::create_seeded_account(&sandbox_worker, seed, "bob", SEEDED_ACCOUNT_BALANCE)
+                    .await
+                    .unwrap(),
+            ),
+            None => (
+                sandbox_worker.dev_create_account().await.unwrap(),
+                sandbox_worker.dev_create_account().await.unwrap(),
+            ),
+        };
+
+        let tracked_accounts = vec![alice.id().clone(), bob.id().clone()];
+        info!(url = %sandbox_worker.rpc_addr(), "NEAR sandbox started");
 
         Self {
             client: sandbox_worker,
             alice,
             bob,
+            seed,
+            created_accounts: AtomicUsize::new(0),
+            tracked_accounts: Mutex::new(tracked_accounts),
         }
     }
 
@@ -42,6 +123,349 @@ impl NearTestContext {
     }
 
     pub async fn create_account(&self) -> Account {
-        self.client.dev_create_account().await.unwrap()
+        let account = match self.seed {
+            Some(_) => self
+                .create_next_account(SEEDED_ACCOUNT_BALANCE)
+                .await
+                .unwrap(),
+            None => self.client.dev_create_account().await.unwrap(),
+        };
+        self.track_account(account.id().clone());
+        account
+    }
+
+    /// Creates the subaccount `name` of the root account, with a key derived from the seed
+    async fn create_seeded_account(
+        worker: &Worker<Sandbox>,
+        seed: u64,
+        name: &str,
+        balance: NearToken,
+    ) -> Result<Account, Box<dyn Error + Send + Sync>> {
+        let secret = derive_secret(seed, &format!("near/{}", name));
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, &hex::encode(secret));
+
+        Ok(worker
+            .root_account()?
+            .create_subaccount(name)
+            .keys(secret_key)
+            .initial_balance(balance)
+            .transact()
+            .await?
+            .into_result()?)
+    }
+
+    /// Creates the next `account-{n}` subaccount of the root account
+    async fn create_next_account(
+        &self,
+        balance: NearToken,
+    ) -> Result<Account, Box<dyn Error + Send + Sync>> {
+        let name = format!(
+            "account-{}",
+            self.created_accounts.fetch_add(1, Ordering::SeqCst)
+        );
+
+        match self.seed {
+            Some(seed) => Self::create_seeded_account(&self.client, seed, &name, balance).await,
+            None => Ok(self
+                .client
+                .root_account()?
+                .create_subaccount(&name)
+                .initial_balance(balance)
+                .transact()
+                .await?
+                .into_result()?),
+        }
+    }
+
+    /// Include an account in the snapshots taken by this context
+    pub fn track_account(&self, account_id: AccountId) {
+        let mut tracked_accounts = self.tracked_accounts.lock().unwrap();
+        if !tracked_accounts.contains(&account_id) {
+            tracked_accounts.push(account_id);
+        }
+    }
+
+    /// Registers an account restored from a warm start template, so seeded names are not reused
+    pub(crate) fn register_restored_account(&self, account_id: AccountId) {
+        self.created_accounts.fetch_add(1, Ordering::SeqCst);
+        self.track_account(account_id);
+    }
+
+    /// Transfer NEAR from the sandbox root account to the given account
+    pub async fn fund(
+        &self,
+        account_id: &AccountId,
+        amount: NearToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.client
+            .root_account()?
+            .transfer_near(account_id, amount)
+            .await?
+            .into_result()?;
+        self.track_account(account_id.clone());
+        debug!(%account_id, %amount, "Funded NEAR account");
+
+        Ok(())
+    }
+}
+
+impl ChainContext for NearTestContext {
+    type Account = Account;
+    type Address = AccountId;
+    type Balance = NearToken;
+    type TxHash = CryptoHash;
+    type SnapshotId = NearSnapshot;
+
+    async fn create_funded_account(
+        &self,
+        balance: NearToken,
+    ) -> Result<Account, Box<dyn Error + Send + Sync>> {
+        let account = self.create_next_account(balance).await?;
+
+        self.track_account(account.id().clone());
+
+        Ok(account)
+    }
+
+    fn account_address(&self, account: &Account) -> AccountId {
+        account.id().clone()
+    }
+
+    async fn balance(
+        &self,
+        address: &AccountId,
+    ) -> Result<NearToken, Box<dyn Error + Send + Sync>> {
+        Ok(self.client.view_account(address).await?.balance)
+    }
+
+    async fn advance_blocks(&self, blocks: u64) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.client.fast_forward(blocks).await?;
+
+        Ok(())
+    }
+
+    async fn broadcast_raw_transaction(
+        &self,
+        raw_transaction: &[u8],
+    ) -> Result<CryptoHash, Box<dyn Error + Send + Sync>> {
+        let signed_transaction = SignedTransaction::try_from_slice(raw_transaction)?;
+        let transaction_hash = signed_transaction.get_hash();
+
+        let client = JsonRpcClient::connect(self.client.rpc_addr());
+        let response = client
+            .call(RpcSendTransactionRequest {
+                signed_transaction,
+                wait_until: TxExecutionStatus::Final,
+            })
+            .await?;
+
+        // Fails if the transaction failed on chain
+        TransactionOutcome::from_response(transaction_hash, response)
+            .map_err(|err| err.to_string())?;
+        debug!(%transaction_hash, "NEAR transaction executed");
+
+        Ok(transaction_hash)
+    }
+
+    async fn height(&self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        Ok(self.client.view_block().await?.height())
+    }
+
+    async fn timestamp(&self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        // Block timestamps are in nanoseconds
+        Ok(self.client.view_block().await?.timestamp() / 1_000_000_000)
+    }
+
+    async fn advance_time(&self, duration: Duration) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let target = self.client.view_block().await?.timestamp() + duration.as_nanos() as u64;
+
+        // Fast forwards move the block timestamps by the block time of the sandbox, measured
+        // from a single block first and then from each fast forward
+        let mut nanos_per_block = None;
+        for _ in 0..MAX_FAST_FORWARDS {
+            let before = self.client.view_block().await?.timestamp();
+            if before >= target {
+                debug!(target, "Advanced NEAR time");
+                return Ok(());
+            }
+
+            let blocks = nanos_per_block.map_or(1, |nanos| (target - before).div_ceil(nanos));
+            self.client.fast_forward(blocks).await?;
+
+            let after = self.client.view_block().await?.timestamp();
+            nanos_per_block = Some((after.saturating_sub(before) / blocks).max(1));
+        }
+
+        if self.client.view_block().await?.timestamp() >= target {
+            debug!(target, "Advanced NEAR time");
+            return Ok(());
+        }
+        Err(format!(
+            "NEAR time did not reach {} after {} fast forwards",
+            target, MAX_FAST_FORWARDS
+        )
+        .into())
+    }
+
+    async fn snapshot(&self) -> Result<NearSnapshot, Box<dyn Error + Send + Sync>> {
+        let height = self.height().await?;
+        let tracked_accounts = self.tracked_accounts.lock().unwrap().clone();
+
+        let mut accounts = Vec::with_capacity(tracked_accounts.len());
+        for account_id in tracked_accounts {
+            let details = self.client.view_account(&account_id).await?;
+            let code = if details.code_hash == Default::default() {
+                None
+            } else {
+                Some(self.client.view_code(&account_id).await?)
+            };
+            let state = self
+                .client
+                .view_state(&account_id)
+                .await?
+                .into_iter()
+                .collect();
+            let access_keys = self
+                .client
+                .view_access_keys(&account_id)
+                .await?
+                .into_iter()
+                .map(|info| (info.public_key, info.access_key))
+                .collect();
+
+            accounts.push(NearAccountSnapshot {
+                account_id,
+                balance: details.balance,
+                locked: details.locked,
+                code_hash: details.code_hash,
+                storage_usage: details.storage_usage,
+                code,
+                state,
+                access_keys,
+            });
+        }
+
+        Ok(NearSnapshot { height, accounts })
+    }
+
+    /// Patches the tracked accounts back to their state at the snapshot
+    ///
+    /// The sandbox can only patch state in, so an account holding storage entries or access keys
+    /// added after the snapshot is deleted and recreated from the snapshot. Deleting an account
+    /// with a large state exceeds the gas of a transaction and fails the revert. This is a
+    /// partial revert, see `NearSnapshot`.
+    async fn revert(&self, snapshot: NearSnapshot) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for account in snapshot.accounts {
+            let mut access_keys = None;
+            if self.has_entries_added_since(&account).await? {
+                access_keys = Some(self.recreate_access_keys(&account).await?);
+                self.delete_account(&account.account_id).await?;
+                debug!(account_id = %account.account_id, "Recreating NEAR account");
+            }
+
+            let mut patch = self
+                .client
+                .patch(&account.account_id)
+                .account(
+                    AccountDetailsPatch::default()
+                        .balance(account.balance)
+                        .locked(account.locked)
+                        .code_hash(account.code_hash)
+                        .storage_usage(account.storage_usage),
+                )
+                .states(
+                    account
+                        .state
+                        .iter()
+                        .map(|(key, value)| (key.as_slice(), value.as_slice())),
+                );
+
+            if let Some(code) = &account.code {
+                patch = patch.code(code);
+            }
+            if let Some(access_keys) = access_keys {
+                patch = patch.access_keys(access_keys);
+            }
+
+            patch.transact().await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl NearTestContext {
+    /// Whether the account holds storage entries or access keys missing from its snapshot
+    async fn has_entries_added_since(
+        &self,
+        account: &NearAccountSnapshot,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let snapshot_keys: HashSet<&[u8]> = account
+            .state
+            .iter()
+            .map(|(key, _)| key.as_slice())
+            .collect();
+        let state = self.client.view_state(&account.account_id).await?;
+        if state
+            .keys()
+            .any(|key| !snapshot_keys.contains(key.as_slice()))
+        {
+            return Ok(true);
+        }
+
+        let snapshot_access_keys: HashSet<&PublicKey> =
+            account.access_keys.iter().map(|(key, _)| key).collect();
+        let access_keys = self.client.view_access_keys(&account.account_id).await?;
+
+        Ok(access_keys
+            .iter()
+            .any(|info| !snapshot_access_keys.contains(&info.public_key)))
+    }
+
+    /// Access keys of the snapshot, with nonces that do not go back so signed transactions can not be replayed
+    async fn recreate_access_keys(
+        &self,
+        account: &NearAccountSnapshot,
+    ) -> Result<Vec<(PublicKey, AccessKey)>, Box<dyn Error + Send + Sync>> {
+        let nonces: BTreeMap<PublicKey, u64> = self
+            .client
+            .view_access_keys(&account.account_id)
+            .await?
+            .into_iter()
+            .map(|info| (info.public_key, info.access_key.nonce))
+            .collect();
+
+        Ok(account
+            .access_keys
+            .iter()
+            .map(|(public_key, access_key)| {
+                let mut access_key = access_key.clone();
+                if let Some(nonce) = nonces.get(public_key) {
+                    access_key.nonce = access_key.nonce.max(*nonce);
+                }
+                (public_key.clone(), access_key)
+            })
+            .collect())
+    }
+
+    /// Deletes an account and its storage, signing with a key patched into it
+    async fn delete_account(
+        &self,
+        account_id: &AccountId,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let secret_key = SecretKey::from_random(KeyType::ED25519);
+        self.client
+            .patch(account_id)
+            .access_key(secret_key.public_key(), AccessKey::full_access())
+            .transact()
+            .await?;
+
+        let beneficiary_id = self.client.root_account()?.id().clone();
+        Account::from_secret_key(account_id.clone(), secret_key, &self.client)
+            .delete_account(&beneficiary_id)
+            .await?
+            .into_result()?;
+
+        Ok(())
     }
 }
@@ -0,0 +1,450 @@
+use crate::{deploy_cache::normalize_contract_path, deploy_error::DeployError, NearAccount};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{Balance, Gas};
+use near_sdk::AccountId;
+use near_workspaces::types::{Gas as NearGas, NearToken};
+use near_workspaces::{Account, Contract};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tracing::{info, warn};
+
+/// Default balance given to the sub accounts created to host contracts (10 NEAR)
+pub const DEFAULT_SUB_ACCOUNT_BALANCE: Balance = 10_000_000_000_000_000_000_000_000;
+
+/// Gas attached to the initialization and migration calls of the deployed contracts
+pub const HOOK_GAS: Gas = 300_000_000_000_000;
+
+/// Where the code of a contract comes from
+#[derive(Debug, Clone)]
+pub enum ContractSource {
+    /// Cargo project compiled with cargo-near
+    Project(&'static str),
+    /// Prebuilt `.wasm` file
+    WasmFile(&'static str),
+    /// Prebuilt wasm bytes
+    WasmBytes(Vec<u8>),
+    /// Cargo project compiled as it was at the given git ref (branch, tag or commit)
+    GitRef {
+        git_ref: &'static str,
+        path: &'static str,
+    },
+}
+
+impl ContractSource {
+    /// Returns the wasm of the contract, compiling it only if it is a Cargo project
+    pub async fn load(&self) -> Result<Vec<u8>, DeployError> {
+        match self {
+            Self::Project(path) => {
+                info!(path, "Compiling contract");
+                near_workspaces::compile_project(path)
+                    .await
+                    .map_err(|err| DeployError::Compile {
+                        path: path.to_string(),
+                        reason: err.to_string(),
+                    })
+            }
+            Self::WasmFile(path) => fs::read(path).map_err(|err| DeployError::ReadWasm {
+                path: path.to_string(),
+                reason: err.to_string(),
+            }),
+            Self::WasmBytes(bytes) => Ok(bytes.clone()),
+            Self::GitRef { git_ref, path } => compile_project_at_git_ref(git_ref, path)
+                .await
+                .map_err(|reason| DeployError::Compile {
+                    path: format!("{}@{}", path, git_ref),
+                    reason,
+                }),
+        }
+    }
+
+    /// Identifies the source in the deploy cache
+    pub fn cache_label(&self) -> String {
+        match self {
+            Self::Project(path) | Self::WasmFile(path) => normalize_contract_path(path),
+            Self::WasmBytes(_) => "wasm-bytes".to_string(),
+            Self::GitRef { git_ref, path } => {
+                format!("{}@{}", normalize_contract_path(path), git_ref)
+            }
+        }
+    }
+}
+
+impl From<&'static str> for ContractSource {
+    fn from(path: &'static str) -> Self {
+        if path.ends_with(".wasm") {
+            Self::WasmFile(path)
+        } else {
+            Self::Project(path)
+        }
+    }
+}
+
+impl From<Vec<u8>> for ContractSource {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::WasmBytes(bytes)
+    }
+}
+
+/// Compiles the project at `path` as it was at `git_ref`, using a temporary git worktree
+async fn compile_project_at_git_ref(git_ref: &str, path: &str) -> Result<Vec<u8>, String> {
+    let project_path = fs::canonicalize(path).map_err(|err| err.to_string())?;
+    let repository_root = run_git(&project_path, &["rev-parse", "--show-toplevel"])?;
+    let repository_root = Path::new(repository_root.trim());
+    let relative_path = project_path
+        .strip_prefix(repository_root)
+        .map_err(|err| err.to_string())?;
+
+    // A fresh directory per compilation, concurrent compilations of a ref must not collide
+    let worktree_dir = tempfile::Builder::new()
+        .prefix("omni-box-worktree-")
+        .tempdir()
+        .map_err(|err| err.to_string())?;
+    let worktree = worktree_dir
+        .path()
+        .join(git_ref.replace(|c: char| !c.is_ascii_alphanumeric(), "-"));
+    let worktree_str = worktree.to_string_lossy().to_string();
+
+    run_git(
+        repository_root,
+        &["worktree", "add", "--detach", &worktree_str, git_ref],
+    )?;
+
+    info!(path, git_ref, "Compiling contract");
+    let result = near_workspaces::compile_project(&worktree.join(relative_path).to_string_lossy())
+        .await
+        .map_err(|err| err.to_string());
+
+    // Always clean up the worktree, a failed cleanup must not hide the compilation result
+    if let Err(err) = run_git(
+        repository_root,
+        &["worktree", "remove", "--force", &worktree_str],
+    ) {
+        warn!(%err, worktree = %worktree_str, "Failed to remove the git worktree");
+    }
+
+    result
+}
+
+fn run_git(directory: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(args)
+        .output()
+        .map_err(|err| format!("Failed to run git: {}", err))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Account a contract of the workspace is deployed to
+#[derive(Debug, Clone)]
+pub enum ContractTarget {
+    /// Sub account of the deployer, e.g. `vault` deploys to `vault.<deployer>`
+    SubAccount {
+        prefix: &'static str,
+        initial_balance: Balance,
+    },
+    /// Fresh dev account in the NEAR sandbox
+    DevAccount,
+}
+
+/// A call made to a contract right after it is deployed
+#[derive(Debug, Clone)]
+pub struct ContractCall {
+    pub method: &'static str,
+    pub args: serde_json::Value,
+    pub deposit: Balance,
+}
+
+impl ContractCall {
+    pub const fn new(method: &'static str, args: serde_json::Value) -> Self {
+        Self {
+            method,
+            args,
+            deposit: 0,
+        }
+    }
+
+    pub const fn with_deposit(mut self, deposit: Balance) -> Self {
+        self.deposit = deposit;
+        self
+    }
+}
+
+/// Hook called once a contract is deployed
+#[derive(Debug)]
+pub enum DeployHook<'a> {
+    Init(&'a ContractCall),
+    Migrate(&'a ContractCall),
+}
+
+impl<'a> DeployHook<'a> {
+    /// Initializes on the first deploy, migrates when changed code replaces existing code
+    pub fn after_deploy(
+        on_chain_hash: Option<CryptoHash>,
+        code_hash: CryptoHash,
+        init: Option<&'a ContractCall>,
+        migrate: Option<&'a ContractCall>,
+    ) -> Option<Self> {
+        match on_chain_hash {
+            None => init.map(Self::Init),
+            Some(hash) if hash == CryptoHash::default() => init.map(Self::Init),
+            Some(hash) if hash != code_hash => migrate.map(Self::Migrate),
+            Some(_) => None,
+        }
+    }
+
+    pub const fn call(&self) -> &'a ContractCall {
+        match self {
+            Self::Init(call) | Self::Migrate(call) => call,
+        }
+    }
+
+    /// Error raised when the hook call fails
+    pub fn error(&self, account_id: AccountId, reason: String) -> DeployError {
+        let method = self.call().method.to_string();
+        match self {
+            Self::Init(_) => DeployError::Init {
+                account_id,
+                method,
+                reason,
+            },
+            Self::Migrate(_) => DeployError::Migrate {
+                account_id,
+                method,
+                reason,
+            },
+        }
+    }
+
+    /// Calls the hook on a contract deployed in the NEAR sandbox
+    pub async fn call_sandbox(&self, contract: &Contract) -> Result<(), DeployError> {
+        let call = self.call();
+        contract
+            .call(call.method)
+            .args_json(call.args.clone())
+            .deposit(NearToken::from_yoctonear(call.deposit))
+            .gas(NearGas::from_gas(HOOK_GAS))
+            .transact()
+            .await
+            .map_err(|err| err.to_string())
+            .and_then(|execution| execution.into_result().map_err(|err| err.to_string()))
+            .map(|_| ())
+            .map_err(|reason| self.error(contract.id().clone(), reason))
+    }
+}
+
+/// Deploys a contract to a NEAR sandbox account
+pub async fn deploy_to_sandbox(account: &Account, wasm: &[u8]) -> Result<Contract, DeployError> {
+    account
+        .deploy(wasm)
+        .await
+        .map_err(|err| err.to_string())
+        .and_then(|execution| execution.into_result().map_err(|err| err.to_string()))
+        .map_err(|reason| DeployError::Deploy {
+            account_id: account.id().clone(),
+            reason,
+        })
+}
+
+/// A contract crate to compile and deploy when OmniBox starts
+#[derive(Debug, Clone)]
+pub struct ContractConfig {
+    pub name: &'static str,            // Name used to get the deployed contract
+    pub source: ContractSource,        // Cargo project or prebuilt wasm
+    pub target: ContractTarget,        // Account the contract is deployed to
+    pub init: Option<ContractCall>,    // Called only after the first deploy
+    pub migrate: Option<ContractCall>, // Called after redeploying changed code
+}
+
+impl ContractConfig {
+    /// Contract deployed to the `prefix` sub account of the deployer
+    pub fn sub_account(
+        name: &'static str,
+        source: impl Into<ContractSource>,
+        prefix: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            source: source.into(),
+            target: ContractTarget::SubAccount {
+                prefix,
+                initial_balance: DEFAULT_SUB_ACCOUNT_BALANCE,
+            },
+            init: None,
+            migrate: None,
+        }
+    }
+
+    /// Contract deployed to a sandbox dev account
+    pub fn dev_account(name: &'static str, source: impl Into<ContractSource>) -> Self {
+        Self {
+            name,
+            source: source.into(),
+            target: ContractTarget::DevAccount,
+            init: None,
+            migrate: None,
+        }
+    }
+
+    /// Call `new` with the given arguments after the first deploy
+    pub fn with_init_args(self, init_args: serde_json::Value) -> Self {
+        self.with_init(ContractCall::new("new", init_args))
+    }
+
+    pub fn with_init(mut self, init: ContractCall) -> Self {
+        self.init = Some(init);
+        self
+    }
+
+    pub fn with_migrate(mut self, migrate: ContractCall) -> Self {
+        self.migrate = Some(migrate);
+        self
+    }
+}
+
+/// Handle of a contract deployed by OmniBox
+#[derive(Debug, Clone)]
+pub struct DeployedContract {
+    pub name: &'static str,
+    pub account: NearAccount, // Credentials of the account holding the contract
+    pub code_hash: CryptoHash,
+    pub sandbox_contract: Option<near_workspaces::Contract>, // Set for sandbox dev accounts
+}
+
+impl DeployedContract {
+    pub const fn account_id(&self) -> &AccountId {
+        &self.account.account_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path_picks_a_wasm_file_or_a_project() {
+        assert!(matches!(
+            ContractSource::from("./res/contract.wasm"),
+            ContractSource::WasmFile("./res/contract.wasm")
+        ));
+        assert!(matches!(
+            ContractSource::from("./contracts/vault"),
+            ContractSource::Project("./contracts/vault")
+        ));
+        assert!(matches!(
+            ContractSource::from(vec![0, 97, 115, 109]),
+            ContractSource::WasmBytes(bytes) if bytes == [0, 97, 115, 109]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_load_reads_prebuilt_wasm() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contract.wasm");
+        fs::write(&path, [0, 97, 115, 109]).unwrap();
+        let path: &'static str = Box::leak(path.to_str().unwrap().to_string().into_boxed_str());
+
+        assert_eq!(
+            ContractSource::from(path).load().await.unwrap(),
+            [0, 97, 115, 109]
+        );
+        assert_eq!(
+            ContractSource::WasmBytes(vec![1, 2]).load().await.unwrap(),
+            [1, 2]
+        );
+        assert!(matches!(
+            ContractSource::WasmFile("./missing.wasm").load().await,
+            Err(DeployError::ReadWasm { path, .. }) if path == "./missing.wasm"
+        ));
+    }
+
+    #[test]
+    fn test_cache_label() {
+        assert_eq!(
+            ContractSource::WasmBytes(vec![]).cache_label(),
+            "wasm-bytes"
+        );
+        assert_eq!(
+            ContractSource::GitRef {
+                git_ref: "v1.0.0",
+                path: "./contracts/vault",
+            }
+            .cache_label(),
+            format!("{}@v1.0.0", normalize_contract_path("./contracts/vault"))
+        );
+    }
+
+    #[test]
+    fn test_contract_configs() {
+        let vault = ContractConfig::sub_account("vault", "./contracts/vault", "vault")
+            .with_init_args(serde_json::json!({ "owner": "alice.testnet" }))
+            .with_migrate(ContractCall::new("migrate", serde_json::json!({})).with_deposit(1));
+        assert!(matches!(
+            vault.target,
+            ContractTarget::SubAccount {
+                prefix: "vault",
+                initial_balance: DEFAULT_SUB_ACCOUNT_BALANCE,
+            }
+        ));
+        let init = vault.init.unwrap();
+        assert_eq!(init.method, "new");
+        assert_eq!(init.args["owner"], "alice.testnet");
+        assert_eq!(init.deposit, 0);
+        assert_eq!(vault.migrate.unwrap().deposit, 1);
+
+        let dev = ContractConfig::dev_account("dev", "./res/dev.wasm");
+        assert!(matches!(dev.target, ContractTarget::DevAccount));
+        assert!(matches!(dev.source, ContractSource::WasmFile(_)));
+        assert!(dev.init.is_none() && dev.migrate.is_none());
+    }
+
+    #[test]
+    fn test_deploy_hook_after_deploy() {
+        let init = ContractCall::new("new", serde_json::json!({}));
+        let migrate = ContractCall::new("migrate", serde_json::json!({}));
+        let code_hash = CryptoHash::hash_bytes(b"new code");
+        let old_hash = CryptoHash::hash_bytes(b"old code");
+        let hook = |on_chain_hash| {
+            DeployHook::after_deploy(on_chain_hash, code_hash, Some(&init), Some(&migrate))
+        };
+
+        // Missing account or account without code
+        assert!(matches!(hook(None), Some(DeployHook::Init(call)) if call.method == "new"));
+        assert!(matches!(
+            hook(Some(CryptoHash::default())),
+            Some(DeployHook::Init(_))
+        ));
+        // Changed code replacing existing code
+        assert!(matches!(
+            hook(Some(old_hash)),
+            Some(DeployHook::Migrate(call)) if call.method == "migrate"
+        ));
+        // Same code forcibly redeployed
+        assert!(hook(Some(code_hash)).is_none());
+        // No hook configured
+        assert!(DeployHook::after_deploy(None, code_hash, None, Some(&migrate)).is_none());
+        assert!(DeployHook::after_deploy(Some(old_hash), code_hash, Some(&init), None).is_none());
+    }
+
+    #[test]
+    fn test_deploy_hook_error() {
+        let account_id: AccountId = "vault.deployer.testnet".parse().unwrap();
+        let migrate = ContractCall::new("migrate", serde_json::json!({}));
+
+        assert!(matches!(
+            DeployHook::Migrate(&migrate).error(account_id, "state mismatch".to_string()),
+            DeployError::Migrate { method, reason, .. }
+                if method == "migrate" && reason == "state mismatch"
+        ));
+    }
+}
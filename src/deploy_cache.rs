@@ -0,0 +1,113 @@
+//! Cache of the contracts deployed by OmniBox
+//!
+//! Entries are keyed by network, account id and contract path and store the code hash that was
+//! deployed, which is the same hash NEAR reports as `code_hash` in `view_account`.
+use near_primitives::hash::CryptoHash;
+use near_sdk::AccountId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+pub const DEFAULT_CACHE_PATH: &str = "cache/contracts.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeployCacheEntry {
+    pub network: String,
+    pub account_id: AccountId,
+    pub contract_path: String,
+    pub code_hash: CryptoHash,
+}
+
+impl DeployCacheEntry {
+    fn key(&self) -> String {
+        cache_key(&self.network, &self.account_id, &self.contract_path)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DeployCache {
+    path: PathBuf,
+    entries: BTreeMap<String, DeployCacheEntry>,
+}
+
+impl DeployCache {
+    /// Loads the cache from disk, starting empty if the file is missing or unreadable
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let entries: BTreeMap<String, DeployCacheEntry> = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        debug!(path = %path.display(), entries = entries.len(), "Loaded deploy cache");
+
+        Self { path, entries }
+    }
+
+    pub fn get(
+        &self,
+        network: &str,
+        account_id: &AccountId,
+        contract_path: &str,
+    ) -> Option<&DeployCacheEntry> {
+        self.entries
+            .get(&cache_key(network, account_id, contract_path))
+    }
+
+    pub fn insert(&mut self, entry: DeployCacheEntry) {
+        self.entries.insert(entry.key(), entry);
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&self.path, serde_json::to_string_pretty(&self.entries)?)?;
+        debug!(path = %self.path.display(), entries = self.entries.len(), "Saved deploy cache");
+        Ok(())
+    }
+}
+
+/// Normalizes the contract path so the same project always maps to the same entry
+pub fn normalize_contract_path(path: &str) -> String {
+    fs::canonicalize(path).map_or_else(
+        |_| path.to_string(),
+        |path| path.to_string_lossy().to_string(),
+    )
+}
+
+fn cache_key(network: &str, account_id: &AccountId, contract_path: &str) -> String {
+    format!("{}:{}:{}", network, account_id, contract_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_is_keyed_by_network_account_and_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deploy_cache.json");
+        let account_id: AccountId = "omnitester.testnet".parse().unwrap();
+
+        let mut cache = DeployCache::load(&path);
+        cache.insert(DeployCacheEntry {
+            network: "testnet".to_string(),
+            account_id: account_id.clone(),
+            contract_path: "./".to_string(),
+            code_hash: CryptoHash::hash_bytes(b"contract"),
+        });
+        cache.save().unwrap();
+
+        let cache = DeployCache::load(&path);
+        assert!(cache.get("testnet", &account_id, "./").is_some());
+        assert!(cache.get("mainnet", &account_id, "./").is_none());
+        assert!(cache
+            .get("testnet", &"other.testnet".parse().unwrap(), "./")
+            .is_none());
+    }
+}
@@ -0,0 +1,139 @@
+use near_sdk::AccountId;
+use std::fmt;
+
+/// Errors raised while compiling, deploying or initializing a contract
+#[derive(Debug)]
+pub enum DeployError {
+    Compile {
+        path: String,
+        reason: String,
+    },
+    ReadWasm {
+        path: String,
+        reason: String,
+    },
+    Account {
+        account_id: AccountId,
+        reason: String,
+    },
+    Deploy {
+        account_id: AccountId,
+        reason: String,
+    },
+    Init {
+        account_id: AccountId,
+        method: String,
+        reason: String,
+    },
+    Migrate {
+        account_id: AccountId,
+        method: String,
+        reason: String,
+    },
+    Cache(String),
+}
+
+impl fmt::Display for DeployError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Compile { path, reason } => {
+                write!(f, "Failed to compile contract at {}: {}", path, reason)
+            }
+            Self::ReadWasm { path, reason } => {
+                write!(f, "Failed to read contract wasm {}: {}", path, reason)
+            }
+            Self::Account { account_id, reason } => {
+                write!(f, "Failed to prepare account {}: {}", account_id, reason)
+            }
+            Self::Deploy { account_id, reason } => {
+                write!(f, "Failed to deploy contract to {}: {}", account_id, reason)
+            }
+            Self::Init {
+                account_id,
+                method,
+                reason,
+            } => write!(
+                f,
+                "Failed to initialize contract {} calling {}: {}",
+                account_id, method, reason
+            ),
+            Self::Migrate {
+                account_id,
+                method,
+                reason,
+            } => write!(
+                f,
+                "Failed to migrate contract {} calling {}: {}",
+                account_id, method, reason
+            ),
+            Self::Cache(reason) => write!(f, "Failed to update the deploy cache: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for DeployError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_names_the_account_and_method() {
+        let account_id: AccountId = "vault.deployer.testnet".parse().unwrap();
+
+        assert_eq!(
+            DeployError::Compile {
+                path: "./contracts/vault".to_string(),
+                reason: "cargo-near not found".to_string(),
+            }
+            .to_string(),
+            "Failed to compile contract at ./contracts/vault: cargo-near not found"
+        );
+        assert_eq!(
+            DeployError::ReadWasm {
+                path: "./res/vault.wasm".to_string(),
+                reason: "No such file or directory (os error 2)".to_string(),
+            }
+            .to_string(),
+            "Failed to read contract wasm ./res/vault.wasm: No such file or directory (os error 2)"
+        );
+        assert_eq!(
+            DeployError::Account {
+                account_id: account_id.clone(),
+                reason: "not enough balance".to_string(),
+            }
+            .to_string(),
+            "Failed to prepare account vault.deployer.testnet: not enough balance"
+        );
+        assert_eq!(
+            DeployError::Deploy {
+                account_id: account_id.clone(),
+                reason: "invalid nonce".to_string(),
+            }
+            .to_string(),
+            "Failed to deploy contract to vault.deployer.testnet: invalid nonce"
+        );
+        assert_eq!(
+            DeployError::Init {
+                account_id: account_id.clone(),
+                method: "new".to_string(),
+                reason: "already initialized".to_string(),
+            }
+            .to_string(),
+            "Failed to initialize contract vault.deployer.testnet calling new: already initialized"
+        );
+        assert_eq!(
+            DeployError::Migrate {
+                account_id,
+                method: "migrate".to_string(),
+                reason: "state mismatch".to_string(),
+            }
+            .to_string(),
+            "Failed to migrate contract vault.deployer.testnet calling migrate: state mismatch"
+        );
+        assert_eq!(
+            DeployError::Cache("read only".to_string()).to_string(),
+            "Failed to update the deploy cache: read only"
+        );
+    }
+}
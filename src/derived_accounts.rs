@@ -0,0 +1,322 @@
+//! Addresses and keys derived from the deployer account through the MPC signer
+use crate::network::Network;
+use crate::utils::address::{self, DerivedAddress};
+#[cfg(feature = "evm")]
+use alloy::primitives::Address as EvmAddress;
+#[cfg(feature = "bitcoin")]
+use bitcoin::{Address as BtcAddress, Network as BtcNetwork, PublicKey as BtcPublicKey, ScriptBuf};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use near_crypto::{PublicKey as NearPublicKey, Secp256K1PublicKey};
+use near_sdk::AccountId;
+use std::collections::BTreeMap;
+use std::error::Error;
+#[cfg(any(feature = "bitcoin", feature = "evm"))]
+use std::str::FromStr;
+
+/// Bitcoin address derived for a given path
+#[cfg(feature = "bitcoin")]
+#[derive(Debug, Clone)]
+pub struct BtcDerivedAccount {
+    pub path: String,
+    pub address: BtcAddress,
+    pub public_key: BtcPublicKey,
+    pub script_pubkey: ScriptBuf,
+    pub derived_address: DerivedAddress,
+}
+
+/// EVM address derived for a given path
+#[cfg(feature = "evm")]
+#[derive(Debug, Clone)]
+pub struct EvmDerivedAccount {
+    pub path: String,
+    pub address: EvmAddress,
+    pub derived_address: DerivedAddress,
+}
+
+/// NEAR account controlled by the key derived for a given path
+///
+/// The account is the ETH-implicit account of the derived key, `0x` followed by its EVM address,
+/// so it exists once it receives NEAR.
+#[derive(Debug, Clone)]
+pub struct NearDerivedAccount {
+    pub path: String,
+    pub account_id: AccountId,
+    pub public_key: NearPublicKey,
+    pub derived_address: DerivedAddress,
+}
+
+/// Derived accounts of the deployer for each configured chain, indexed by derivation path
+#[derive(Debug, Clone)]
+pub struct DerivedAccounts {
+    pub predecessor_id: AccountId,
+    #[cfg(feature = "bitcoin")]
+    pub btc_legacy: BTreeMap<String, BtcDerivedAccount>,
+    #[cfg(feature = "bitcoin")]
+    pub btc_segwit: BTreeMap<String, BtcDerivedAccount>,
+    #[cfg(feature = "evm")]
+    pub evm: BTreeMap<String, EvmDerivedAccount>,
+    pub near: BTreeMap<String, NearDerivedAccount>,
+    btc_path: String,
+    evm_path: String,
+    near_path: String,
+}
+
+impl DerivedAccounts {
+    /// Derives the default paths for the configured chains
+    pub fn new(
+        predecessor_id: AccountId,
+        modules: &[Network],
+        btc_path: &str,
+        evm_path: &str,
+        near_path: &str,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut derived_accounts = Self {
+            predecessor_id,
+            #[cfg(feature = "bitcoin")]
+            btc_legacy: BTreeMap::new(),
+            #[cfg(feature = "bitcoin")]
+            btc_segwit: BTreeMap::new(),
+            #[cfg(feature = "evm")]
+            evm: BTreeMap::new(),
+            near: BTreeMap::new(),
+            btc_path: btc_path.to_string(),
+            evm_path: evm_path.to_string(),
+            near_path: near_path.to_string(),
+        };
+
+        for module in modules {
+            match module {
+                #[cfg(feature = "bitcoin")]
+                Network::Bitcoin => derived_accounts.register_path(module, btc_path)?,
+                #[cfg(feature = "evm")]
+                Network::EVM => derived_accounts.register_path(module, evm_path)?,
+                Network::Near => derived_accounts.register_path(module, near_path)?,
+            }
+        }
+
+        Ok(derived_accounts)
+    }
+
+    /// Derives the accounts of an extra path and registers them
+    pub fn register_path(
+        &mut self,
+        network: &Network,
+        path: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match network {
+            #[cfg(feature = "bitcoin")]
+            Network::Bitcoin => {
+                let legacy =
+                    address::get_derived_address_for_btc_legacy(&self.predecessor_id, path);
+                let segwit = address::get_derived_address_for_segwit(&self.predecessor_id, path);
+
+                self.btc_legacy
+                    .insert(path.to_string(), Self::btc_account(path, legacy, false)?);
+                self.btc_segwit
+                    .insert(path.to_string(), Self::btc_account(path, segwit, true)?);
+            }
+            #[cfg(feature = "evm")]
+            Network::EVM => {
+                let derived_address =
+                    address::get_derived_address_for_evm(&self.predecessor_id, path);
+                let evm_address = EvmAddress::from_str(&derived_address.address)
+                    .map_err(|err| format!("Invalid derived EVM address for {}: {}", path, err))?;
+
+                self.evm.insert(
+                    path.to_string(),
+                    EvmDerivedAccount {
+                        path: path.to_string(),
+                        address: evm_address,
+                        derived_address,
+                    },
+                );
+            }
+            Network::Near => {
+                let derived_address =
+                    address::get_derived_address_for_evm(&self.predecessor_id, path);
+                self.near
+                    .insert(path.to_string(), Self::near_account(path, derived_address)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Legacy Bitcoin account of the default path
+    #[cfg(feature = "bitcoin")]
+    pub fn btc_legacy(&self) -> Option<&BtcDerivedAccount> {
+        self.btc_legacy.get(&self.btc_path)
+    }
+
+    /// Segwit Bitcoin account of the default path
+    #[cfg(feature = "bitcoin")]
+    pub fn btc_segwit(&self) -> Option<&BtcDerivedAccount> {
+        self.btc_segwit.get(&self.btc_path)
+    }
+
+    /// EVM account of the default path
+    #[cfg(feature = "evm")]
+    pub fn evm(&self) -> Option<&EvmDerivedAccount> {
+        self.evm.get(&self.evm_path)
+    }
+
+    /// NEAR account of the default path
+    pub fn near(&self) -> Option<&NearDerivedAccount> {
+        self.near.get(&self.near_path)
+    }
+
+    /// Default Bitcoin derivation path
+    pub fn btc_path(&self) -> &str {
+        &self.btc_path
+    }
+
+    /// Default EVM derivation path
+    pub fn evm_path(&self) -> &str {
+        &self.evm_path
+    }
+
+    /// Default NEAR derivation path
+    pub fn near_path(&self) -> &str {
+        &self.near_path
+    }
+
+    fn near_account(
+        path: &str,
+        derived_address: DerivedAddress,
+    ) -> Result<NearDerivedAccount, Box<dyn Error + Send + Sync>> {
+        let account_id = derived_address
+            .address
+            .parse()
+            .map_err(|err| format!("Invalid derived NEAR account for {}: {}", path, err))?;
+
+        // NEAR keys hold the uncompressed point without its 0x04 prefix
+        let encoded_point = derived_address.public_key.to_encoded_point(false);
+        let public_key = NearPublicKey::SECP256K1(Secp256K1PublicKey::try_from(
+            &encoded_point.as_bytes()[1..],
+        )?);
+
+        Ok(NearDerivedAccount {
+            path: path.to_string(),
+            account_id,
+            public_key,
+            derived_address,
+        })
+    }
+
+    #[cfg(feature = "bitcoin")]
+    fn btc_account(
+        path: &str,
+        derived_address: DerivedAddress,
+        compressed: bool,
+    ) -> Result<BtcDerivedAccount, Box<dyn Error + Send + Sync>> {
+        let address = BtcAddress::from_str(&derived_address.address)
+            .map_err(|err| format!("Invalid derived Bitcoin address for {}: {}", path, err))?
+            .require_network(BtcNetwork::Regtest)
+            .map_err(|err| {
+                format!(
+                    "Derived Bitcoin address for {} is not regtest: {}",
+                    path, err
+                )
+            })?;
+
+        // Legacy addresses are derived from the uncompressed key, segwit ones from the compressed key
+        let encoded_point = derived_address.public_key.to_encoded_point(false);
+        let secp_public_key = bitcoin::secp256k1::PublicKey::from_slice(encoded_point.as_bytes())?;
+        let public_key = if compressed {
+            BtcPublicKey::new(secp_public_key)
+        } else {
+            BtcPublicKey::new_uncompressed(secp_public_key)
+        };
+
+        Ok(BtcDerivedAccount {
+            path: path.to_string(),
+            script_pubkey: address.script_pubkey(),
+            address,
+            public_key,
+            derived_address,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn predecessor_id() -> AccountId {
+        "omnitester.testnet".parse().unwrap()
+    }
+
+    #[test]
+    fn test_new_derives_the_default_paths_of_the_modules() {
+        let derived_accounts = DerivedAccounts::new(
+            predecessor_id(),
+            &[Network::Near],
+            "bitcoin-1",
+            "ethereum-1",
+            "near-1",
+        )
+        .unwrap();
+
+        assert_eq!(derived_accounts.near_path(), "near-1");
+        assert_eq!(derived_accounts.near().unwrap().path, "near-1");
+        #[cfg(feature = "bitcoin")]
+        assert!(derived_accounts.btc_segwit().is_none());
+        #[cfg(feature = "evm")]
+        assert!(derived_accounts.evm().is_none());
+    }
+
+    #[test]
+    fn test_near_account_is_the_eth_implicit_account_of_the_key() {
+        let mut derived_accounts =
+            DerivedAccounts::new(predecessor_id(), &[], "bitcoin-1", "ethereum-1", "near-1")
+                .unwrap();
+        derived_accounts
+            .register_path(&Network::Near, "near-2")
+            .unwrap();
+
+        let near = &derived_accounts.near["near-2"];
+        let evm_address = address::get_derived_address_for_evm(&predecessor_id(), "near-2");
+        assert_eq!(near.account_id.as_str(), evm_address.address);
+        assert!(near.account_id.as_str().starts_with("0x"));
+        assert_eq!(near.public_key.key_data().len(), 64);
+        assert!(derived_accounts.near().is_none());
+    }
+
+    #[cfg(feature = "bitcoin")]
+    #[test]
+    fn test_register_bitcoin_path() {
+        let mut derived_accounts =
+            DerivedAccounts::new(predecessor_id(), &[], "bitcoin-1", "ethereum-1", "near-1")
+                .unwrap();
+        derived_accounts
+            .register_path(&Network::Bitcoin, "bitcoin-2")
+            .unwrap();
+
+        let legacy = &derived_accounts.btc_legacy["bitcoin-2"];
+        let segwit = &derived_accounts.btc_segwit["bitcoin-2"];
+        assert!(!legacy.public_key.compressed);
+        assert!(segwit.public_key.compressed);
+        assert_eq!(segwit.script_pubkey, segwit.address.script_pubkey());
+        assert_eq!(
+            segwit.address.to_string(),
+            address::get_derived_address_for_segwit(&predecessor_id(), "bitcoin-2").address
+        );
+    }
+
+    #[cfg(feature = "evm")]
+    #[test]
+    fn test_register_evm_path() {
+        let mut derived_accounts =
+            DerivedAccounts::new(predecessor_id(), &[Network::EVM], "b", "ethereum-1", "n")
+                .unwrap();
+        derived_accounts
+            .register_path(&Network::EVM, "ethereum-2")
+            .unwrap();
+
+        assert_eq!(derived_accounts.evm.len(), 2);
+        assert_ne!(
+            derived_accounts.evm().unwrap().address,
+            derived_accounts.evm["ethereum-2"].address
+        );
+    }
+}
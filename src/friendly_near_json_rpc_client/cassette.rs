@@ -0,0 +1,627 @@
+//! Record and replay the JSON-RPC traffic of the friendly client
+//!
+//! A `Cassette` runs a small local HTTP stand-in that the `FriendlyNearJsonRpcClient` connects to.
+//! In `Record` mode every request is forwarded to the upstream RPC and the request / response pair
+//! is stored in the cassette file. In `Replay` mode the responses are served from the cassette file
+//! by request fingerprint, so tests can run deterministically without access to the NEAR network.
+//! Requests are read with a `Content-Length` or chunked, others are answered with `411`. A request
+//! missing from the cassette is answered with a JSON-RPC error naming its fingerprint.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha3::{Digest, Sha3_256};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+const CASSETTE_VERSION: u32 = 1;
+
+/// Defines whether the cassette records live traffic or replays a fixture file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+/// A single recorded JSON-RPC request / response pair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub fingerprint: String,
+    pub method: String,
+    pub request: Value,
+    pub response: Value,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    version: u32,
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug)]
+struct CassetteState {
+    mode: CassetteMode,
+    path: PathBuf,
+    upstream_url: String,
+    http_client: reqwest::Client,
+    interactions: Mutex<Vec<Interaction>>,
+    // Number of interactions in the cassette file, concurrent records must not write older ones
+    persisted: tokio::sync::Mutex<usize>,
+    // Number of times each fingerprint has been served during replay
+    replay_cursors: Mutex<HashMap<String, usize>>,
+}
+
+/// Local stand-in for a NEAR JSON-RPC endpoint that records or replays the traffic
+#[derive(Debug)]
+pub struct Cassette {
+    url: String,
+    state: Arc<CassetteState>,
+    server: JoinHandle<()>,
+}
+
+impl Cassette {
+    /// Start the cassette server for the given fixture file
+    ///
+    /// In `Record` mode the requests are forwarded to `upstream_url`, in `Replay` mode the
+    /// fixture file must exist and `upstream_url` is ignored.
+    pub async fn start<P: AsRef<Path>>(
+        mode: CassetteMode,
+        path: P,
+        upstream_url: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+
+        let interactions = match mode {
+            CassetteMode::Record => Vec::new(),
+            CassetteMode::Replay => {
+                let contents = fs::read_to_string(&path).map_err(|err| {
+                    format!("Failed to read cassette {}: {}", path.display(), err)
+                })?;
+                let file: CassetteFile = serde_json::from_str(&contents)?;
+                if file.version != CASSETTE_VERSION {
+                    return Err(format!(
+                        "Unsupported cassette version {} in {}",
+                        file.version,
+                        path.display()
+                    )
+                    .into());
+                }
+                file.interactions
+            }
+        };
+
+        let state = Arc::new(CassetteState {
+            mode,
+            path,
+            upstream_url: upstream_url.to_string(),
+            http_client: reqwest::Client::new(),
+            interactions: Mutex::new(interactions),
+            persisted: tokio::sync::Mutex::new(0),
+            replay_cursors: Mutex::new(HashMap::new()),
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+
+        let server_state = state.clone();
+        let server = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = server_state.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle_connection(stream, state).await {
+                        error!(error = %err, "Cassette failed to serve request");
+                    }
+                });
+            }
+        });
+
+        info!(url = %url, mode = ?mode, path = %state.path.display(), "Cassette started");
+
+        Ok(Self { url, state, server })
+    }
+
+    /// URL the JSON-RPC client has to connect to
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Mode the cassette is running in
+    pub fn mode(&self) -> CassetteMode {
+        self.state.mode
+    }
+
+    /// Interactions recorded so far (or loaded from the fixture file when replaying)
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state.interactions.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Cassette {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Computes the fingerprint of a JSON-RPC request, ignoring its id
+pub fn fingerprint(request: &Value) -> String {
+    let canonical = serde_json::json!({
+        "method": request["method"],
+        "params": canonicalize(&request["params"]),
+    })
+    .to_string();
+
+    let mut hasher = Sha3_256::new();
+    hasher.update(canonical);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Copy of `value` with the keys of every object sorted
+///
+/// The serialization then does not depend on the key order, which serde_json keeps as inserted
+/// when any crate of the build enables its `preserve_order` feature.
+fn canonicalize(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<_> = object.iter().collect();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.clone(), canonicalize(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.iter().map(canonicalize).collect()),
+        _ => value.clone(),
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    state: Arc<CassetteState>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(body) = read_http_body(&mut stream).await? else {
+        return write_http_response(&mut stream, "411 Length Required", "").await;
+    };
+    let request: Value = serde_json::from_slice(&body)?;
+
+    let mut response = match state.mode {
+        CassetteMode::Record => record(&state, request.clone(), body).await?,
+        CassetteMode::Replay => replay(&state, &request),
+    };
+
+    // Answer with the id of the incoming request
+    if let Some(object) = response.as_object_mut() {
+        object.insert("id".to_string(), request["id"].clone());
+    }
+
+    write_http_response(&mut stream, "200 OK", &response.to_string()).await
+}
+
+async fn write_http_response(
+    stream: &mut TcpStream,
+    status: &str,
+    payload: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let http_response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        payload.len(),
+        payload
+    );
+    stream.write_all(http_response.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+async fn record(
+    state: &CassetteState,
+    request: Value,
+    body: Vec<u8>,
+) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    let response: Value = state
+        .http_client
+        .post(&state.upstream_url)
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    debug!(method = %request["method"], "Recorded NEAR RPC interaction");
+
+    let interaction = Interaction {
+        fingerprint: fingerprint(&request),
+        method: request["method"].as_str().unwrap_or_default().to_string(),
+        request,
+        response: response.clone(),
+    };
+
+    // Persist after every interaction so the fixture survives a failing test
+    let file = {
+        let mut interactions = state.interactions.lock().unwrap();
+        interactions.push(interaction);
+        CassetteFile {
+            version: CASSETTE_VERSION,
+            interactions: interactions.clone(),
+        }
+    };
+    let contents = serde_json::to_string_pretty(&file)?;
+
+    persist(state, file.interactions.len(), contents).await?;
+
+    Ok(response)
+}
+
+/// Writes the cassette file, unless a concurrent record already wrote more interactions
+async fn persist(
+    state: &CassetteState,
+    interactions: usize,
+    contents: String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut persisted = state.persisted.lock().await;
+    if interactions <= *persisted {
+        return Ok(());
+    }
+
+    if let Some(parent) = state.path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(&state.path, contents).await?;
+    *persisted = interactions;
+    drop(persisted);
+
+    Ok(())
+}
+
+fn replay(state: &CassetteState, request: &Value) -> Value {
+    let fingerprint = fingerprint(request);
+    let responses: Vec<Value> = state
+        .interactions
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|interaction| interaction.fingerprint == fingerprint)
+        .map(|interaction| interaction.response.clone())
+        .collect();
+
+    debug!(method = %request["method"], fingerprint = %fingerprint, "Replaying NEAR RPC interaction");
+
+    if responses.is_empty() {
+        let message = format!(
+            "No recorded response for {} request with fingerprint {}",
+            request["method"], fingerprint
+        );
+        warn!(%message, "Cassette replay miss");
+        return serde_json::json!({
+            "jsonrpc": "2.0",
+            "error": { "code": -32000, "message": "Server error", "data": message },
+        });
+    }
+
+    // Identical requests are served in recording order, repeating the last response when exhausted
+    let served = *state
+        .replay_cursors
+        .lock()
+        .unwrap()
+        .entry(fingerprint)
+        .and_modify(|served| *served += 1)
+        .or_insert(1);
+
+    responses[(served - 1).min(responses.len() - 1)].clone()
+}
+
+/// Reads the body of a request sent with a `Content-Length` or chunked, `None` without either
+async fn read_http_body(
+    stream: &mut TcpStream,
+) -> Result<Option<Vec<u8>>, Box<dyn Error + Send + Sync>> {
+    let mut buffer = Vec::new();
+
+    // Read until the end of the headers
+    let headers_end = loop {
+        if let Some(position) = find(&buffer, b"\r\n\r\n") {
+            break position + 4;
+        }
+        read_more(stream, &mut buffer, "headers").await?;
+    };
+
+    let headers = String::from_utf8_lossy(&buffer[..headers_end]).to_string();
+    let header = |header: &str| {
+        headers.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case(header)
+                .then(|| value.trim().to_string())
+        })
+    };
+
+    if header("transfer-encoding").is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+    {
+        return read_chunked_body(stream, buffer.split_off(headers_end))
+            .await
+            .map(Some);
+    }
+
+    let Some(content_length) = header("content-length") else {
+        return Ok(None);
+    };
+    let content_length: usize = content_length.parse()?;
+
+    while buffer.len() < headers_end + content_length {
+        read_more(stream, &mut buffer, "body").await?;
+    }
+
+    Ok(Some(
+        buffer[headers_end..headers_end + content_length].to_vec(),
+    ))
+}
+
+/// Decodes a chunked body, `buffer` holds what was already read after the headers
+async fn read_chunked_body(
+    stream: &mut TcpStream,
+    mut buffer: Vec<u8>,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let mut body = Vec::new();
+    let mut position = 0;
+
+    loop {
+        let size_end = loop {
+            if let Some(offset) = find(&buffer[position..], b"\r\n") {
+                break position + offset;
+            }
+            read_more(stream, &mut buffer, "body").await?;
+        };
+
+        // The chunk size is in hexadecimal, optionally followed by extensions
+        let size_line = String::from_utf8_lossy(&buffer[position..size_end]).to_string();
+        let size_digits = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_digits, 16)
+            .map_err(|err| format!("Invalid chunk size {:?}: {}", size_line, err))?;
+        position = size_end + 2;
+
+        // The trailers of the last chunk are ignored, the connection is closed after the response
+        if size == 0 {
+            return Ok(body);
+        }
+
+        while buffer.len() < position + size + 2 {
+            read_more(stream, &mut buffer, "body").await?;
+        }
+        body.extend_from_slice(&buffer[position..position + size]);
+        position += size + 2;
+    }
+}
+
+async fn read_more(
+    stream: &mut TcpStream,
+    buffer: &mut Vec<u8>,
+    part: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut chunk = [0u8; 4096];
+    let read = stream.read(&mut chunk).await?;
+    if read == 0 {
+        return Err(format!(
+            "Connection closed before the request {} were received",
+            part
+        )
+        .into());
+    }
+    buffer.extend_from_slice(&chunk[..read]);
+
+    Ok(())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// JSON-RPC upstream answering every request with `respond`, stopped when the handle is aborted
+#[cfg(test)]
+pub(crate) async fn stub_upstream(
+    respond: impl Fn(&Value) -> Value + Send + 'static,
+) -> (String, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let body = read_http_body(&mut stream).await.unwrap().unwrap();
+            let request: Value = serde_json::from_slice(&body).unwrap();
+            let mut response = respond(&request);
+            response["jsonrpc"] = "2.0".into();
+            response["id"] = request["id"].clone();
+            write_http_response(&mut stream, "200 OK", &response.to_string())
+                .await
+                .unwrap();
+        }
+    });
+    (url, server)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fingerprint_ignores_request_id() {
+        let first = json!({
+            "jsonrpc": "2.0",
+            "id": "dontcare",
+            "method": "query",
+            "params": { "request_type": "view_account", "account_id": "omnitester.testnet" },
+        });
+        let second = json!({
+            "id": 42,
+            "params": { "account_id": "omnitester.testnet", "request_type": "view_account" },
+            "method": "query",
+            "jsonrpc": "2.0",
+        });
+
+        assert_eq!(fingerprint(&first), fingerprint(&second));
+    }
+
+    #[test]
+    fn test_fingerprint_depends_on_params() {
+        let first = json!({ "method": "block", "params": { "finality": "final" } });
+        let second = json!({ "method": "block", "params": { "finality": "optimistic" } });
+
+        assert_ne!(fingerprint(&first), fingerprint(&second));
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_nested_key_order() {
+        let first = json!({ "method": "query", "params": [{ "a": 1, "b": { "c": 2, "d": 3 } }] });
+        let second = json!({ "method": "query", "params": [{ "b": { "d": 3, "c": 2 }, "a": 1 }] });
+
+        assert_eq!(fingerprint(&first), fingerprint(&second));
+        assert_eq!(
+            canonicalize(&second["params"]).to_string(),
+            r#"[{"a":1,"b":{"c":2,"d":3}}]"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_miss_answers_a_json_rpc_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        fs::write(
+            &path,
+            json!({ "version": 1, "interactions": [] }).to_string(),
+        )
+        .unwrap();
+        let cassette = Cassette::start(CassetteMode::Replay, &path, "")
+            .await
+            .unwrap();
+        let request = json!({ "jsonrpc": "2.0", "id": 3, "method": "status", "params": [] });
+
+        let response: Value = reqwest::Client::new()
+            .post(cassette.url())
+            .json(&request)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(response["id"], 3);
+        assert_eq!(response["error"]["code"], -32000);
+        assert!(response["error"]["data"]
+            .as_str()
+            .unwrap()
+            .contains(&fingerprint(&request)));
+    }
+
+    /// Upstream answering every request with its params and a call counter
+    async fn echo_upstream() -> (String, JoinHandle<()>) {
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        stub_upstream(move |request| {
+            let calls = calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            json!({ "result": { "params": request["params"], "calls": calls } })
+        })
+        .await
+    }
+
+    /// Sends a raw HTTP request and returns the raw response
+    async fn send_raw(url: &str, request: &str) -> String {
+        let mut stream = TcpStream::connect(url.trim_start_matches("http://"))
+            .await
+            .unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay_without_upstream() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fixtures/cassette.json");
+        let (upstream_url, upstream) = echo_upstream().await;
+        let http_client = reqwest::Client::new();
+        let request = |id: u64, finality: &str| json!({ "jsonrpc": "2.0", "id": id, "method": "block", "params": { "finality": finality } });
+
+        let cassette = Cassette::start(CassetteMode::Record, &path, &upstream_url)
+            .await
+            .unwrap();
+        let mut recorded = Vec::new();
+        for finality in ["final", "optimistic", "final"] {
+            let response: Value = http_client
+                .post(cassette.url())
+                .json(&request(1, finality))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            recorded.push(response);
+        }
+        assert_eq!(cassette.interactions().len(), 3);
+        drop(cassette);
+        upstream.abort();
+
+        let cassette = Cassette::start(CassetteMode::Replay, &path, "")
+            .await
+            .unwrap();
+        for (finality, recorded) in ["final", "optimistic", "final", "final"]
+            .into_iter()
+            .zip(recorded.iter().chain([&recorded[2]]))
+        {
+            let response: Value = http_client
+                .post(cassette.url())
+                .json(&request(7, finality))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            assert_eq!(response["result"], recorded["result"]);
+            assert_eq!(response["id"], 7);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunked_and_length_less_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        let (upstream_url, upstream) = echo_upstream().await;
+        let cassette = Cassette::start(CassetteMode::Record, &path, &upstream_url)
+            .await
+            .unwrap();
+
+        let body =
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "status", "params": [] }).to_string();
+        let (first, second) = body.split_at(10);
+        let chunked = format!(
+            "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n{:x};ext=1\r\n{}\r\n0\r\n\r\n",
+            first.len(),
+            first,
+            second.len(),
+            second
+        );
+        let response = send_raw(cassette.url(), &chunked).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert_eq!(cassette.interactions()[0].method, "status");
+
+        let without_length = format!("POST / HTTP/1.1\r\nHost: localhost\r\n\r\n{}", body);
+        let response = send_raw(cassette.url(), &without_length).await;
+        assert!(
+            response.starts_with("HTTP/1.1 411 Length Required"),
+            "{}",
+            response
+        );
+        assert_eq!(cassette.interactions().len(), 1);
+
+        upstream.abort();
+    }
+}
@@ -1,28 +1,41 @@
 //! A friendly way to interact with the NEAR blockchain using the NEAR JSON RPC client
 use near_crypto::{InMemorySigner, PublicKey};
-use near_jsonrpc_client::methods;
+use near_jsonrpc_client::errors::{JsonRpcError, JsonRpcServerError};
 use near_jsonrpc_client::methods::send_tx::RpcSendTransactionRequest;
 use near_jsonrpc_client::methods::tx::{
     RpcTransactionError, RpcTransactionResponse, RpcTransactionStatusRequest, TransactionInfo,
 };
 use near_jsonrpc_client::{methods::query::RpcQueryRequest, JsonRpcClient};
-use near_jsonrpc_primitives::types::query::QueryResponseKind;
-use near_primitives::action::{Action, DeployContractAction, FunctionCallAction};
-use near_primitives::transaction::{Transaction, TransactionV0};
-use near_primitives::types::{BlockReference, Finality, FunctionArgs};
-use near_primitives::views::TxExecutionStatus;
+use near_jsonrpc_primitives::types::query::{QueryResponseKind, RpcQueryError};
+use near_primitives::account::{AccessKey, AccessKeyPermission, FunctionCallPermission};
+use near_primitives::action::delegate::{DelegateAction, NonDelegateAction, SignedDelegateAction};
+use near_primitives::action::{
+    Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
+    DeployContractAction, FunctionCallAction, StakeAction, TransferAction,
+};
+use near_primitives::transaction::{SignedTransaction, Transaction, TransactionV0};
+use near_primitives::types::{Balance, BlockHeight, BlockReference, Finality, FunctionArgs, Gas};
+use near_primitives::views::{AccountView, TxExecutionStatus};
 use near_primitives::{hash::CryptoHash, views::QueryRequest};
 use near_sdk::AccountId;
 use std::error::Error;
 use std::time::{Duration, Instant};
+use tracing::{debug, instrument, warn};
 
 // local modules
+pub mod cassette;
 pub mod near_network_config;
+pub mod outcome;
 mod parser;
 
 // import local modules
 use crate::NearAccount;
+use cassette::Cassette;
 use near_network_config::{get_rpc_url, NearNetworkConfig};
+use outcome::{
+    AccessKeyOutcome, CreateAccountOutcome, DeleteAccountOutcome, StakeOutcome, TransactionOutcome,
+    TransferOutcome,
+};
 use parser::ParseResult;
 
 /// Wrapper around the Near JsonRpcClient that provides a more user-friendly interface
@@ -30,62 +43,115 @@ pub struct FriendlyNearJsonRpcClient {
     client: JsonRpcClient,
     account_config: NearAccount,
     signer: InMemorySigner,
+    wait_until: TxExecutionStatus,
 }
 
 const TIMEOUT: Duration = Duration::from_secs(300);
+/// Number of blocks a signed delegate action stays valid for
+const DELEGATE_ACTION_TTL: BlockHeight = 120;
 
 impl FriendlyNearJsonRpcClient {
     pub fn new(network: NearNetworkConfig, account_config: NearAccount) -> Self {
+        Self::new_with_rpc_url(get_rpc_url(network), account_config)
+    }
+
+    /// Create a client connected to the given RPC URL
+    pub fn new_with_rpc_url(rpc_url: &str, account_config: NearAccount) -> Self {
         let account_id = account_config.account_id.clone();
         let private_key = account_config.private_key.clone();
         let signer: InMemorySigner = InMemorySigner::from_secret_key(account_id, private_key);
 
         Self {
-            client: Self::get_near_rpc_client(network),
+            client: JsonRpcClient::connect(rpc_url),
+            account_config,
+            signer,
+            wait_until: TxExecutionStatus::Final,
+        }
+    }
+
+    /// Create a client that records or replays its traffic through the given cassette
+    pub fn new_with_cassette(cassette: &Cassette, account_config: NearAccount) -> Self {
+        Self::new_with_rpc_url(cassette.url(), account_config)
+    }
+
+    /// Create a client for another account that shares the connection and settings of this one
+    pub fn with_account(&self, account_config: NearAccount) -> Self {
+        let signer = InMemorySigner::from_secret_key(
+            account_config.account_id.clone(),
+            account_config.private_key.clone(),
+        );
+
+        Self {
+            client: self.client.clone(),
             account_config,
             signer,
+            wait_until: self.wait_until.clone(),
         }
     }
 
+    /// Get the account the client signs with
+    pub const fn account(&self) -> &NearAccount {
+        &self.account_config
+    }
+
+    /// Get the URL of the RPC server the client sends requests to
+    pub fn rpc_url(&self) -> &str {
+        self.client.server_addr()
+    }
+
+    /// Set the default execution level the client waits for when sending transactions
+    pub const fn with_wait_until(mut self, wait_until: TxExecutionStatus) -> Self {
+        self.wait_until = wait_until;
+        self
+    }
+
+    /// Get the default execution level the client waits for when sending transactions
+    pub const fn wait_until(&self) -> &TxExecutionStatus {
+        &self.wait_until
+    }
+
     /// Deploy a contract to the NEAR blockchain using the default account
     pub async fn deploy_contract(
         &self,
         contract_wasm: Vec<u8>,
     ) -> Result<RpcTransactionResponse, Box<dyn std::error::Error>> {
-        let account_id = self.account_config.account_id.clone();
-
-        let (nonce, block_hash) = self
-            .get_nonce_and_block_hash(account_id.clone(), self.account_config.public_key.clone())
+        self.deploy_contract_with_wait_until(contract_wasm, self.wait_until.clone())
             .await
-            .unwrap();
+    }
 
-        let nonce = nonce + 1;
+    /// Deploy a contract using the default account, waiting until the given execution level
+    pub async fn deploy_contract_with_wait_until(
+        &self,
+        contract_wasm: Vec<u8>,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, Box<dyn std::error::Error>> {
+        let account_id = self.account_config.account_id.clone();
 
         let deploy_action = Action::DeployContract(DeployContractAction {
             code: contract_wasm,
         });
 
-        let near_tx: Transaction = Transaction::V0(TransactionV0 {
-            signer_id: account_id.clone(),
-            public_key: self.signer.public_key(),
-            nonce,
-            receiver_id: account_id,
-            block_hash,
-            actions: vec![deploy_action],
-        });
-
-        let signer: near_crypto::Signer = self.signer.clone().into();
-
         // Sign and send the transaction
         let request = RpcSendTransactionRequest {
-            signed_transaction: near_tx.sign(&signer),
-            wait_until: TxExecutionStatus::Final,
+            signed_transaction: self
+                .sign_transaction(account_id, vec![deploy_action])
+                .await?,
+            wait_until,
         };
 
         self.send_transaction_request(request).await
     }
 
     /// Send a transaction request to the NEAR blockchain
+    #[instrument(
+        skip_all,
+        fields(
+            transaction_hash = %request.signed_transaction.get_hash(),
+            signer_id = %request.signed_transaction.transaction.signer_id(),
+            receiver_id = %request.signed_transaction.transaction.receiver_id(),
+            wait_until = ?request.wait_until,
+        )
+    )]
     pub async fn send_transaction_request(
         &self,
         request: RpcSendTransactionRequest,
@@ -93,16 +159,25 @@ impl FriendlyNearJsonRpcClient {
         let sent_at: Instant = Instant::now();
 
         match self.client.call(request.clone()).await {
-            Ok(response) => Ok(response),
+            Ok(response) => {
+                debug!(elapsed = ?sent_at.elapsed(), "Transaction executed");
+                Ok(response)
+            }
             Err(err) => {
                 if matches!(err.handler_error(), Some(RpcTransactionError::TimeoutError))
                     || err.to_string().contains("408 Request Timeout")
                 {
+                    debug!("Transaction request timed out, polling its status");
                     let tx_hash = request.signed_transaction.get_hash();
                     let sender_account_id =
                         request.signed_transaction.transaction.signer_id().clone();
-                    self.wait_for_transaction(tx_hash, sender_account_id, sent_at)
-                        .await
+                    self.wait_for_transaction(
+                        tx_hash,
+                        sender_account_id,
+                        request.wait_until.clone(),
+                        sent_at,
+                    )
+                    .await
                 } else {
                     Err(err.into())
                 }
@@ -116,6 +191,44 @@ impl FriendlyNearJsonRpcClient {
         JsonRpcClient::connect(rpc_url)
     }
 
+    /// View the state of an account, including the hash of its deployed code
+    pub async fn view_account(&self, account_id: AccountId) -> Result<AccountView, Box<dyn Error>> {
+        let request = RpcQueryRequest {
+            block_reference: BlockReference::Finality(Finality::Final),
+            request: QueryRequest::ViewAccount { account_id },
+        };
+
+        let response = self.client.call(request).await?;
+
+        if let QueryResponseKind::ViewAccount(account_view) = response.kind {
+            return Ok(account_view);
+        }
+
+        Err("Failed to parse view account result".into())
+    }
+
+    /// View the state of an account, `None` if it does not exist, other RPC errors are returned
+    pub async fn view_account_if_exists(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AccountView>, Box<dyn Error>> {
+        let request = RpcQueryRequest {
+            block_reference: BlockReference::Finality(Finality::Final),
+            request: QueryRequest::ViewAccount { account_id },
+        };
+
+        match self.client.call(request).await {
+            Ok(response) => match response.kind {
+                QueryResponseKind::ViewAccount(account_view) => Ok(Some(account_view)),
+                _ => Err("Failed to parse view account result".into()),
+            },
+            Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+                RpcQueryError::UnknownAccount { .. },
+            ))) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     /// Function to call a contract with a generic return type
     pub async fn call_contract<T>(
         &self,
@@ -181,77 +294,491 @@ impl FriendlyNearJsonRpcClient {
     pub async fn send_action(
         &self,
         action: FunctionCallAction,
+    ) -> Result<RpcTransactionResponse, Box<dyn Error>> {
+        self.send_action_with_wait_until(action, self.wait_until.clone())
+            .await
+    }
+
+    /// Send a function call action, waiting until the given execution level
+    pub async fn send_action_with_wait_until(
+        &self,
+        action: FunctionCallAction,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, Box<dyn Error>> {
+        let signing_action = Action::FunctionCall(Box::new(action));
+
+        self.send_actions_with_wait_until(vec![signing_action], wait_until)
+            .await
+    }
+
+    pub async fn send_actions(
+        &self,
+        actions: Vec<Action>,
+    ) -> Result<RpcTransactionResponse, Box<dyn Error>> {
+        self.send_actions_with_wait_until(actions, self.wait_until.clone())
+            .await
+    }
+
+    /// Send a list of actions, waiting until the given execution level
+    pub async fn send_actions_with_wait_until(
+        &self,
+        actions: Vec<Action>,
+        wait_until: TxExecutionStatus,
     ) -> Result<RpcTransactionResponse, Box<dyn Error>> {
         let account_id = self.account_config.account_id.clone();
 
-        let (nonce, block_hash) = self
-            .get_nonce_and_block_hash(account_id.clone(), self.account_config.public_key.clone())
+        // Sign and send the transaction
+        let request = RpcSendTransactionRequest {
+            signed_transaction: self.sign_transaction(account_id, actions).await?,
+            wait_until,
+        };
+
+        self.send_transaction_request(request).await
+    }
+
+    /// Wait for an already sent transaction to reach the given execution level
+    ///
+    /// Useful to upgrade a transaction sent with a lower `wait_until` to a later one
+    pub async fn wait_for_transaction_status(
+        &self,
+        tx_hash: CryptoHash,
+        sender_account_id: AccountId,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, Box<dyn Error>> {
+        self.wait_for_transaction(tx_hash, sender_account_id, wait_until, Instant::now())
             .await
-            .unwrap();
+    }
 
-        let nonce = nonce + 1;
+    /// Call a change method of the contract deployed on the default account
+    pub async fn function_call(
+        &self,
+        method_name: &str,
+        args: serde_json::Value,
+        gas: Gas,
+        deposit: Balance,
+    ) -> Result<TransactionOutcome, Box<dyn Error>> {
+        self.function_call_with_wait_until(method_name, args, gas, deposit, self.wait_until.clone())
+            .await
+    }
 
-        let signing_action = Action::FunctionCall(Box::new(action));
+    /// Call a change method of the contract deployed on the default account, waiting until the
+    /// given execution level
+    pub async fn function_call_with_wait_until(
+        &self,
+        method_name: &str,
+        args: serde_json::Value,
+        gas: Gas,
+        deposit: Balance,
+        wait_until: TxExecutionStatus,
+    ) -> Result<TransactionOutcome, Box<dyn Error>> {
+        let account_id = self.account_config.account_id.clone();
 
-        let near_tx: Transaction = Transaction::V0(TransactionV0 {
-            signer_id: account_id.clone(),
-            public_key: self.signer.public_key(),
-            nonce,
-            receiver_id: account_id.clone(),
-            block_hash,
-            actions: vec![signing_action],
+        let function_call_action = Action::FunctionCall(Box::new(FunctionCallAction {
+            method_name: method_name.to_string(),
+            args: args.to_string().into_bytes(),
+            gas,
+            deposit,
+        }));
+
+        self.sign_and_send_actions(account_id, vec![function_call_action], wait_until)
+            .await
+    }
+
+    /// Create a sub account of the default account with an initial balance and a full access key
+    pub async fn create_sub_account(
+        &self,
+        new_account_id: AccountId,
+        public_key: PublicKey,
+        initial_balance: Balance,
+    ) -> Result<CreateAccountOutcome, Box<dyn Error>> {
+        self.create_sub_account_with_wait_until(
+            new_account_id,
+            public_key,
+            initial_balance,
+            self.wait_until.clone(),
+        )
+        .await
+    }
+
+    /// Create a sub account of the default account, waiting until the given execution level
+    pub async fn create_sub_account_with_wait_until(
+        &self,
+        new_account_id: AccountId,
+        public_key: PublicKey,
+        initial_balance: Balance,
+        wait_until: TxExecutionStatus,
+    ) -> Result<CreateAccountOutcome, Box<dyn Error>> {
+        let actions = vec![
+            Action::CreateAccount(CreateAccountAction {}),
+            Action::Transfer(TransferAction {
+                deposit: initial_balance,
+            }),
+            Action::AddKey(Box::new(AddKeyAction {
+                public_key: public_key.clone(),
+                access_key: AccessKey::full_access(),
+            })),
+        ];
+
+        let outcome = self
+            .sign_and_send_actions(new_account_id.clone(), actions, wait_until)
+            .await?;
+
+        Ok(CreateAccountOutcome {
+            account_id: new_account_id,
+            public_key,
+            initial_balance,
+            outcome,
+        })
+    }
+
+    /// Add a full access key to the default account
+    pub async fn add_full_access_key(
+        &self,
+        public_key: PublicKey,
+    ) -> Result<AccessKeyOutcome, Box<dyn Error>> {
+        self.add_full_access_key_with_wait_until(public_key, self.wait_until.clone())
+            .await
+    }
+
+    /// Add a full access key to the default account, waiting until the given execution level
+    pub async fn add_full_access_key_with_wait_until(
+        &self,
+        public_key: PublicKey,
+        wait_until: TxExecutionStatus,
+    ) -> Result<AccessKeyOutcome, Box<dyn Error>> {
+        self.add_key(public_key, AccessKey::full_access(), wait_until)
+            .await
+    }
+
+    /// Add a function call key to the default account
+    ///
+    /// An empty `method_names` list allows calling any method of `receiver_id`
+    pub async fn add_function_call_key(
+        &self,
+        public_key: PublicKey,
+        receiver_id: AccountId,
+        method_names: Vec<String>,
+        allowance: Option<Balance>,
+    ) -> Result<AccessKeyOutcome, Box<dyn Error>> {
+        self.add_function_call_key_with_wait_until(
+            public_key,
+            receiver_id,
+            method_names,
+            allowance,
+            self.wait_until.clone(),
+        )
+        .await
+    }
+
+    /// Add a function call key to the default account, waiting until the given execution level
+    pub async fn add_function_call_key_with_wait_until(
+        &self,
+        public_key: PublicKey,
+        receiver_id: AccountId,
+        method_names: Vec<String>,
+        allowance: Option<Balance>,
+        wait_until: TxExecutionStatus,
+    ) -> Result<AccessKeyOutcome, Box<dyn Error>> {
+        let access_key = AccessKey {
+            nonce: 0,
+            permission: AccessKeyPermission::FunctionCall(FunctionCallPermission {
+                allowance,
+                receiver_id: receiver_id.to_string(),
+                method_names,
+            }),
+        };
+
+        self.add_key(public_key, access_key, wait_until).await
+    }
+
+    /// Delete an access key from the default account
+    pub async fn delete_key(
+        &self,
+        public_key: PublicKey,
+    ) -> Result<AccessKeyOutcome, Box<dyn Error>> {
+        self.delete_key_with_wait_until(public_key, self.wait_until.clone())
+            .await
+    }
+
+    /// Delete an access key from the default account, waiting until the given execution level
+    pub async fn delete_key_with_wait_until(
+        &self,
+        public_key: PublicKey,
+        wait_until: TxExecutionStatus,
+    ) -> Result<AccessKeyOutcome, Box<dyn Error>> {
+        let account_id = self.account_config.account_id.clone();
+
+        let delete_key_action = Action::DeleteKey(Box::new(DeleteKeyAction {
+            public_key: public_key.clone(),
+        }));
+
+        let outcome = self
+            .sign_and_send_actions(account_id.clone(), vec![delete_key_action], wait_until)
+            .await?;
+
+        Ok(AccessKeyOutcome {
+            account_id,
+            public_key,
+            outcome,
+        })
+    }
+
+    /// Transfer NEAR from the default account to the given receiver
+    pub async fn transfer(
+        &self,
+        receiver_id: AccountId,
+        amount: Balance,
+    ) -> Result<TransferOutcome, Box<dyn Error>> {
+        self.transfer_with_wait_until(receiver_id, amount, self.wait_until.clone())
+            .await
+    }
+
+    /// Transfer NEAR from the default account, waiting until the given execution level
+    pub async fn transfer_with_wait_until(
+        &self,
+        receiver_id: AccountId,
+        amount: Balance,
+        wait_until: TxExecutionStatus,
+    ) -> Result<TransferOutcome, Box<dyn Error>> {
+        let transfer_action = Action::Transfer(TransferAction { deposit: amount });
+
+        let outcome = self
+            .sign_and_send_actions(receiver_id.clone(), vec![transfer_action], wait_until)
+            .await?;
+
+        Ok(TransferOutcome {
+            receiver_id,
+            amount,
+            outcome,
+        })
+    }
+
+    /// Stake NEAR from the default account using the given validator key
+    pub async fn stake(
+        &self,
+        stake: Balance,
+        public_key: PublicKey,
+    ) -> Result<StakeOutcome, Box<dyn Error>> {
+        self.stake_with_wait_until(stake, public_key, self.wait_until.clone())
+            .await
+    }
+
+    /// Stake NEAR from the default account, waiting until the given execution level
+    pub async fn stake_with_wait_until(
+        &self,
+        stake: Balance,
+        public_key: PublicKey,
+        wait_until: TxExecutionStatus,
+    ) -> Result<StakeOutcome, Box<dyn Error>> {
+        let account_id = self.account_config.account_id.clone();
+
+        let stake_action = Action::Stake(Box::new(StakeAction {
+            stake,
+            public_key: public_key.clone(),
+        }));
+
+        let outcome = self
+            .sign_and_send_actions(account_id.clone(), vec![stake_action], wait_until)
+            .await?;
+
+        Ok(StakeOutcome {
+            account_id,
+            public_key,
+            stake,
+            outcome,
+        })
+    }
+
+    /// Delete the default account, sending the remaining balance to the beneficiary
+    pub async fn delete_account(
+        &self,
+        beneficiary_id: AccountId,
+    ) -> Result<DeleteAccountOutcome, Box<dyn Error>> {
+        self.delete_account_with_wait_until(beneficiary_id, self.wait_until.clone())
+            .await
+    }
+
+    /// Delete the default account, waiting until the given execution level
+    pub async fn delete_account_with_wait_until(
+        &self,
+        beneficiary_id: AccountId,
+        wait_until: TxExecutionStatus,
+    ) -> Result<DeleteAccountOutcome, Box<dyn Error>> {
+        let account_id = self.account_config.account_id.clone();
+
+        let delete_account_action = Action::DeleteAccount(DeleteAccountAction {
+            beneficiary_id: beneficiary_id.clone(),
         });
-        let signer: near_crypto::Signer = self.signer.clone().into();
 
-        let signed_transaction = near_tx.sign(&signer);
+        let outcome = self
+            .sign_and_send_actions(account_id.clone(), vec![delete_account_action], wait_until)
+            .await?;
+
+        Ok(DeleteAccountOutcome {
+            account_id,
+            beneficiary_id,
+            outcome,
+        })
+    }
+
+    /// Build and sign a NEP-366 delegate action for the given account key
+    ///
+    /// The resulting `SignedDelegateAction` can be relayed by any account, see `relay_delegate_action`
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use near_crypto::SecretKey;
+    /// # use near_primitives::action::{Action, TransferAction};
+    /// # use omni_box::friendly_near_json_rpc_client::FriendlyNearJsonRpcClient;
+    /// # use omni_box::NearAccount;
+    /// # async fn example(relayer: &FriendlyNearJsonRpcClient) -> Result<(), Box<dyn std::error::Error>> {
+    /// let private_key: SecretKey = "ed25519:...".parse()?;
+    /// let sender = NearAccount {
+    ///     account_id: "sender.testnet".parse()?,
+    ///     public_key: private_key.public_key(),
+    ///     private_key,
+    /// };
+    /// let transfer = Action::Transfer(TransferAction { deposit: 1 });
+    /// let signed = relayer
+    ///     .build_signed_delegate_action(&sender, "receiver.testnet".parse()?, vec![transfer])
+    ///     .await?;
+    /// relayer.relay_delegate_action(signed).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn build_signed_delegate_action(
+        &self,
+        sender: &NearAccount,
+        receiver_id: AccountId,
+        actions: Vec<Action>,
+    ) -> Result<SignedDelegateAction, Box<dyn Error>> {
+        let access_key = self
+            .access_key_nonce(sender.account_id.clone(), sender.public_key.clone())
+            .await?;
+
+        let actions = actions
+            .into_iter()
+            .map(|action| {
+                NonDelegateAction::try_from(action)
+                    .map_err(|_| "Delegate actions cannot be nested".into())
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+        let delegate_action = DelegateAction {
+            sender_id: sender.account_id.clone(),
+            receiver_id,
+            actions,
+            nonce: access_key.nonce + 1,
+            max_block_height: access_key.block_height + DELEGATE_ACTION_TTL,
+            public_key: sender.public_key.clone(),
+        };
+
+        let sender_signer =
+            InMemorySigner::from_secret_key(sender.account_id.clone(), sender.private_key.clone());
+        let signature = sender_signer.sign(delegate_action.get_nep461_hash().as_ref());
+
+        Ok(SignedDelegateAction {
+            delegate_action,
+            signature,
+        })
+    }
+
+    /// Relay a signed delegate action, paying the gas with the default account
+    pub async fn relay_delegate_action(
+        &self,
+        signed_delegate_action: SignedDelegateAction,
+    ) -> Result<TransactionOutcome, Box<dyn Error>> {
+        self.relay_delegate_action_with_wait_until(signed_delegate_action, self.wait_until.clone())
+            .await
+    }
+
+    /// Relay a signed delegate action, waiting until the given execution level
+    pub async fn relay_delegate_action_with_wait_until(
+        &self,
+        signed_delegate_action: SignedDelegateAction,
+        wait_until: TxExecutionStatus,
+    ) -> Result<TransactionOutcome, Box<dyn Error>> {
+        let sender_id = signed_delegate_action.delegate_action.sender_id.clone();
+        let delegate_action = Action::Delegate(Box::new(signed_delegate_action));
+
+        self.sign_and_send_actions(sender_id, vec![delegate_action], wait_until)
+            .await
+    }
+
+    // private functions
+    async fn add_key(
+        &self,
+        public_key: PublicKey,
+        access_key: AccessKey,
+        wait_until: TxExecutionStatus,
+    ) -> Result<AccessKeyOutcome, Box<dyn Error>> {
+        let account_id = self.account_config.account_id.clone();
+
+        let add_key_action = Action::AddKey(Box::new(AddKeyAction {
+            public_key: public_key.clone(),
+            access_key,
+        }));
+
+        let outcome = self
+            .sign_and_send_actions(account_id.clone(), vec![add_key_action], wait_until)
+            .await?;
+
+        Ok(AccessKeyOutcome {
+            account_id,
+            public_key,
+            outcome,
+        })
+    }
+
+    async fn sign_and_send_actions(
+        &self,
+        receiver_id: AccountId,
+        actions: Vec<Action>,
+        wait_until: TxExecutionStatus,
+    ) -> Result<TransactionOutcome, Box<dyn Error>> {
+        let signed_transaction = self.sign_transaction(receiver_id, actions).await?;
+        let transaction_hash = signed_transaction.get_hash();
 
-        // Send the transaction
-        let request = methods::send_tx::RpcSendTransactionRequest {
+        let request = RpcSendTransactionRequest {
             signed_transaction,
-            wait_until: TxExecutionStatus::Final,
+            wait_until,
         };
 
-        self.send_transaction_request(request).await
+        let response = self.send_transaction_request(request).await?;
+
+        TransactionOutcome::from_response(transaction_hash, response)
     }
 
-    pub async fn send_actions(
+    async fn sign_transaction(
         &self,
+        receiver_id: AccountId,
         actions: Vec<Action>,
-    ) -> Result<RpcTransactionResponse, Box<dyn Error>> {
+    ) -> Result<SignedTransaction, Box<dyn Error>> {
         let account_id = self.account_config.account_id.clone();
 
-        let (nonce, block_hash) = self
-            .get_nonce_and_block_hash(account_id.clone(), self.account_config.public_key.clone())
-            .await
-            .unwrap();
-
-        let nonce = nonce + 1;
+        let access_key = self
+            .access_key_nonce(account_id.clone(), self.account_config.public_key.clone())
+            .await?;
 
         let near_tx: Transaction = Transaction::V0(TransactionV0 {
-            signer_id: account_id.clone(),
+            signer_id: account_id,
             public_key: self.signer.public_key(),
-            nonce,
-            receiver_id: account_id.clone(),
-            block_hash,
+            nonce: access_key.nonce + 1,
+            receiver_id,
+            block_hash: access_key.block_hash,
             actions,
         });
 
         let signer: near_crypto::Signer = self.signer.clone().into();
 
-        // Sign and send the transaction
-        let request = RpcSendTransactionRequest {
-            signed_transaction: near_tx.sign(&signer),
-            wait_until: TxExecutionStatus::Final,
-        };
-
-        self.send_transaction_request(request).await
+        Ok(near_tx.sign(&signer))
     }
 
-    // private functions
     async fn wait_for_transaction(
         &self,
         tx_hash: CryptoHash,
         sender_account_id: AccountId,
+        wait_until: TxExecutionStatus,
         sent_at: Instant,
     ) -> Result<RpcTransactionResponse, Box<dyn std::error::Error>> {
         loop {
@@ -262,16 +789,18 @@ impl FriendlyNearJsonRpcClient {
                         tx_hash,
                         sender_account_id: sender_account_id.clone(),
                     },
-                    wait_until: TxExecutionStatus::Final,
+                    wait_until: wait_until.clone(),
                 })
                 .await;
 
             if sent_at.elapsed() > TIMEOUT {
+                warn!(transaction_hash = %tx_hash, "Time limit exceeded waiting for the transaction");
                 return Err("Time limit exceeded for the transaction to be recognized".into());
             }
 
             match response {
                 Ok(response) => {
+                    debug!(transaction_hash = %tx_hash, elapsed = ?sent_at.elapsed(), "Transaction executed");
                     return Ok(response);
                 }
                 Err(err) => {
@@ -286,28 +815,237 @@ impl FriendlyNearJsonRpcClient {
         }
     }
 
-    async fn get_nonce_and_block_hash(
+    /// Current nonce of an access key, with the block the query was answered at
+    async fn access_key_nonce(
         &self,
         account_id: AccountId,
         public_key: PublicKey,
-    ) -> Result<(u64, CryptoHash), Box<dyn std::error::Error>> {
+    ) -> Result<AccessKeyNonce, Box<dyn std::error::Error>> {
         let access_key_query_response = self
             .client
             .call(RpcQueryRequest {
                 block_reference: BlockReference::latest(),
                 request: QueryRequest::ViewAccessKey {
-                    account_id: account_id.clone(),
-                    public_key: public_key.clone(),
+                    account_id,
+                    public_key,
                 },
             })
-            .await
-            .expect("Failed to call RPC");
+            .await?;
 
         match access_key_query_response.kind {
-            QueryResponseKind::AccessKey(access_key) => {
-                Ok((access_key.nonce, access_key_query_response.block_hash))
-            }
-            _ => panic!("Failed to extract current nonce"),
+            QueryResponseKind::AccessKey(access_key) => Ok(AccessKeyNonce {
+                nonce: access_key.nonce,
+                block_hash: access_key_query_response.block_hash,
+                block_height: access_key_query_response.block_height,
+            }),
+            _ => Err("Failed to extract current nonce".into()),
         }
     }
 }
+
+/// Nonce of an access key and the block it was read at
+struct AccessKeyNonce {
+    nonce: u64,
+    block_hash: CryptoHash,
+    block_height: BlockHeight,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cassette::{fingerprint, stub_upstream, CassetteMode};
+    use near_crypto::{KeyType, SecretKey};
+    use serde_json::{json, Value};
+
+    fn account(account_id: &str) -> NearAccount {
+        let private_key = SecretKey::from_seed(KeyType::ED25519, account_id);
+        NearAccount {
+            account_id: account_id.parse().unwrap(),
+            public_key: private_key.public_key(),
+            private_key,
+        }
+    }
+
+    fn query_interaction(params: Value, response: Value) -> Value {
+        let request = json!({ "method": "query", "params": params });
+        json!({
+            "fingerprint": fingerprint(&request),
+            "method": "query",
+            "request": request,
+            "response": response,
+        })
+    }
+
+    fn view_account_interaction(account_id: &str, response: Value) -> Value {
+        query_interaction(
+            json!({ "request_type": "view_account", "finality": "final", "account_id": account_id }),
+            response,
+        )
+    }
+
+    /// Client of `account_id` served by a cassette replaying the given interactions
+    async fn replay_client(
+        dir: &tempfile::TempDir,
+        account_id: &str,
+        interactions: &[Value],
+    ) -> (Cassette, FriendlyNearJsonRpcClient) {
+        let path = dir.path().join("cassette.json");
+        std::fs::write(
+            &path,
+            json!({ "version": 1, "interactions": interactions }).to_string(),
+        )
+        .unwrap();
+
+        let cassette = Cassette::start(CassetteMode::Replay, &path, "")
+            .await
+            .unwrap();
+        let client =
+            FriendlyNearJsonRpcClient::new_with_rpc_url(cassette.url(), account(account_id));
+        (cassette, client)
+    }
+
+    #[tokio::test]
+    async fn test_view_account_if_exists_only_maps_unknown_accounts_to_none() {
+        let dir = tempfile::tempdir().unwrap();
+        let interactions = [
+            view_account_interaction(
+                "missing.testnet",
+                json!({ "jsonrpc": "2.0", "error": {
+                    "name": "HANDLER_ERROR",
+                    "cause": { "name": "UNKNOWN_ACCOUNT", "info": {
+                        "requested_account_id": "missing.testnet",
+                        "block_height": 1,
+                        "block_hash": CryptoHash::default().to_string(),
+                    } },
+                    "code": -32000,
+                    "message": "Server error",
+                } }),
+            ),
+            view_account_interaction(
+                "unavailable.testnet",
+                json!({ "jsonrpc": "2.0", "error": {
+                    "name": "INTERNAL_ERROR",
+                    "cause": { "name": "INTERNAL_ERROR", "info": { "error_message": "node is syncing" } },
+                    "code": -32000,
+                    "message": "Server error",
+                } }),
+            ),
+        ];
+        let (_cassette, client) = replay_client(&dir, "omnitester.testnet", &interactions).await;
+
+        assert!(client
+            .view_account_if_exists("missing.testnet".parse().unwrap())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(client
+            .view_account_if_exists("unavailable.testnet".parse().unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_miss_names_the_fingerprint() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_cassette, client) = replay_client(&dir, "omnitester.testnet", &[]).await;
+        let request = json!({ "method": "query", "params": {
+            "request_type": "view_account",
+            "finality": "final",
+            "account_id": "omnitester.testnet",
+        } });
+
+        let err = client
+            .view_account("omnitester.testnet".parse().unwrap())
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains(&fingerprint(&request)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_signed_delegate_action() {
+        let dir = tempfile::tempdir().unwrap();
+        let sender = account("sender.testnet");
+        let access_key = query_interaction(
+            json!({
+                "request_type": "view_access_key",
+                "finality": "optimistic",
+                "account_id": "sender.testnet",
+                "public_key": sender.public_key.to_string(),
+            }),
+            json!({ "jsonrpc": "2.0", "result": {
+                "nonce": 41,
+                "permission": "FullAccess",
+                "block_height": 1000,
+                "block_hash": CryptoHash::default().to_string(),
+            } }),
+        );
+        let (_cassette, relayer) = replay_client(&dir, "relayer.testnet", &[access_key]).await;
+        let receiver_id: AccountId = "receiver.testnet".parse().unwrap();
+        let transfer = Action::Transfer(TransferAction { deposit: 1 });
+
+        let signed = relayer
+            .build_signed_delegate_action(&sender, receiver_id.clone(), vec![transfer.clone()])
+            .await
+            .unwrap();
+
+        let delegate_action = &signed.delegate_action;
+        assert_eq!(delegate_action.sender_id, sender.account_id);
+        assert_eq!(delegate_action.receiver_id, receiver_id);
+        assert_eq!(delegate_action.public_key, sender.public_key);
+        assert_eq!(delegate_action.nonce, 42);
+        assert_eq!(delegate_action.max_block_height, 1000 + DELEGATE_ACTION_TTL);
+        assert_eq!(delegate_action.get_actions(), vec![transfer]);
+        assert!(signed.verify());
+
+        let nested = Action::Delegate(Box::new(signed));
+        assert!(relayer
+            .build_signed_delegate_action(&sender, receiver_id, vec![nested])
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("cannot be nested"));
+    }
+
+    #[tokio::test]
+    async fn test_typed_operations_wait_until() {
+        let dir = tempfile::tempdir().unwrap();
+        let (upstream_url, upstream) = stub_upstream(|request| match request["method"].as_str() {
+            Some("query") => json!({ "result": {
+                "nonce": 1,
+                "permission": "FullAccess",
+                "block_height": 1000,
+                "block_hash": CryptoHash::default().to_string(),
+            } }),
+            _ => json!({ "result": { "final_execution_status": "INCLUDED" } }),
+        })
+        .await;
+        let cassette = Cassette::start(
+            CassetteMode::Record,
+            dir.path().join("cassette.json"),
+            &upstream_url,
+        )
+        .await
+        .unwrap();
+        let client =
+            FriendlyNearJsonRpcClient::new_with_cassette(&cassette, account("sender.testnet"));
+        let receiver_id: AccountId = "receiver.testnet".parse().unwrap();
+
+        let transfer = client
+            .transfer_with_wait_until(receiver_id.clone(), 1, TxExecutionStatus::Included)
+            .await
+            .unwrap();
+        assert!(transfer.outcome.status.is_none());
+        client.transfer(receiver_id, 1).await.unwrap();
+
+        let wait_until: Vec<Value> = cassette
+            .interactions()
+            .iter()
+            .filter(|interaction| interaction.method == "send_tx")
+            .map(|interaction| interaction.request["params"]["wait_until"].clone())
+            .collect();
+        assert_eq!(wait_until, [json!("INCLUDED"), json!("FINAL")]);
+
+        upstream.abort();
+    }
+}
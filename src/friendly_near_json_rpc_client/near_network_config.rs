@@ -15,3 +15,12 @@ pub const fn get_rpc_url(network: NearNetworkConfig) -> &'static str {
         NearNetworkConfig::Local => "http://localhost:3030",
     }
 }
+
+/// Get the name used to identify the given network configuration.
+pub const fn get_network_name(network: NearNetworkConfig) -> &'static str {
+    match network {
+        NearNetworkConfig::Testnet => "testnet",
+        NearNetworkConfig::Mainnet => "mainnet",
+        NearNetworkConfig::Local => "local",
+    }
+}
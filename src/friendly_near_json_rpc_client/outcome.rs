@@ -0,0 +1,239 @@
+//! Typed outcomes for the transactions sent through the friendly client
+use near_crypto::PublicKey;
+use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{Balance, Gas};
+use near_primitives::views::{FinalExecutionOutcomeViewEnum, FinalExecutionStatus};
+use near_sdk::AccountId;
+use std::error::Error;
+
+/// Summary of a transaction sent to the NEAR blockchain
+#[derive(Debug)]
+pub struct TransactionOutcome {
+    pub transaction_hash: CryptoHash,
+    pub status: Option<FinalExecutionStatus>,
+    pub gas_burnt: Gas,
+    pub response: RpcTransactionResponse,
+}
+
+impl TransactionOutcome {
+    /// Builds the outcome from the raw RPC response, failing if the transaction failed on chain
+    pub fn from_response(
+        transaction_hash: CryptoHash,
+        response: RpcTransactionResponse,
+    ) -> Result<Self, Box<dyn Error>> {
+        let final_outcome = match &response.final_execution_outcome {
+            Some(FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(outcome)) => Some(outcome),
+            Some(FinalExecutionOutcomeViewEnum::FinalExecutionOutcomeWithReceipt(outcome)) => {
+                Some(&outcome.final_outcome)
+            }
+            None => None,
+        };
+
+        let status = final_outcome.map(|outcome| outcome.status.clone());
+        let gas_burnt = final_outcome.map_or(0, |outcome| {
+            outcome.transaction_outcome.outcome.gas_burnt
+                + outcome
+                    .receipts_outcome
+                    .iter()
+                    .map(|receipt| receipt.outcome.gas_burnt)
+                    .sum::<Gas>()
+        });
+
+        if let Some(FinalExecutionStatus::Failure(err)) = &status {
+            return Err(format!("Transaction {} failed: {}", transaction_hash, err).into());
+        }
+
+        Ok(Self {
+            transaction_hash,
+            status,
+            gas_burnt,
+            response,
+        })
+    }
+
+    /// Builds the outcome from a response that contains the final execution outcome
+    pub fn from_final_response(response: RpcTransactionResponse) -> Result<Self, Box<dyn Error>> {
+        let transaction_hash = match &response.final_execution_outcome {
+            Some(FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(outcome)) => {
+                outcome.transaction_outcome.id
+            }
+            Some(FinalExecutionOutcomeViewEnum::FinalExecutionOutcomeWithReceipt(outcome)) => {
+                outcome.final_outcome.transaction_outcome.id
+            }
+            None => return Err("Transaction response has no execution outcome".into()),
+        };
+
+        Self::from_response(transaction_hash, response)
+    }
+
+    /// Returns true if the transaction finished with a success value
+    pub const fn is_success(&self) -> bool {
+        matches!(self.status, Some(FinalExecutionStatus::SuccessValue(_)))
+    }
+}
+
+/// Outcome of creating a sub account
+#[derive(Debug)]
+pub struct CreateAccountOutcome {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+    pub initial_balance: Balance,
+    pub outcome: TransactionOutcome,
+}
+
+/// Outcome of a NEAR transfer
+#[derive(Debug)]
+pub struct TransferOutcome {
+    pub receiver_id: AccountId,
+    pub amount: Balance,
+    pub outcome: TransactionOutcome,
+}
+
+/// Outcome of adding or deleting an access key
+#[derive(Debug)]
+pub struct AccessKeyOutcome {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+    pub outcome: TransactionOutcome,
+}
+
+/// Outcome of a staking transaction
+#[derive(Debug)]
+pub struct StakeOutcome {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+    pub stake: Balance,
+    pub outcome: TransactionOutcome,
+}
+
+/// Outcome of deleting an account
+#[derive(Debug)]
+pub struct DeleteAccountOutcome {
+    pub account_id: AccountId,
+    pub beneficiary_id: AccountId,
+    pub outcome: TransactionOutcome,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{KeyType, Signature};
+    use near_primitives::views::SignedTransactionView;
+    use serde_json::{json, Value};
+
+    fn execution_outcome(id: CryptoHash, gas_burnt: Gas, status: Value) -> Value {
+        json!({
+            "proof": [],
+            "block_hash": CryptoHash::default(),
+            "id": id,
+            "outcome": {
+                "logs": [],
+                "receipt_ids": [],
+                "gas_burnt": gas_burnt,
+                "tokens_burnt": "0",
+                "executor_id": "alice.test.near",
+                "status": status,
+                "metadata": { "version": 1, "gas_profile": null }
+            }
+        })
+    }
+
+    fn response(transaction_hash: CryptoHash, status: Value) -> RpcTransactionResponse {
+        let transaction = SignedTransactionView {
+            signer_id: "alice.test.near".parse().unwrap(),
+            public_key: PublicKey::empty(KeyType::ED25519),
+            nonce: 1,
+            receiver_id: "bob.test.near".parse().unwrap(),
+            actions: vec![],
+            priority_fee: 0,
+            signature: Signature::empty(KeyType::ED25519),
+            hash: transaction_hash,
+        };
+        let receipt_status = match &status {
+            Value::Object(status) if status.contains_key("Failure") => json!(status),
+            _ => json!({ "SuccessValue": "" }),
+        };
+
+        serde_json::from_value(json!({
+            "final_execution_status": "FINAL",
+            "status": status,
+            "transaction": transaction,
+            "transaction_outcome": execution_outcome(
+                transaction_hash,
+                100,
+                json!({ "SuccessReceiptId": CryptoHash::hash_bytes(b"receipt") }),
+            ),
+            "receipts_outcome": [
+                execution_outcome(CryptoHash::hash_bytes(b"receipt"), 200, receipt_status.clone()),
+                execution_outcome(CryptoHash::hash_bytes(b"refund"), 300, receipt_status),
+            ],
+        }))
+        .unwrap()
+    }
+
+    fn failure() -> Value {
+        json!({
+            "Failure": {
+                "ActionError": {
+                    "index": 0,
+                    "kind": { "AccountDoesNotExist": { "account_id": "bob.test.near" } }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_from_response_sums_the_gas_of_a_success() {
+        let hash = CryptoHash::hash_bytes(b"transaction");
+        let outcome =
+            TransactionOutcome::from_response(hash, response(hash, json!({ "SuccessValue": "" })))
+                .unwrap();
+
+        assert_eq!(outcome.transaction_hash, hash);
+        assert_eq!(outcome.gas_burnt, 600);
+        assert!(outcome.is_success());
+    }
+
+    #[test]
+    fn test_from_response_fails_on_a_failure() {
+        let hash = CryptoHash::hash_bytes(b"transaction");
+        let err = TransactionOutcome::from_response(hash, response(hash, failure())).unwrap_err();
+
+        assert!(err.to_string().contains(&hash.to_string()));
+        assert!(err.to_string().contains("bob.test.near"));
+    }
+
+    #[test]
+    fn test_from_response_without_outcome() {
+        let hash = CryptoHash::hash_bytes(b"transaction");
+        let response: RpcTransactionResponse =
+            serde_json::from_value(json!({ "final_execution_status": "INCLUDED" })).unwrap();
+        let outcome = TransactionOutcome::from_response(hash, response).unwrap();
+
+        assert_eq!(outcome.status, None);
+        assert_eq!(outcome.gas_burnt, 0);
+        assert!(!outcome.is_success());
+    }
+
+    #[test]
+    fn test_from_final_response_reads_the_transaction_hash() {
+        let hash = CryptoHash::hash_bytes(b"transaction");
+        let outcome =
+            TransactionOutcome::from_final_response(response(hash, json!({ "SuccessValue": "" })))
+                .unwrap();
+        assert_eq!(outcome.transaction_hash, hash);
+        assert!(outcome.is_success());
+
+        let err = TransactionOutcome::from_final_response(response(hash, failure())).unwrap_err();
+        assert!(err.to_string().contains("failed"));
+    }
+
+    #[test]
+    fn test_from_final_response_without_outcome() {
+        let response: RpcTransactionResponse =
+            serde_json::from_value(json!({ "final_execution_status": "NONE" })).unwrap();
+
+        assert!(TransactionOutcome::from_final_response(response).is_err());
+    }
+}
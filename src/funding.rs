@@ -0,0 +1,63 @@
+#[cfg(feature = "evm")]
+use crate::contexts::Erc20Balance;
+#[cfg(all(feature = "evm", feature = "mpc-utils"))]
+use alloy::primitives::utils::parse_units;
+#[cfg(feature = "evm")]
+use alloy::primitives::{Address as EvmAddress, U256};
+#[cfg(feature = "bitcoin")]
+use bitcoin::{Address as BtcAddress, Amount};
+use near_sdk::AccountId;
+use near_workspaces::types::NearToken;
+
+/// Funds requested for an account on one of the chains
+#[derive(Debug, Clone)]
+pub enum FundingRequest {
+    #[cfg(feature = "evm")]
+    Evm {
+        address: EvmAddress,
+        eth: Option<U256>,
+        erc20: Vec<Erc20Balance>,
+    },
+    #[cfg(feature = "bitcoin")]
+    Bitcoin {
+        address: BtcAddress,
+        utxos: Vec<Amount>, // One confirmed UTXO is created for each amount
+    },
+    Near {
+        account_id: AccountId,
+        amount: NearToken,
+    },
+}
+
+/// Funds given to the derived addresses of the deployer on every enabled chain
+#[cfg(feature = "mpc-utils")]
+#[derive(Debug, Clone)]
+pub struct DerivedFunding {
+    #[cfg(feature = "evm")]
+    pub eth: Option<U256>, // Balance of the derived EVM address
+    #[cfg(feature = "evm")]
+    pub erc20: Vec<Erc20Balance>, // Token balances of the derived EVM address
+    #[cfg(feature = "bitcoin")]
+    pub btc_legacy_utxos: Vec<Amount>, // UTXOs of the derived legacy address
+    #[cfg(feature = "bitcoin")]
+    pub btc_segwit_utxos: Vec<Amount>, // UTXOs of the derived segwit address
+    pub near: Option<NearToken>, // Balance sent to the derived NEAR account, creating it
+}
+
+#[cfg(feature = "mpc-utils")]
+#[cfg_attr(not(feature = "evm"), allow(clippy::derivable_impls))]
+impl Default for DerivedFunding {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "evm")]
+            eth: Some(parse_units("100.0", "ether").unwrap().into()),
+            #[cfg(feature = "evm")]
+            erc20: vec![],
+            #[cfg(feature = "bitcoin")]
+            btc_legacy_utxos: vec![],
+            #[cfg(feature = "bitcoin")]
+            btc_segwit_utxos: vec![],
+            near: None,
+        }
+    }
+}
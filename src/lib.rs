@@ -5,20 +5,142 @@
 //! - Support for EVM Chains, Bitcoin and NEAR
 //! - Pre configured accounts for easy development
 //! - Account creation
+//! - Deterministic accounts and keys on every chain from a `seed` option
 //! - Utilities for account derivation and signature construction
 //! - Utilities to interact with the NEAR contract via a friendly NEAR JSON RPC client
-//! - Automatic compilation and deployment
+//! - Automatic compilation and deployment, including multiple contracts deployed to named accounts
+//! - Contract upgrade regression harness running in the NEAR sandbox
+//! - Record / replay of the NEAR JSON RPC traffic for offline tests
+//! - Coordinated snapshot and revert of all the running chains
+//! - Coordinated time and block control across the chains, for deadlines and time locks
+//! - Warm start templates of the node data, invalidated by a hash of the configuration
+//! - A process wide shared OmniBox handing out isolated leases to the tests of a binary
+//! - A `#[omni_box::test]` attribute building the environment and injecting it into async tests
+//! - Export of the endpoints, accounts and derived addresses as a JSON manifest and a dotenv file
+//! - Discovery and version checks of the node binaries, with a `doctor` report
+//! - A `ChainContext` trait to write chain agnostic helpers over the BTC, EVM and NEAR contexts
+//! - A `ChainModule` trait to plug third-party networks into an OmniBox, next to the built-in chains
+//!
+//! Cargo features
+//! - `evm`: Anvil client, `EVMTestContext` and the EVM module of `OmniBox`
+//! - `bitcoin`: bitcoind regtest client, `BTCTestContext` and the Bitcoin module of `OmniBox`
+//! - `near-sandbox`: NEAR sandbox client, `NearTestContext`, the friendly NEAR JSON RPC client, contract deployment and `OmniBox` itself
+//! - `mpc-utils`: address derivation and signature helpers of `utils`, and the derived accounts of `OmniBox`
+//! - `logs`: log subscriber of the `#[omni_box::test]` attribute and the `omnibox` CLI
+//!
+//! All of them are enabled by default. `OmniBox` deploys to NEAR so it needs `near-sandbox`, the
+//! other chains are optional.
+
+#[cfg(feature = "near-sandbox")]
 mod account_config;
+#[cfg(feature = "near-sandbox")]
 mod chain_config;
+#[cfg(feature = "near-sandbox")]
+mod chain_module;
+#[cfg(feature = "near-sandbox")]
+mod chain_modules;
+#[cfg(feature = "near-sandbox")]
+mod chain_time;
 mod clients;
 mod contexts;
+#[cfg(feature = "near-sandbox")]
+mod contract_config;
+#[cfg(feature = "near-sandbox")]
+mod deploy_cache;
+#[cfg(feature = "near-sandbox")]
+mod deploy_error;
+#[cfg(all(feature = "near-sandbox", feature = "mpc-utils"))]
+mod derived_accounts;
+#[cfg(feature = "near-sandbox")]
 pub mod friendly_near_json_rpc_client;
+#[cfg(feature = "near-sandbox")]
+mod funding;
+#[cfg(feature = "near-sandbox")]
+mod manifest;
 mod network;
+#[cfg(feature = "near-sandbox")]
 mod omni_box;
+#[cfg(feature = "near-sandbox")]
 mod omni_box_options;
+pub mod prerequisites;
+#[cfg(any(feature = "bitcoin", feature = "evm", feature = "near-sandbox"))]
+mod seed;
+#[cfg(feature = "near-sandbox")]
+mod shared;
+#[cfg(feature = "near-sandbox")]
+mod snapshot;
+#[cfg(feature = "near-sandbox")]
+mod startup_timings;
+#[cfg(feature = "near-sandbox")]
+#[doc(hidden)]
+pub mod test_support;
+#[cfg(feature = "near-sandbox")]
+pub mod upgrade_harness;
+#[cfg(feature = "mpc-utils")]
 pub mod utils;
+#[cfg(feature = "near-sandbox")]
+mod warm_start;
 
+#[cfg(feature = "near-sandbox")]
+pub use chain_config::ChainOverrides;
+#[cfg(feature = "near-sandbox")]
+pub use chain_module::{ChainModule, ChainModuleFactory, ModuleFuture, ModuleSnapshot};
+#[cfg(all(feature = "near-sandbox", feature = "bitcoin"))]
+pub use chain_modules::BitcoinModule;
+#[cfg(all(feature = "near-sandbox", feature = "evm"))]
+pub use chain_modules::EvmModule;
+#[cfg(feature = "near-sandbox")]
+pub use chain_modules::NearModule;
+#[cfg(feature = "near-sandbox")]
+pub use chain_time::{ChainTime, ChainTimes};
+#[cfg(all(feature = "near-sandbox", feature = "mpc-utils", feature = "bitcoin"))]
+pub use derived_accounts::BtcDerivedAccount;
+#[cfg(all(feature = "near-sandbox", feature = "mpc-utils"))]
+pub use derived_accounts::DerivedAccounts;
+#[cfg(all(feature = "near-sandbox", feature = "mpc-utils", feature = "evm"))]
+pub use derived_accounts::EvmDerivedAccount;
+#[cfg(all(feature = "near-sandbox", feature = "mpc-utils"))]
+pub use derived_accounts::NearDerivedAccount;
+#[cfg(all(feature = "near-sandbox", feature = "mpc-utils"))]
+pub use funding::DerivedFunding;
+#[cfg(feature = "near-sandbox")]
+pub use funding::FundingRequest;
+#[cfg(feature = "near-sandbox")]
+pub use manifest::{
+    BitcoinManifest, DerivedAddressesManifest, EvmManifest, Manifest, ManifestAccount,
+    NearManifest, MANIFEST_VERSION,
+};
+#[cfg(feature = "near-sandbox")]
 pub use omni_box::OmniBox;
+#[cfg(feature = "near-sandbox")]
+pub use omni_box_macros::test;
+#[cfg(feature = "near-sandbox")]
+pub use omni_box_options::OmniBoxOptions;
+#[cfg(feature = "near-sandbox")]
+pub use shared::{LeaseIsolation, OmniBoxLease};
+#[cfg(feature = "near-sandbox")]
+pub use snapshot::{OmniBoxSnapshot, SnapshotId};
+#[cfg(feature = "near-sandbox")]
+pub use startup_timings::StartupTimings;
+#[cfg(feature = "near-sandbox")]
+pub use warm_start::DEFAULT_TEMPLATES_PATH;
+
+pub use contexts::ChainContext;
+#[cfg(feature = "bitcoin")]
+pub use contexts::{BTCTestContext, BtcSnapshot, UserInfo};
+#[cfg(feature = "evm")]
+pub use contexts::{EVMTestContext, Erc20Balance};
+#[cfg(feature = "near-sandbox")]
+pub use contexts::{NearAccountSnapshot, NearSnapshot, NearTestContext};
+#[cfg(feature = "near-sandbox")]
+pub use contract_config::{
+    ContractCall, ContractConfig, ContractSource, ContractTarget, DeployedContract,
+};
+#[cfg(feature = "near-sandbox")]
+pub use deploy_error::DeployError;
+pub use network::Network;
 
-use account_config::near_account::NearAccount;
+#[cfg(feature = "near-sandbox")]
+pub use account_config::near_account::NearAccount;
+#[cfg(feature = "near-sandbox")]
 use account_config::Account;
@@ -0,0 +1,388 @@
+//! Machine readable description of a running OmniBox, for tools outside of Rust
+use crate::chain_modules::BUILTIN_MODULES;
+#[cfg(feature = "evm")]
+use crate::contexts::EVMTestContext;
+use crate::contexts::NearTestContext;
+#[cfg(feature = "bitcoin")]
+use crate::contexts::{BTCTestContext, UserInfo};
+use crate::omni_box::OmniBox;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the manifest format, bumped on breaking changes
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Endpoints, accounts and derived addresses of a running OmniBox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub bitcoin: Option<BitcoinManifest>, // Sections of the enabled built-in chain modules
+    pub evm: Option<EvmManifest>,
+    pub near: Option<NearManifest>,
+    pub contracts: BTreeMap<String, String>, // Account of each deployed contract, by name
+    pub derived_addresses: DerivedAddressesManifest,
+    #[serde(default)]
+    pub modules: BTreeMap<String, Value>, // Sections of the registered third-party chain modules
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BitcoinManifest {
+    pub rpc_url: String,
+    pub cookie_file: PathBuf,
+    pub accounts: BTreeMap<String, ManifestAccount>, // Private keys are WIF encoded
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvmManifest {
+    pub rpc_url: String,
+    pub chain_id: u64,
+    pub accounts: BTreeMap<String, ManifestAccount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NearManifest {
+    pub sandbox_rpc_url: String,
+    pub rpc_url: String, // Network the contracts are deployed to
+    pub deployer: String,
+    pub accounts: BTreeMap<String, ManifestAccount>,
+}
+
+/// Funded account and the key controlling it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestAccount {
+    pub address: String,
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DerivedAddressesManifest {
+    pub btc_legacy: Option<String>,
+    pub btc_segwit: Option<String>,
+    pub evm: Option<String>,
+    pub near: Option<String>,
+}
+
+#[cfg(feature = "bitcoin")]
+impl BitcoinManifest {
+    pub fn new(btc: &BTCTestContext) -> Self {
+        Self {
+            rpc_url: btc.bitcoind_instance.rpc_url(),
+            cookie_file: btc.bitcoind_instance.params.cookie_file.clone(),
+            accounts: BTreeMap::from([
+                (
+                    "alice_legacy".to_string(),
+                    ManifestAccount::bitcoin(&btc.alice_legacy),
+                ),
+                (
+                    "alice_segwit".to_string(),
+                    ManifestAccount::bitcoin(&btc.alice_segwit),
+                ),
+                (
+                    "bob_legacy".to_string(),
+                    ManifestAccount::bitcoin(&btc.bob_legacy),
+                ),
+                (
+                    "bob_segwit".to_string(),
+                    ManifestAccount::bitcoin(&btc.bob_segwit),
+                ),
+            ]),
+        }
+    }
+}
+
+#[cfg(feature = "evm")]
+impl EvmManifest {
+    pub fn new(evm: &EVMTestContext) -> Self {
+        let evm_account = |index: usize| ManifestAccount {
+            address: evm.anvil.addresses()[index].to_string(),
+            private_key: format!("0x{}", hex::encode(evm.anvil.keys()[index].to_bytes())),
+        };
+
+        Self {
+            rpc_url: evm.anvil.endpoint(),
+            chain_id: evm.anvil.chain_id(),
+            accounts: BTreeMap::from([
+                ("alice".to_string(), evm_account(0)),
+                ("bob".to_string(), evm_account(1)),
+            ]),
+        }
+    }
+}
+
+impl NearManifest {
+    /// Describes the sandbox, `rpc_url` and `deployer` are those of the contract deployments
+    pub fn new(near: &NearTestContext, rpc_url: String, deployer: String) -> Self {
+        Self {
+            sandbox_rpc_url: near.client.rpc_addr(),
+            rpc_url,
+            deployer,
+            accounts: BTreeMap::from([
+                ("alice".to_string(), ManifestAccount::near(&near.alice)),
+                ("bob".to_string(), ManifestAccount::near(&near.bob)),
+            ]),
+        }
+    }
+}
+
+impl ManifestAccount {
+    /// Regtest account, with a WIF encoded private key
+    #[cfg(feature = "bitcoin")]
+    pub(crate) fn bitcoin(account: &UserInfo) -> Self {
+        Self {
+            address: account.address.to_string(),
+            private_key: bitcoin::PrivateKey::new(account.private_key, bitcoin::Network::Regtest)
+                .to_wif(),
+        }
+    }
+
+    pub(crate) fn near(account: &near_workspaces::Account) -> Self {
+        Self {
+            address: account.id().to_string(),
+            private_key: account.secret_key().to_string(),
+        }
+    }
+}
+
+impl DerivedAddressesManifest {
+    /// Default derived addresses of the deployer, none without the `mpc-utils` feature
+    #[cfg_attr(not(feature = "mpc-utils"), allow(unused_variables))]
+    fn new(omni_box: &OmniBox) -> Self {
+        Self {
+            #[cfg(all(feature = "mpc-utils", feature = "bitcoin"))]
+            btc_legacy: omni_box
+                .derived_accounts
+                .btc_legacy()
+                .map(|account| account.address.to_string()),
+            #[cfg(all(feature = "mpc-utils", feature = "bitcoin"))]
+            btc_segwit: omni_box
+                .derived_accounts
+                .btc_segwit()
+                .map(|account| account.address.to_string()),
+            #[cfg(not(all(feature = "mpc-utils", feature = "bitcoin")))]
+            btc_legacy: None,
+            #[cfg(not(all(feature = "mpc-utils", feature = "bitcoin")))]
+            btc_segwit: None,
+            #[cfg(all(feature = "mpc-utils", feature = "evm"))]
+            evm: omni_box
+                .derived_accounts
+                .evm()
+                .map(|account| account.address.to_string()),
+            #[cfg(not(all(feature = "mpc-utils", feature = "evm")))]
+            evm: None,
+            #[cfg(feature = "mpc-utils")]
+            near: omni_box
+                .derived_accounts
+                .near()
+                .map(|account| account.account_id.to_string()),
+            #[cfg(not(feature = "mpc-utils"))]
+            near: None,
+        }
+    }
+}
+
+impl Manifest {
+    /// Describes the environment of an OmniBox
+    pub fn new(omni_box: &OmniBox) -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            #[cfg(feature = "bitcoin")]
+            bitcoin: omni_box.btc_context.as_deref().map(BitcoinManifest::new),
+            #[cfg(not(feature = "bitcoin"))]
+            bitcoin: None,
+            #[cfg(feature = "evm")]
+            evm: omni_box.evm_context.as_deref().map(EvmManifest::new),
+            #[cfg(not(feature = "evm"))]
+            evm: None,
+            near: omni_box.near_context.as_deref().map(|near| {
+                NearManifest::new(
+                    near,
+                    omni_box.friendly_near_json_rpc_client.rpc_url().to_string(),
+                    omni_box.deployer_account.account_id.to_string(),
+                )
+            }),
+            contracts: omni_box
+                .contracts
+                .values()
+                .map(|contract| (contract.name.to_string(), contract.account_id().to_string()))
+                .collect(),
+            derived_addresses: DerivedAddressesManifest::new(omni_box),
+            modules: omni_box
+                .chain_modules
+                .iter()
+                .filter(|(name, _)| !BUILTIN_MODULES.contains(&name.as_str()))
+                .map(|(name, module)| (name.clone(), module.manifest()))
+                .collect(),
+        }
+    }
+
+    /// Renders the manifest as `OMNIBOX_*` environment variables
+    ///
+    /// The sections of third-party chain modules are only part of the JSON manifest.
+    pub fn to_dotenv(&self) -> String {
+        let mut variables = vec![("MANIFEST_VERSION".to_string(), self.version.to_string())];
+        if let Some(bitcoin) = &self.bitcoin {
+            variables.push(("BTC_RPC_URL".to_string(), bitcoin.rpc_url.clone()));
+            variables.push((
+                "BTC_COOKIE_FILE".to_string(),
+                bitcoin.cookie_file.display().to_string(),
+            ));
+        }
+        if let Some(evm) = &self.evm {
+            variables.push(("EVM_RPC_URL".to_string(), evm.rpc_url.clone()));
+            variables.push(("EVM_CHAIN_ID".to_string(), evm.chain_id.to_string()));
+        }
+        if let Some(near) = &self.near {
+            variables.push((
+                "NEAR_SANDBOX_RPC_URL".to_string(),
+                near.sandbox_rpc_url.clone(),
+            ));
+            variables.push(("NEAR_RPC_URL".to_string(), near.rpc_url.clone()));
+            variables.push(("NEAR_DEPLOYER".to_string(), near.deployer.clone()));
+        }
+
+        for (chain, accounts) in [
+            (
+                "BTC",
+                self.bitcoin.as_ref().map(|bitcoin| &bitcoin.accounts),
+            ),
+            ("EVM", self.evm.as_ref().map(|evm| &evm.accounts)),
+            ("NEAR", self.near.as_ref().map(|near| &near.accounts)),
+        ] {
+            for (name, account) in accounts.into_iter().flatten() {
+                let prefix = format!("{}_{}", chain, name.to_uppercase());
+                variables.push((format!("{}_ADDRESS", prefix), account.address.clone()));
+                variables.push((
+                    format!("{}_PRIVATE_KEY", prefix),
+                    account.private_key.clone(),
+                ));
+            }
+        }
+        for (name, account_id) in &self.contracts {
+            variables.push((
+                format!("CONTRACT_{}", name.to_uppercase().replace(['-', '.'], "_")),
+                account_id.clone(),
+            ));
+        }
+        for (name, address) in [
+            ("BTC_LEGACY", &self.derived_addresses.btc_legacy),
+            ("BTC_SEGWIT", &self.derived_addresses.btc_segwit),
+            ("EVM", &self.derived_addresses.evm),
+            ("NEAR", &self.derived_addresses.near),
+        ] {
+            if let Some(address) = address {
+                variables.push((format!("DERIVED_{}_ADDRESS", name), address.clone()));
+            }
+        }
+
+        let mut dotenv = String::new();
+        for (name, value) in variables {
+            let _ = writeln!(dotenv, "OMNIBOX_{}={}", name, dotenv_quote(&value));
+        }
+        dotenv
+    }
+}
+
+/// Double quotes a dotenv value, escaped so dotenv parsers and shells read it back unchanged
+fn dotenv_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' | '"' | '$' | '`' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl OmniBox {
+    /// Writes the manifest of the environment as JSON to `path` and as a dotenv file next to it
+    ///
+    /// The dotenv file has the same name with the `env` extension.
+    pub fn export_manifest(&self, path: impl AsRef<Path>) -> Result<Manifest, Box<dyn Error>> {
+        let path = path.as_ref();
+        let manifest = Manifest::new(self);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&manifest)?)?;
+        fs::write(path.with_extension("env"), manifest.to_dotenv())?;
+
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_dotenv() {
+        let account = |address: &str| ManifestAccount {
+            address: address.to_string(),
+            private_key: format!("{}-key", address),
+        };
+        let manifest = Manifest {
+            version: MANIFEST_VERSION,
+            bitcoin: Some(BitcoinManifest {
+                rpc_url: "http://127.0.0.1:18443".to_string(),
+                cookie_file: PathBuf::from("/tmp/regtest/.cookie"),
+                accounts: BTreeMap::from([("alice_segwit".to_string(), account("bcrt1q"))]),
+            }),
+            evm: Some(EvmManifest {
+                rpc_url: "http://localhost:8545".to_string(),
+                chain_id: 31337,
+                accounts: BTreeMap::from([("alice".to_string(), account("0xabc"))]),
+            }),
+            near: None,
+            contracts: BTreeMap::from([(
+                "token-v2".to_string(),
+                "token.deployer.testnet".to_string(),
+            )]),
+            derived_addresses: DerivedAddressesManifest {
+                btc_legacy: None,
+                btc_segwit: None,
+                evm: Some("0xdef".to_string()),
+                near: None,
+            },
+            modules: BTreeMap::from([(
+                "solana".to_string(),
+                serde_json::json!({ "rpc_url": "http://127.0.0.1:8899" }),
+            )]),
+        };
+
+        let dotenv = manifest.to_dotenv();
+
+        assert!(dotenv.contains("OMNIBOX_MANIFEST_VERSION=\"1\"\n"));
+        assert!(dotenv.contains("OMNIBOX_BTC_COOKIE_FILE=\"/tmp/regtest/.cookie\"\n"));
+        assert!(dotenv.contains("OMNIBOX_EVM_CHAIN_ID=\"31337\"\n"));
+        assert!(dotenv.contains("OMNIBOX_BTC_ALICE_SEGWIT_ADDRESS=\"bcrt1q\"\n"));
+        assert!(dotenv.contains("OMNIBOX_EVM_ALICE_PRIVATE_KEY=\"0xabc-key\"\n"));
+        assert!(dotenv.contains("OMNIBOX_CONTRACT_TOKEN_V2=\"token.deployer.testnet\"\n"));
+        assert!(dotenv.contains("OMNIBOX_DERIVED_EVM_ADDRESS=\"0xdef\"\n"));
+        assert!(!dotenv.contains("OMNIBOX_DERIVED_BTC_LEGACY_ADDRESS"));
+        assert!(!dotenv.contains("8899"));
+        assert!(!dotenv.contains("OMNIBOX_NEAR_"));
+    }
+
+    #[test]
+    fn test_dotenv_quote_escapes_the_value() {
+        assert_eq!(dotenv_quote("plain"), r#""plain""#);
+        assert_eq!(
+            dotenv_quote("/tmp/my dir/.cookie"),
+            r#""/tmp/my dir/.cookie""#
+        );
+        assert_eq!(dotenv_quote("a\\b\"c$d`e\nf"), r#""a\\b\"c\$d\`e\nf""#);
+    }
+}
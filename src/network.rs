@@ -1,7 +1,11 @@
+/// Chain run by an OmniBox, each variant is enabled by the cargo feature of its chain
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum Network {
+    #[cfg(feature = "evm")]
     EVM,
+    #[cfg(feature = "near-sandbox")]
     Near,
+    #[cfg(feature = "bitcoin")]
     Bitcoin,
 }
@@ -1,31 +1,71 @@
 use crate::{
     account_config::get_user_account_info_from_file,
     chain_config::ChainConfig,
-    contexts::{BTCTestContext, EVMTestContext, NearTestContext},
-    friendly_near_json_rpc_client::FriendlyNearJsonRpcClient,
+    chain_module::{ChainModule, ChainModuleFactory},
+    chain_modules::{NearModule, BUILTIN_MODULES},
+    chain_time::{ChainTime, ChainTimes},
+    contexts::NearTestContext,
+    contract_config::{
+        deploy_to_sandbox, ContractCall, ContractConfig, ContractSource, ContractTarget,
+        DeployHook, DeployedContract, HOOK_GAS,
+    },
+    deploy_cache::{DeployCache, DeployCacheEntry, DEFAULT_CACHE_PATH},
+    deploy_error::DeployError,
+    friendly_near_json_rpc_client::{
+        cassette::Cassette,
+        near_network_config::{get_network_name, get_rpc_url, NearNetworkConfig},
+        outcome::TransactionOutcome,
+        FriendlyNearJsonRpcClient,
+    },
+    funding::FundingRequest,
     network::Network,
     omni_box_options::OmniBoxOptions,
-    utils::address,
+    snapshot::{OmniBoxSnapshot, SnapshotId, SnapshotRegistry},
+    startup_timings::{timed, StartupTimings},
+    warm_start::WarmStart,
     NearAccount,
 };
-use alloy::{
-    hex::FromHex,
-    primitives::{utils::parse_units, Address, U256},
-    providers::ext::AnvilApi,
+#[cfg(feature = "bitcoin")]
+use crate::{chain_modules::BitcoinModule, contexts::BTCTestContext};
+#[cfg(feature = "evm")]
+use crate::{chain_modules::EvmModule, contexts::EVMTestContext};
+#[cfg(feature = "mpc-utils")]
+use crate::{derived_accounts::DerivedAccounts, funding::DerivedFunding};
+use futures::future::{join_all, try_join_all};
+use near_crypto::SecretKey;
+use near_primitives::hash::CryptoHash;
+use near_primitives::views::TxExecutionStatus;
+use near_sdk::AccountId;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
-use sha3::{Digest, Sha3_256};
-use std::fs::{self, OpenOptions};
-use std::io::prelude::*;
-use std::path::Path;
-use std::{collections::HashMap, error::Error};
+use tracing::{debug, info, instrument, warn};
+
+/// Time given to a registered chain module to answer requests once started
+const MODULE_READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+const MODULE_READY_POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 pub struct OmniBox {
     chains: HashMap<Network, ChainConfig>,
-    pub btc_context: BTCTestContext,
-    pub near_context: NearTestContext,
-    pub evm_context: EVMTestContext,
+    #[cfg(feature = "bitcoin")]
+    pub(crate) btc_context: Option<Arc<BTCTestContext>>, // Set when the bitcoin module is enabled
+    pub(crate) near_context: Option<Arc<NearTestContext>>, // Set when the near module is enabled
+    #[cfg(feature = "evm")]
+    pub(crate) evm_context: Option<Arc<EVMTestContext>>, // Set when the evm module is enabled
     pub deployer_account: NearAccount,
     pub friendly_near_json_rpc_client: FriendlyNearJsonRpcClient,
+    pub near_cassette: Option<Cassette>,
+    pub contracts: HashMap<&'static str, DeployedContract>,
+    #[cfg(feature = "mpc-utils")]
+    pub derived_accounts: DerivedAccounts,
+    pub(crate) chain_modules: BTreeMap<String, Box<dyn ChainModule>>, // Built-in and third-party chains, by name
+    snapshots: Mutex<SnapshotRegistry<OmniBoxSnapshot>>,
+    pub startup_timings: StartupTimings,
+    warm_start: Option<WarmStart>, // Dropped last, it holds the bitcoind data directory
 }
 
 impl OmniBox {
@@ -33,172 +73,1179 @@ impl OmniBox {
         Self::new_with_conf(None).await
     }
 
+    #[instrument(skip_all)]
     pub async fn new_with_conf(options: Option<OmniBoxOptions>) -> Self {
+        let startup_started_at = Instant::now();
         let mut chains = HashMap::new();
         let options = options.unwrap_or_default();
 
-        println!("Starting OmniBox with options: {:#?}", options);
+        debug!(?options, "Starting OmniBox");
 
-        for module in options.modules {
+        for module in &options.modules {
             // Create a default configuration for this module / chain / network
             let mut config = ChainConfig::default(module.clone());
 
             // Apply overrides if they exist
-            if let Some(overrides) = options.overrides.get(&module) {
+            if let Some(overrides) = options.overrides.get(module) {
                 if let Some(url) = &overrides.node_url {
                     config.node_url = url.clone();
                 }
             }
-            chains.insert(module, config);
+            chains.insert(module.clone(), config);
         }
 
         // Get the deployer account
         let deployer_account = get_user_account_info_from_file(None).unwrap();
-        let friendly_client =
-            FriendlyNearJsonRpcClient::new(options.default_near_network, deployer_account.clone());
-        println!("Using deployer account: {:#?}", deployer_account.account_id);
 
-        // Create the OmniBox instance, each context will be initialized with the default configuration
-        let omnibox = Self {
-            chains,
-            btc_context: BTCTestContext::default(),
-            near_context: NearTestContext::new().await,
-            evm_context: EVMTestContext::default(),
-            deployer_account: deployer_account.clone(),
-            friendly_near_json_rpc_client: friendly_client,
+        // Optionally put the friendly client behind a record / replay cassette
+        let near_cassette = match options.near_cassette {
+            Some((mode, path)) => Some(
+                Cassette::start(mode, path, get_rpc_url(options.default_near_network))
+                    .await
+                    .unwrap(),
+            ),
+            None => None,
+        };
+        let friendly_client = match &near_cassette {
+            Some(cassette) => {
+                FriendlyNearJsonRpcClient::new_with_cassette(cassette, deployer_account.clone())
+            }
+            None => FriendlyNearJsonRpcClient::new(
+                options.default_near_network,
+                deployer_account.clone(),
+            ),
         };
+        info!(deployer = %deployer_account.account_id, "Using deployer account");
 
-        // Calculate derived addresses for Bitcoin legacy
-        let legacy_derived_address = address::get_derived_address_for_btc_legacy(
-            &deployer_account.account_id,
+        // Calculate the derived addresses of the deployer for the configured chains
+        #[cfg(feature = "mpc-utils")]
+        let derived_accounts = DerivedAccounts::new(
+            deployer_account.account_id.clone(),
+            &options.modules,
             options.btc_path,
-        );
+            options.evm_path,
+            options.near_path,
+        )
+        .unwrap_or_else(|err| panic!("Failed to derive the deployer accounts: {}", err));
 
-        println!(
-            "Legacy BTC Derived Address: {:?}",
-            legacy_derived_address.address
-        );
-
-        // Calculate derived addresses for Bitcoin Segwit
-        let segwit_derived_address =
-            address::get_derived_address_for_segwit(&deployer_account.account_id, options.btc_path);
+        #[cfg(all(feature = "mpc-utils", feature = "bitcoin"))]
+        if let Some(legacy) = derived_accounts.btc_legacy() {
+            info!(path = %legacy.path, address = %legacy.address, "Legacy BTC derived address");
+        }
+        #[cfg(all(feature = "mpc-utils", feature = "bitcoin"))]
+        if let Some(segwit) = derived_accounts.btc_segwit() {
+            info!(path = %segwit.path, address = %segwit.address, "Segwit BTC derived address");
+        }
+        #[cfg(all(feature = "mpc-utils", feature = "evm"))]
+        if let Some(evm) = derived_accounts.evm() {
+            info!(path = %evm.path, address = %evm.address, "EVM derived address");
+        }
+        #[cfg(feature = "mpc-utils")]
+        if let Some(near) = derived_accounts.near() {
+            info!(path = %near.path, account_id = %near.account_id, "NEAR derived account");
+        }
 
-        println!(
-            "Segwit BTC Derived Address: {:?}",
-            segwit_derived_address.address
-        );
+        // Start the nodes from the warm start templates of this configuration when enabled
+        let warm_start = options.warm_start.map(|root| {
+            WarmStart::new(root, &options, &deployer_account.account_id)
+                .unwrap_or_else(|err| panic!("Failed to prepare the warm start: {}", err))
+        });
 
-        // Calculate default derived addresses EVM
-        let evm_derived_address =
-            address::get_derived_address_for_evm(&deployer_account.account_id, options.evm_path);
+        // The enabled built-in chains are modules spawning their node
+        #[cfg(feature = "bitcoin")]
+        let mut bitcoin_module = chains.contains_key(&Network::Bitcoin).then(|| {
+            let module = BitcoinModule::new(options.binaries.clone(), options.seed);
+            match &warm_start {
+                Some(warm_start) => module.with_template(warm_start.bitcoin_template()),
+                None => module,
+            }
+        });
+        #[cfg(feature = "evm")]
+        let mut evm_module = chains.contains_key(&Network::EVM).then(|| {
+            let module = EvmModule::new(options.binaries.clone(), options.seed);
+            match &warm_start {
+                Some(warm_start) => module.with_state_template(warm_start.evm_template()),
+                None => module,
+            }
+        });
+        let mut near_module = chains.contains_key(&Network::Near).then(|| {
+            NearModule::new(
+                options.binaries.clone(),
+                options.seed,
+                friendly_client.rpc_url().to_string(),
+                deployer_account.account_id.to_string(),
+            )
+        });
+        let mut third_party_modules: Vec<Box<dyn ChainModule>> = options
+            .chain_modules
+            .iter()
+            .map(ChainModuleFactory::build)
+            .collect();
+        for module in &third_party_modules {
+            if BUILTIN_MODULES.contains(&module.name()) {
+                panic!("The chain module name {} is reserved", module.name());
+            }
+        }
 
-        println!("EVM Derived Address: {:?}", evm_derived_address.address);
+        let mut modules: Vec<&mut dyn ChainModule> = Vec::new();
+        #[cfg(feature = "bitcoin")]
+        if let Some(module) = &mut bitcoin_module {
+            modules.push(module);
+        }
+        #[cfg(feature = "evm")]
+        if let Some(module) = &mut evm_module {
+            modules.push(module);
+        }
+        if let Some(module) = &mut near_module {
+            modules.push(module);
+        }
+        modules.extend(third_party_modules.iter_mut().map(Box::as_mut));
 
-        // Give initial funds to the deployer account in EVM
-        let mocked_balance: U256 = match parse_units("100.0", "ether") {
-            Ok(units) => units.into(),
-            Err(e) => {
-                eprintln!("Failed to parse units: {}", e);
-                return omnibox;
+        let mut names = HashSet::new();
+        for module in &modules {
+            if !names.insert(module.name().to_string()) {
+                panic!("Two chain modules are named {}", module.name());
             }
-        };
+        }
 
-        omnibox
-            .evm_context
-            .provider
-            .anvil_set_balance(
-                Address::from_hex(evm_derived_address.address).unwrap(),
-                mocked_balance,
+        // Start the modules and compile the contract concurrently, the compilation runs on its own task
+        let contract_source = options.contract.clone();
+        let ((started_modules, (contract_wasm, compile_time)), concurrent_time) = timed(async {
+            tokio::join!(
+                join_all(modules.into_iter().map(|module| async move {
+                    let name = module.name().to_string();
+                    let (started, time) = timed(start_module(module)).await;
+                    (name, started.map_err(|err| err.to_string()), time)
+                })),
+                timed(tokio::spawn(async move { contract_source.load().await })),
             )
-            .await
-            .unwrap();
+        })
+        .await;
 
-        // Auto compile and deploy
+        let mut module_timings = BTreeMap::new();
+        for (name, started, time) in started_modules {
+            started.unwrap_or_else(|err| panic!("Failed to start the {} module: {}", name, err));
+            module_timings.insert(name, time);
+        }
+
+        // Create the OmniBox instance, each context is initialized with the default configuration
+        let mut omnibox = Self {
+            chains,
+            #[cfg(feature = "bitcoin")]
+            btc_context: bitcoin_module
+                .as_ref()
+                .and_then(|module| module.context().cloned()),
+            near_context: near_module
+                .as_ref()
+                .and_then(|module| module.context().cloned()),
+            #[cfg(feature = "evm")]
+            evm_context: evm_module
+                .as_ref()
+                .and_then(|module| module.context().cloned()),
+            deployer_account: deployer_account.clone(),
+            friendly_near_json_rpc_client: friendly_client,
+            near_cassette,
+            contracts: HashMap::new(),
+            #[cfg(feature = "mpc-utils")]
+            derived_accounts,
+            chain_modules: BTreeMap::new(),
+            snapshots: Mutex::new(SnapshotRegistry::new()),
+            startup_timings: StartupTimings::default(),
+            warm_start,
+        };
+        let builtin_modules = [
+            #[cfg(feature = "bitcoin")]
+            bitcoin_module.map(|module| Box::new(module) as Box<dyn ChainModule>),
+            #[cfg(feature = "evm")]
+            evm_module.map(|module| Box::new(module) as Box<dyn ChainModule>),
+            near_module.map(|module| Box::new(module) as Box<dyn ChainModule>),
+        ];
+        for module in builtin_modules
+            .into_iter()
+            .flatten()
+            .chain(third_party_modules)
+        {
+            omnibox
+                .chain_modules
+                .insert(module.name().to_string(), module);
+        }
+
+        // Give initial funds to the derived addresses and the configured accounts
+        let started_at = Instant::now();
+        #[cfg(feature = "mpc-utils")]
         omnibox
-            .compile_and_deploy_contract(options.path)
+            .fund_derived_accounts(&options.derived_funding)
             .await
             .unwrap();
+        for request in &options.funding {
+            omnibox.fund(request).await.unwrap();
+        }
+        let funding_time = started_at.elapsed();
+
+        // Deploy the compiled contract
+        let (deploy_result, deploy_time) = timed(Self::deploy_contract_wasm(
+            &omnibox.friendly_near_json_rpc_client,
+            &options.contract,
+            contract_wasm
+                .expect("Contract compilation panicked")
+                .unwrap(),
+            options.default_near_network,
+            options.force_redeploy,
+            options.init.as_ref(),
+            options.migrate.as_ref(),
+        ))
+        .await;
+        deploy_result.unwrap();
+
+        // Deploy the contracts of the workspace to their own accounts
+        let (contracts, workspace_contracts_time) = timed(omnibox.deploy_workspace_contracts(
+            options.contracts,
+            options.default_near_network,
+            options.force_redeploy,
+        ))
+        .await;
+        omnibox.contracts = contracts.unwrap();
+
+        // Save the templates missing for this configuration
+        if let Some(warm_start) = &mut omnibox.warm_start {
+            #[cfg(feature = "evm")]
+            if let Some(evm) = &omnibox.evm_context {
+                warm_start
+                    .save_evm(evm)
+                    .await
+                    .unwrap_or_else(|err| warn!(%err, "Failed to save the Anvil template"));
+            }
+            if let Some(near) = &omnibox.near_context {
+                warm_start
+                    .save_near(&near.client, &omnibox.contracts)
+                    .await
+                    .unwrap_or_else(
+                        |err| warn!(%err, "Failed to save the sandbox contracts template"),
+                    );
+            }
+        }
+
+        omnibox.startup_timings = StartupTimings {
+            modules: module_timings,
+            compile: compile_time,
+            concurrent_phases: concurrent_time,
+            funding: funding_time,
+            deploy: deploy_time,
+            workspace_contracts: workspace_contracts_time,
+            total: startup_started_at.elapsed(),
+        };
+        let timings = &omnibox.startup_timings;
+        info!(
+            modules = ?timings.modules,
+            compile = ?timings.compile,
+            concurrent_phases = ?timings.concurrent_phases,
+            funding = ?timings.funding,
+            deploy = ?timings.deploy,
+            workspace_contracts = ?timings.workspace_contracts,
+            total = ?timings.total,
+            "OmniBox started"
+        );
 
         omnibox
     }
 
+    /// Context of the Bitcoin regtest node, if the `bitcoin` module is enabled
+    #[cfg(feature = "bitcoin")]
+    pub const fn btc_context(&self) -> Option<&Arc<BTCTestContext>> {
+        self.btc_context.as_ref()
+    }
+
+    /// Context of the Anvil node, if the `evm` module is enabled
+    #[cfg(feature = "evm")]
+    pub const fn evm_context(&self) -> Option<&Arc<EVMTestContext>> {
+        self.evm_context.as_ref()
+    }
+
+    /// Context of the NEAR sandbox, if the `near` module is enabled
+    pub const fn near_context(&self) -> Option<&Arc<NearTestContext>> {
+        self.near_context.as_ref()
+    }
+
     pub fn get_chain_config(&self, network: &Network) -> Option<&ChainConfig> {
         self.chains.get(network)
     }
 
+    /// Networks enabled for this OmniBox
+    pub fn modules(&self) -> Vec<Network> {
+        self.chains.keys().cloned().collect()
+    }
+
+    /// Starts a chain module and registers it under its name
+    ///
+    /// The module then takes part in the snapshots, reverts, time control and manifest of this
+    /// OmniBox. Modules known before startup are better set in `OmniBoxOptions::chain_modules`.
+    #[instrument(skip_all, fields(name = module.name()))]
+    pub async fn register_module(
+        &mut self,
+        mut module: Box<dyn ChainModule>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let name = module.name().to_string();
+        if self.chain_modules.contains_key(&name) {
+            return Err(format!("A chain module named {} is already registered", name).into());
+        }
+        if BUILTIN_MODULES.contains(&name.as_str()) {
+            return Err(format!("The chain module name {} is reserved", name).into());
+        }
+
+        start_module(module.as_mut()).await?;
+        info!(%name, "Registered chain module");
+
+        self.chain_modules.insert(name, module);
+        Ok(())
+    }
+
+    /// Chain module registered under `name`, the built-in ones are `bitcoin`, `evm` and `near`
+    pub fn chain_module(&self, name: &str) -> Option<&dyn ChainModule> {
+        self.chain_modules.get(name).map(Box::as_ref)
+    }
+
+    /// Names of the registered chain modules, built-in ones included
+    pub fn chain_module_names(&self) -> Vec<&str> {
+        self.chain_modules.keys().map(String::as_str).collect()
+    }
+
+    /// Funds an account on the chain of the request
+    #[instrument(skip(self))]
+    pub async fn fund(&self, request: &FundingRequest) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match request {
+            #[cfg(feature = "evm")]
+            FundingRequest::Evm {
+                address,
+                eth,
+                erc20,
+            } => {
+                let evm = enabled(&self.evm_context, EvmModule::NAME)?;
+                evm.fund(*address, *eth, erc20).await
+            }
+            #[cfg(feature = "bitcoin")]
+            FundingRequest::Bitcoin { address, utxos } => {
+                enabled(&self.btc_context, BitcoinModule::NAME)?.fund_address(address, utxos)
+            }
+            FundingRequest::Near { account_id, amount } => {
+                let near = enabled(&self.near_context, NearModule::NAME)?;
+                near.fund(account_id, *amount).await
+            }
+        }
+    }
+
+    /// Funds the derived addresses of the default paths on every enabled chain
+    #[cfg(feature = "mpc-utils")]
+    pub async fn fund_derived_accounts(
+        &self,
+        funding: &DerivedFunding,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.fund_derived(&self.derived_accounts, funding).await
+    }
+
+    #[cfg(feature = "mpc-utils")]
+    #[cfg_attr(
+        not(any(feature = "bitcoin", feature = "evm")),
+        allow(unused_variables)
+    )]
+    pub(crate) async fn fund_derived(
+        &self,
+        derived_accounts: &DerivedAccounts,
+        funding: &DerivedFunding,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        #[cfg(feature = "evm")]
+        if let Some(derived) = derived_accounts.evm() {
+            let evm = enabled(&self.evm_context, EvmModule::NAME)?;
+            evm.fund(derived.address, funding.eth, &funding.erc20)
+                .await?;
+        }
+        #[cfg(feature = "bitcoin")]
+        if let Some(legacy) = derived_accounts.btc_legacy() {
+            enabled(&self.btc_context, BitcoinModule::NAME)?
+                .fund_address(&legacy.address, &funding.btc_legacy_utxos)?;
+        }
+        #[cfg(feature = "bitcoin")]
+        if let Some(segwit) = derived_accounts.btc_segwit() {
+            enabled(&self.btc_context, BitcoinModule::NAME)?
+                .fund_address(&segwit.address, &funding.btc_segwit_utxos)?;
+        }
+        if let (Some(derived), Some(amount)) = (derived_accounts.near(), funding.near) {
+            let near = enabled(&self.near_context, NearModule::NAME)?;
+            near.fund(&derived.account_id, amount).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Derives the accounts of an extra path on the given chain and registers them
+    #[cfg(feature = "mpc-utils")]
+    pub fn register_derivation_path(
+        &mut self,
+        network: &Network,
+        path: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.derived_accounts.register_path(network, path)
+    }
+
+    pub fn contract(&self, name: &str) -> Option<&DeployedContract> {
+        self.contracts.get(name)
+    }
+
+    /// Moves time forward by at least `duration` on every chain module
+    ///
+    /// Bitcoin uses a mock time and mines enough blocks to move the median time past, Anvil
+    /// increases its time and mines a block, and the NEAR sandbox is fast forwarded.
+    #[instrument(skip(self))]
+    pub async fn advance_time(
+        &self,
+        duration: Duration,
+    ) -> Result<ChainTimes, Box<dyn Error + Send + Sync>> {
+        try_join_all(
+            self.chain_modules
+                .values()
+                .map(|module| module.advance_time(duration)),
+        )
+        .await?;
+
+        self.chain_times().await
+    }
+
+    /// Produces `blocks` blocks on every chain module
+    #[instrument(skip(self))]
+    pub async fn advance_blocks(
+        &self,
+        blocks: u64,
+    ) -> Result<ChainTimes, Box<dyn Error + Send + Sync>> {
+        try_join_all(
+            self.chain_modules
+                .values()
+                .map(|module| module.advance_blocks(blocks)),
+        )
+        .await?;
+
+        self.chain_times().await
+    }
+
+    /// Current height and timestamp of every chain module
+    pub async fn chain_times(&self) -> Result<ChainTimes, Box<dyn Error + Send + Sync>> {
+        let mut times = ChainTimes::default();
+        for (name, module) in &self.chain_modules {
+            let time = ChainTime {
+                height: module.height().await?,
+                timestamp: module.timestamp().await?,
+            };
+            times.chains.insert(name.clone(), time);
+        }
+        debug!(?times, "Chain times");
+
+        Ok(times)
+    }
+
+    /// Takes a snapshot of every chain module
+    #[instrument(skip(self))]
+    pub async fn snapshot(&self) -> Result<SnapshotId, Box<dyn Error + Send + Sync>> {
+        let mut modules = BTreeMap::new();
+        for (name, module) in &self.chain_modules {
+            modules.insert(name.clone(), module.snapshot().await?);
+        }
+        let snapshot = OmniBoxSnapshot { modules };
+
+        let id = self.snapshots.lock().unwrap().insert(snapshot);
+        debug!(id, "Took snapshot");
+
+        Ok(id)
+    }
+
+    /// Reverts every chain to a snapshot, which stays valid for later reverts
+    ///
+    /// Snapshots taken after it are discarded. Contracts deployed through the friendly client
+    /// live on the configured NEAR network, not in the sandbox, and are not reverted. Modules
+    /// registered after the snapshot keep their state.
+    ///
+    /// The NEAR revert is partial, only the sandbox accounts known to OmniBox are restored:
+    /// accounts created or funded without OmniBox survive the revert with their state. A known
+    /// account that got storage entries or access keys since the snapshot is deleted and
+    /// recreated, which fails when its state is too large to be deleted in one transaction.
+    #[instrument(skip(self))]
+    pub async fn revert(&self, id: SnapshotId) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let snapshot = self
+            .snapshots
+            .lock()
+            .unwrap()
+            .get(id)
+            .ok_or_else(|| format!("Unknown snapshot {}", id))?;
+
+        for (name, module) in &self.chain_modules {
+            if let Some(module_snapshot) = snapshot.modules.get(name) {
+                module.revert(module_snapshot).await?;
+            }
+        }
+
+        self.snapshots.lock().unwrap().reset_to(id, snapshot);
+        debug!(id, "Reverted to snapshot");
+
+        Ok(())
+    }
+
     // Near utils
     async fn compile_and_deploy_contract(
-        &self,
-        path: &'static str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let cache_path = "cache/contract.json";
+        client: &FriendlyNearJsonRpcClient,
+        source: &ContractSource,
+        network: NearNetworkConfig,
+        force_redeploy: bool,
+        init: Option<&ContractCall>,
+        migrate: Option<&ContractCall>,
+    ) -> Result<CryptoHash, DeployError> {
+        // Prebuilt artifacts are hashed as they are, only Cargo projects get compiled
+        let contract_wasm = source.load().await?;
 
-        // Compile the contract
-        println!("Compiling contract");
+        Self::deploy_contract_wasm(
+            client,
+            source,
+            contract_wasm,
+            network,
+            force_redeploy,
+            init,
+            migrate,
+        )
+        .await
+    }
 
-        let contract_wasm = near_workspaces::compile_project(path).await?;
+    #[instrument(skip_all, fields(account_id = %client.account().account_id, contract = %source.cache_label()))]
+    async fn deploy_contract_wasm(
+        client: &FriendlyNearJsonRpcClient,
+        source: &ContractSource,
+        contract_wasm: Vec<u8>,
+        network: NearNetworkConfig,
+        force_redeploy: bool,
+        init: Option<&ContractCall>,
+        migrate: Option<&ContractCall>,
+    ) -> Result<CryptoHash, DeployError> {
+        let account_id = client.account().account_id.clone();
+        let network_name = get_network_name(network);
+        let contract_path = source.cache_label();
 
-        // Calculate the current hash of the contract
-        let current_hash = self.calculate_hash_from_bytes(&contract_wasm);
+        // Calculate the hash of the code, as reported by NEAR in `view_account`
+        let code_hash = CryptoHash::hash_bytes(&contract_wasm);
 
-        // Read the cached hash
-        let cached_hash = self.read_hash_from_cache(cache_path).unwrap_or_default();
+        let mut cache = DeployCache::load(DEFAULT_CACHE_PATH);
+        let cached_hash = cache
+            .get(network_name, &account_id, &contract_path)
+            .map(|entry| entry.code_hash);
+        let cache_entry = DeployCacheEntry {
+            network: network_name.to_string(),
+            account_id: account_id.clone(),
+            contract_path,
+            code_hash,
+        };
 
-        // Check if the hash has changed
-        if current_hash == cached_hash {
-            println!("Contract has not changed, skipping deployment.");
-            return Ok(());
+        // The on chain code decides whether to deploy, the cache records what was deployed so a
+        // drift of the on chain code can be reported
+        // A missing account is a first deploy, any other RPC error stops the deployment
+        let on_chain_hash = client
+            .view_account_if_exists(account_id.clone())
+            .await
+            .map_err(|err| DeployError::Account {
+                account_id: account_id.clone(),
+                reason: err.to_string(),
+            })?
+            .map(|account| account.code_hash);
+
+        if !force_redeploy && on_chain_hash == Some(code_hash) {
+            if cached_hash != Some(code_hash) {
+                cache.insert(cache_entry);
+                cache
+                    .save()
+                    .map_err(|err| DeployError::Cache(err.to_string()))?;
+            }
+            info!(account_id = %account_id, code_hash = %code_hash, "Contract has not changed, skipping deployment");
+            return Ok(code_hash);
+        }
+
+        if cached_hash == Some(code_hash) && on_chain_hash != Some(code_hash) {
+            warn!(
+                account_id = %account_id,
+                code_hash = %code_hash,
+                "Deployed code drifted from the cache, redeploying"
+            );
         }
 
         // Deploy the contract
-        self.friendly_near_json_rpc_client
-            .deploy_contract(contract_wasm)
-            .await?;
+        let outcome = client
+            .deploy_contract_with_wait_until(contract_wasm, TxExecutionStatus::Final)
+            .await
+            .and_then(TransactionOutcome::from_final_response)
+            .map_err(|err| DeployError::Deploy {
+                account_id: account_id.clone(),
+                reason: err.to_string(),
+            })?;
+
+        if let Some(hook) = DeployHook::after_deploy(on_chain_hash, code_hash, init, migrate) {
+            Self::call_deploy_hook(client, hook.call())
+                .await
+                .map_err(|reason| hook.error(account_id.clone(), reason))?;
+        }
 
         // Update the cache with the new hash
-        self.write_hash_to_cache(cache_path, &current_hash)?;
+        cache.insert(cache_entry);
+        cache
+            .save()
+            .map_err(|err| DeployError::Cache(err.to_string()))?;
 
-        println!("Contract deployed");
+        info!(
+            account_id = %account_id,
+            code_hash = %code_hash,
+            transaction_hash = %outcome.transaction_hash,
+            gas_burnt = outcome.gas_burnt,
+            "Contract deployed"
+        );
 
-        Ok(())
+        Ok(code_hash)
+    }
+
+    async fn call_deploy_hook(
+        client: &FriendlyNearJsonRpcClient,
+        call: &ContractCall,
+    ) -> Result<(), String> {
+        client
+            .function_call(call.method, call.args.clone(), HOOK_GAS, call.deposit)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+
+    #[instrument(skip_all)]
+    async fn deploy_workspace_contracts(
+        &self,
+        contracts: Vec<ContractConfig>,
+        network: NearNetworkConfig,
+        force_redeploy: bool,
+    ) -> Result<HashMap<&'static str, DeployedContract>, DeployError> {
+        let mut deployed_contracts = HashMap::new();
+
+        for contract in contracts {
+            let deployed_contract = match contract.target {
+                ContractTarget::SubAccount {
+                    prefix,
+                    initial_balance,
+                } => {
+                    let account_id: AccountId =
+                        format!("{}.{}", prefix, self.deployer_account.account_id)
+                            .parse::<AccountId>()
+                            .map_err(|err| DeployError::Account {
+                                account_id: self.deployer_account.account_id.clone(),
+                                reason: err.to_string(),
+                            })?;
+
+                    // Sub accounts share the deployer key, so they can be reused across runs
+                    let account = NearAccount {
+                        account_id: account_id.clone(),
+                        private_key: self.deployer_account.private_key.clone(),
+                        public_key: self.deployer_account.public_key.clone(),
+                    };
+
+                    let client = &self.friendly_near_json_rpc_client;
+                    let existing = client
+                        .view_account_if_exists(account_id.clone())
+                        .await
+                        .map_err(|err| DeployError::Account {
+                            account_id: account_id.clone(),
+                            reason: err.to_string(),
+                        })?;
+                    if existing.is_none() {
+                        client
+                            .create_sub_account(
+                                account_id.clone(),
+                                account.public_key.clone(),
+                                initial_balance,
+                            )
+                            .await
+                            .map_err(|err| DeployError::Account {
+                                account_id,
+                                reason: err.to_string(),
+                            })?;
+                    }
+
+                    let code_hash = Self::compile_and_deploy_contract(
+                        &client.with_account(account.clone()),
+                        &contract.source,
+                        network,
+                        force_redeploy,
+                        contract.init.as_ref(),
+                        contract.migrate.as_ref(),
+                    )
+                    .await?;
+
+                    DeployedContract {
+                        name: contract.name,
+                        account,
+                        code_hash,
+                        sandbox_contract: None,
+                    }
+                }
+                ContractTarget::DevAccount => self.deploy_to_dev_account(&contract).await?,
+            };
+
+            info!(
+                name = contract.name,
+                account_id = %deployed_contract.account_id(),
+                code_hash = %deployed_contract.code_hash,
+                "Contract available"
+            );
+            deployed_contracts.insert(contract.name, deployed_contract);
+        }
+
+        Ok(deployed_contracts)
+    }
+
+    #[instrument(skip_all, fields(name = contract.name))]
+    async fn deploy_to_dev_account(
+        &self,
+        contract: &ContractConfig,
+    ) -> Result<DeployedContract, DeployError> {
+        // Dev accounts live in the sandbox, which only runs with the near module
+        let near =
+            enabled(&self.near_context, NearModule::NAME).map_err(|err| DeployError::Account {
+                account_id: self.deployer_account.account_id.clone(),
+                reason: format!("no dev account for {}: {}", contract.name, err),
+            })?;
+        let contract_wasm = contract.source.load().await?;
+        let code_hash = CryptoHash::hash_bytes(&contract_wasm);
+
+        // Contracts saved in the warm start template are patched in instead of deployed again
+        let restored = match &self.warm_start {
+            Some(warm_start) => warm_start
+                .restore_near_contract(near, contract.name, &contract_wasm)
+                .await
+                .map_err(|err| DeployError::Cache(err.to_string()))?,
+            None => None,
+        };
+        let sandbox_contract = match restored {
+            Some(sandbox_contract) => sandbox_contract,
+            None => Self::deploy_and_init_dev_account(near, contract, &contract_wasm).await?,
+        };
+        let account_id = sandbox_contract.id().clone();
+
+        let private_key = sandbox_contract
+            .as_account()
+            .secret_key()
+            .to_string()
+            .parse::<SecretKey>()
+            .map_err(|err| DeployError::Account {
+                account_id: account_id.clone(),
+                reason: err.to_string(),
+            })?;
+
+        Ok(DeployedContract {
+            name: contract.name,
+            account: NearAccount {
+                account_id,
+                public_key: private_key.public_key(),
+                private_key,
+            },
+            code_hash,
+            sandbox_contract: Some(sandbox_contract),
+        })
+    }
+
+    async fn deploy_and_init_dev_account(
+        near: &NearTestContext,
+        contract: &ContractConfig,
+        contract_wasm: &[u8],
+    ) -> Result<near_workspaces::Contract, DeployError> {
+        let dev_account = near.create_account().await;
+        let sandbox_contract = deploy_to_sandbox(&dev_account, contract_wasm).await?;
+
+        // Dev accounts are always fresh, so the contract is always initialized
+        if let Some(init) = &contract.init {
+            DeployHook::Init(init)
+                .call_sandbox(&sandbox_contract)
+                .await?;
+        }
+
+        Ok(sandbox_contract)
     }
 
     pub async fn get_experimental_signature_deposit(&self) -> Result<u128, Box<dyn Error>> {
         Ok(1) // Since the experimental signature deposit function was deprecated in favour of simply 1 yoctoNEAR
     }
+}
+
+/// Starts a chain module and waits until its node answers requests
+async fn start_module(module: &mut dyn ChainModule) -> Result<(), Box<dyn Error + Send + Sync>> {
+    module.start().await?;
 
-    // Caching capabilities
-    fn calculate_hash_from_bytes(&self, bytes: &[u8]) -> String {
-        let mut hasher = Sha3_256::new();
-        hasher.update(bytes);
-        let hash = hasher.finalize();
-        format!("{:x}", hash)
+    let started_at = Instant::now();
+    while !module.is_ready().await? {
+        if started_at.elapsed() > MODULE_READY_TIMEOUT {
+            return Err(format!(
+                "Chain module {} is not ready after {:?}",
+                module.name(),
+                MODULE_READY_TIMEOUT
+            )
+            .into());
+        }
+        tokio::time::sleep(MODULE_READY_POLL_INTERVAL).await;
     }
 
-    fn write_hash_to_cache<P: AsRef<Path>>(
-        &self,
-        cache_path: P,
-        hash: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let cache_path = cache_path.as_ref();
-        if let Some(parent) = cache_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(cache_path)?;
-        writeln!(file, "{}", hash)?;
-        Ok(())
+    Ok(())
+}
+
+/// Context of a built-in module, if enabled
+fn enabled<'a, T>(
+    context: &'a Option<Arc<T>>,
+    module: &str,
+) -> Result<&'a Arc<T>, Box<dyn Error + Send + Sync>> {
+    context.as_ref().ok_or_else(|| {
+        format!(
+            "The {} module is not enabled in OmniBoxOptions::modules",
+            module
+        )
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_module::{ModuleFuture, ModuleSnapshot};
+    use crate::manifest::{Manifest, ManifestAccount};
+    use near_crypto::KeyType;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Chain kept in memory, its whole state is its height
+    #[derive(Default)]
+    struct MemoryChain {
+        height: AtomicU64,
     }
 
-    fn read_hash_from_cache<P: AsRef<Path>>(
-        &self,
-        cache_path: P,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        let hash = std::fs::read_to_string(cache_path)?;
-        Ok(hash.trim().to_string())
+    impl ChainModule for MemoryChain {
+        fn name(&self) -> &str {
+            "memory"
+        }
+
+        fn start(&mut self) -> ModuleFuture<'_, ()> {
+            Box::pin(async move { Ok(()) })
+        }
+
+        fn is_ready(&self) -> ModuleFuture<'_, bool> {
+            Box::pin(async move { Ok(true) })
+        }
+
+        fn create_funded_account(&self, _balance: u128) -> ModuleFuture<'_, ManifestAccount> {
+            Box::pin(async move { Err("Not supported".into()) })
+        }
+
+        fn fund<'a>(&'a self, _address: &'a str, _amount: u128) -> ModuleFuture<'a, ()> {
+            Box::pin(async move { Ok(()) })
+        }
+
+        fn broadcast_raw_transaction<'a>(
+            &'a self,
+            _raw_transaction: &'a [u8],
+        ) -> ModuleFuture<'a, String> {
+            Box::pin(async move { Err("Not supported".into()) })
+        }
+
+        fn height(&self) -> ModuleFuture<'_, u64> {
+            Box::pin(async move { Ok(self.height.load(Ordering::SeqCst)) })
+        }
+
+        fn timestamp(&self) -> ModuleFuture<'_, u64> {
+            Box::pin(async move { Ok(self.height.load(Ordering::SeqCst) * 10) })
+        }
+
+        fn advance_time(&self, duration: Duration) -> ModuleFuture<'_, ()> {
+            Box::pin(async move {
+                self.height
+                    .fetch_add(duration.as_secs().div_ceil(10), Ordering::SeqCst);
+                Ok(())
+            })
+        }
+
+        fn advance_blocks(&self, blocks: u64) -> ModuleFuture<'_, ()> {
+            Box::pin(async move {
+                self.height.fetch_add(blocks, Ordering::SeqCst);
+                Ok(())
+            })
+        }
+
+        fn snapshot(&self) -> ModuleFuture<'_, ModuleSnapshot> {
+            Box::pin(async move {
+                let snapshot: ModuleSnapshot = Arc::new(self.height.load(Ordering::SeqCst));
+                Ok(snapshot)
+            })
+        }
+
+        fn revert<'a>(&'a self, snapshot: &'a ModuleSnapshot) -> ModuleFuture<'a, ()> {
+            Box::pin(async move {
+                let height = crate::chain_module::downcast_snapshot::<u64>("memory", snapshot)?;
+                self.height.store(*height, Ordering::SeqCst);
+                Ok(())
+            })
+        }
+
+        fn manifest(&self) -> serde_json::Value {
+            serde_json::json!({ "height": self.height.load(Ordering::SeqCst) })
+        }
+    }
+
+    /// OmniBox without any built-in module nor deployed contract
+    fn omni_box_without_nodes() -> OmniBox {
+        let private_key = SecretKey::from_random(KeyType::ED25519);
+        let deployer_account = NearAccount {
+            account_id: "deployer.testnet".parse().unwrap(),
+            public_key: private_key.public_key(),
+            private_key,
+        };
+
+        OmniBox {
+            chains: HashMap::new(),
+            #[cfg(feature = "bitcoin")]
+            btc_context: None,
+            near_context: None,
+            #[cfg(feature = "evm")]
+            evm_context: None,
+            friendly_near_json_rpc_client: FriendlyNearJsonRpcClient::new(
+                NearNetworkConfig::Testnet,
+                deployer_account.clone(),
+            ),
+            #[cfg(feature = "mpc-utils")]
+            derived_accounts: DerivedAccounts::new(
+                deployer_account.account_id.clone(),
+                &[],
+                "bitcoin-1",
+                "ethereum-1",
+                "near-1",
+            )
+            .unwrap(),
+            deployer_account,
+            near_cassette: None,
+            contracts: HashMap::new(),
+            chain_modules: BTreeMap::new(),
+            snapshots: Mutex::new(SnapshotRegistry::new()),
+            startup_timings: StartupTimings::default(),
+            warm_start: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registered_module_takes_part_in_snapshots_and_manifest() {
+        let mut omni_box = omni_box_without_nodes();
+        omni_box
+            .register_module(Box::new(MemoryChain::default()))
+            .await
+            .unwrap();
+        assert!(omni_box.near_context().is_none());
+        assert_eq!(omni_box.chain_module_names(), ["memory"]);
+
+        let times = omni_box.advance_blocks(5).await.unwrap();
+        assert_eq!(times["memory"].height, 5);
+
+        let id = omni_box.snapshot().await.unwrap();
+        let times = omni_box
+            .advance_time(Duration::from_secs(25))
+            .await
+            .unwrap();
+        assert_eq!(times["memory"].height, 8);
+
+        omni_box.revert(id).await.unwrap();
+        assert_eq!(omni_box.chain_times().await.unwrap()["memory"].height, 5);
+
+        let manifest = Manifest::new(&omni_box);
+        assert_eq!(
+            manifest.modules["memory"],
+            serde_json::json!({ "height": 5 })
+        );
+        assert!(manifest.bitcoin.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_register_module_rejects_reserved_and_duplicate_names() {
+        let mut omni_box = omni_box_without_nodes();
+        omni_box
+            .register_module(Box::new(MemoryChain::default()))
+            .await
+            .unwrap();
+
+        assert!(omni_box
+            .register_module(Box::new(MemoryChain::default()))
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("already registered"));
+        assert!(omni_box
+            .register_module(Box::new(NearModule::new(
+                Default::default(),
+                None,
+                String::new(),
+                String::new(),
+            )))
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("reserved"));
+    }
+
+    fn rpc_error(name: &str, info: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({ "error": {
+            "name": "HANDLER_ERROR",
+            "cause": { "name": name, "info": info },
+            "code": -32000,
+            "message": "Server error",
+        } })
+    }
+
+    async fn deploy_to_stub(
+        respond: impl Fn(&serde_json::Value) -> serde_json::Value + Send + 'static,
+    ) -> DeployError {
+        let (url, upstream) =
+            crate::friendly_near_json_rpc_client::cassette::stub_upstream(respond).await;
+        let omni_box = omni_box_without_nodes();
+        let client =
+            FriendlyNearJsonRpcClient::new_with_rpc_url(&url, omni_box.deployer_account.clone());
+
+        let err = OmniBox::deploy_contract_wasm(
+            &client,
+            &ContractSource::WasmBytes(vec![0, 97, 115, 109]),
+            vec![0, 97, 115, 109],
+            NearNetworkConfig::Testnet,
+            false,
+            None,
+            None,
+        )
+        .await
+        .unwrap_err();
+        upstream.abort();
+        err
+    }
+
+    #[tokio::test]
+    async fn test_deploy_reports_account_errors() {
+        let err = deploy_to_stub(|_| {
+            rpc_error(
+                "INTERNAL_ERROR",
+                serde_json::json!({ "error_message": "node is syncing" }),
+            )
+        })
+        .await;
+
+        assert!(
+            matches!(&err, DeployError::Account { account_id, .. } if account_id == "deployer.testnet"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deploy_reports_deploy_errors() {
+        let err = deploy_to_stub(|request| match request["params"]["request_type"].as_str() {
+            Some("view_account") => rpc_error(
+                "UNKNOWN_ACCOUNT",
+                serde_json::json!({
+                    "requested_account_id": "deployer.testnet",
+                    "block_height": 1,
+                    "block_hash": CryptoHash::default().to_string(),
+                }),
+            ),
+            Some("view_access_key") => serde_json::json!({ "result": {
+                    "nonce": 1,
+                    "permission": "FullAccess",
+                    "block_height": 1,
+                    "block_hash": CryptoHash::default().to_string(),
+                } }),
+            _ => rpc_error(
+                "INTERNAL_ERROR",
+                serde_json::json!({ "error_message": "rejected" }),
+            ),
+        })
+        .await;
+
+        assert!(
+            matches!(&err, DeployError::Deploy { account_id, .. } if account_id == "deployer.testnet"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sub_account_contracts_create_the_missing_account() {
+        let methods = Arc::new(Mutex::new(vec![]));
+        let recorded = methods.clone();
+        let (url, upstream) =
+            crate::friendly_near_json_rpc_client::cassette::stub_upstream(move |request| {
+                let method = request["params"]["request_type"]
+                    .as_str()
+                    .or_else(|| request["method"].as_str())
+                    .unwrap()
+                    .to_string();
+                recorded.lock().unwrap().push(method.clone());
+                match method.as_str() {
+                    "view_account" => rpc_error(
+                        "UNKNOWN_ACCOUNT",
+                        serde_json::json!({
+                            "requested_account_id": "vault.deployer.testnet",
+                            "block_height": 1,
+                            "block_hash": CryptoHash::default().to_string(),
+                        }),
+                    ),
+                    "view_access_key" => serde_json::json!({ "result": {
+                        "nonce": 1,
+                        "permission": "FullAccess",
+                        "block_height": 1,
+                        "block_hash": CryptoHash::default().to_string(),
+                    } }),
+                    _ => rpc_error(
+                        "INTERNAL_ERROR",
+                        serde_json::json!({ "error_message": "rejected" }),
+                    ),
+                }
+            })
+            .await;
+        let mut omni_box = omni_box_without_nodes();
+        omni_box.friendly_near_json_rpc_client =
+            FriendlyNearJsonRpcClient::new_with_rpc_url(&url, omni_box.deployer_account.clone());
+
+        let err = omni_box
+            .deploy_workspace_contracts(
+                vec![ContractConfig::sub_account(
+                    "vault",
+                    vec![0, 97, 115, 109],
+                    "vault",
+                )],
+                NearNetworkConfig::Testnet,
+                false,
+            )
+            .await
+            .unwrap_err();
+        upstream.abort();
+
+        assert!(
+            matches!(&err, DeployError::Account { account_id, .. } if account_id == "vault.deployer.testnet"),
+            "{:?}",
+            err
+        );
+        let methods = methods.lock().unwrap();
+        assert_eq!(methods[0], "view_account");
+        assert_eq!(methods.last().unwrap(), "send_tx");
+    }
+
+    #[tokio::test]
+    async fn test_dev_account_contracts_need_the_near_module() {
+        let omni_box = omni_box_without_nodes();
+
+        let err = omni_box
+            .deploy_workspace_contracts(
+                vec![ContractConfig::dev_account("dev", vec![0, 97, 115, 109])],
+                NearNetworkConfig::Testnet,
+                false,
+            )
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(&err, DeployError::Account { reason, .. } if reason.contains("no dev account for dev")),
+            "{:?}",
+            err
+        );
     }
 }
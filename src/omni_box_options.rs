@@ -1,31 +1,82 @@
+#[cfg(feature = "mpc-utils")]
+use crate::funding::DerivedFunding;
 use crate::{
     chain_config::ChainOverrides,
-    friendly_near_json_rpc_client::near_network_config::NearNetworkConfig, network::Network,
+    chain_module::ChainModuleFactory,
+    contract_config::{ContractCall, ContractConfig, ContractSource},
+    friendly_near_json_rpc_client::{
+        cassette::CassetteMode, near_network_config::NearNetworkConfig,
+    },
+    funding::FundingRequest,
+    network::Network,
+    prerequisites::BinaryPaths,
 };
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct OmniBoxOptions {
-    pub modules: Vec<Network>,                       // Networks to include
+    pub modules: Vec<Network>,                  // Built-in networks to include
+    pub chain_modules: Vec<ChainModuleFactory>, // Third-party chains started with them
     pub overrides: HashMap<Network, ChainOverrides>, // Overrides for each network
-    pub path: &'static str,                          // Path to the config file
-    pub default_near_network: NearNetworkConfig,     // Default Near network
-    pub btc_path: &'static str,                      // Default path of the Bitcoin address
-    pub evm_path: &'static str,                      // Default path of the EVM address
+    pub contract: ContractSource,               // Contract deployed to the deployer account
+    pub default_near_network: NearNetworkConfig, // Default Near network
+    #[cfg(feature = "mpc-utils")]
+    pub btc_path: &'static str, // Default path of the Bitcoin address
+    #[cfg(feature = "mpc-utils")]
+    pub evm_path: &'static str, // Default path of the EVM address
+    #[cfg(feature = "mpc-utils")]
+    pub near_path: &'static str, // Default path of the NEAR account
+    pub near_cassette: Option<(CassetteMode, &'static str)>, // Record / replay the NEAR RPC traffic
+    pub force_redeploy: bool,                   // Deploy the contract even if it has not changed
+    pub init: Option<ContractCall>,             // Called after the first deploy of `contract`
+    pub migrate: Option<ContractCall>,          // Called after redeploying changed `contract` code
+    pub contracts: Vec<ContractConfig>, // Additional contracts deployed to their own accounts
+    #[cfg(feature = "mpc-utils")]
+    pub derived_funding: DerivedFunding, // Funds of the derived addresses on every enabled chain
+    pub funding: Vec<FundingRequest>,   // Funds of any other account, applied at startup
+    pub binaries: BinaryPaths,          // Explicit paths of bitcoind, anvil and near-sandbox
+    pub seed: Option<u64>,              // Makes every generated account and key deterministic
+    pub warm_start: Option<&'static str>, // Directory of the warm start templates, e.g. `DEFAULT_TEMPLATES_PATH`
 }
 
+#[cfg(feature = "mpc-utils")]
 const DEFAULT_BTC_PATH: &str = "bitcoin-1";
+#[cfg(feature = "mpc-utils")]
 const DEFAULT_EVM_PATH: &str = "ethereum-1";
+#[cfg(feature = "mpc-utils")]
+const DEFAULT_NEAR_PATH: &str = "near-1";
 
 impl Default for OmniBoxOptions {
     fn default() -> Self {
         Self {
-            modules: vec![Network::EVM, Network::Near, Network::Bitcoin],
+            modules: vec![
+                #[cfg(feature = "evm")]
+                Network::EVM,
+                Network::Near,
+                #[cfg(feature = "bitcoin")]
+                Network::Bitcoin,
+            ],
+            chain_modules: vec![],
             overrides: HashMap::new(),
-            path: "./",
+            contract: ContractSource::Project("./"),
             default_near_network: NearNetworkConfig::Testnet,
+            #[cfg(feature = "mpc-utils")]
             btc_path: DEFAULT_BTC_PATH,
+            #[cfg(feature = "mpc-utils")]
             evm_path: DEFAULT_EVM_PATH,
+            #[cfg(feature = "mpc-utils")]
+            near_path: DEFAULT_NEAR_PATH,
+            near_cassette: None,
+            force_redeploy: false,
+            init: None,
+            migrate: None,
+            contracts: vec![],
+            #[cfg(feature = "mpc-utils")]
+            derived_funding: DerivedFunding::default(),
+            funding: vec![],
+            binaries: BinaryPaths::default(),
+            seed: None,
+            warm_start: None,
         }
     }
 }
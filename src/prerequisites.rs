@@ -0,0 +1,404 @@
+//! Discovery and version checks of the node binaries OmniBox runs
+//!
+//! Each binary is resolved from the explicit path in the options, then from its environment
+//! variable, then from `PATH`. bitcoind falls back to the binary downloaded when building its
+//! client, and the NEAR sandbox is downloaded by near-workspaces when no binary is configured.
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variable pointing at the bitcoind executable
+pub const BITCOIND_ENV: &str = "BITCOIND_EXE";
+
+/// Environment variable pointing at the anvil executable
+pub const ANVIL_ENV: &str = "ANVIL_BIN";
+
+/// Environment variable pointing at the near-sandbox executable, read by near-workspaces
+pub const NEAR_SANDBOX_ENV: &str = "NEAR_SANDBOX_BIN_PATH";
+
+/// Explicit paths of the node binaries, overriding the environment variables and `PATH`
+#[derive(Debug, Clone, Default)]
+pub struct BinaryPaths {
+    pub bitcoind: Option<PathBuf>,
+    pub anvil: Option<PathBuf>,
+    pub near_sandbox: Option<PathBuf>,
+}
+
+/// Node binary required by OmniBox
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prerequisite {
+    Bitcoind,
+    Anvil,
+    NearSandbox,
+}
+
+type Version = (u64, u64, u64);
+
+impl Prerequisite {
+    pub const ALL: [Self; 3] = [Self::Bitcoind, Self::Anvil, Self::NearSandbox];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Bitcoind => "bitcoind",
+            Self::Anvil => "anvil",
+            Self::NearSandbox => "near-sandbox",
+        }
+    }
+
+    pub const fn env_var(self) -> &'static str {
+        match self {
+            Self::Bitcoind => BITCOIND_ENV,
+            Self::Anvil => ANVIL_ENV,
+            Self::NearSandbox => NEAR_SANDBOX_ENV,
+        }
+    }
+
+    /// Supported versions, from the minimum included to the maximum excluded
+    pub const fn supported_versions(self) -> (Version, Version) {
+        match self {
+            Self::Bitcoind => ((25, 0, 0), (29, 0, 0)),
+            Self::Anvil => ((0, 2, 0), (2, 0, 0)),
+            Self::NearSandbox => ((2, 0, 0), (3, 0, 0)),
+        }
+    }
+
+    /// How to install the binary or point OmniBox at it
+    pub const fn install_hint(self) -> &'static str {
+        match self {
+            Self::Bitcoind => "Install Bitcoin Core from https://bitcoin.org/en/download, or set BITCOIND_EXE or `binaries.bitcoind` to the bitcoind executable",
+            Self::Anvil => "Install Foundry with `curl -L https://foundry.paradigm.xyz | bash && foundryup`, or set ANVIL_BIN or `binaries.anvil` to the anvil executable",
+            Self::NearSandbox => "Unset NEAR_SANDBOX_BIN_PATH to let near-workspaces download the sandbox, or point it or `binaries.near_sandbox` at a near-sandbox executable",
+        }
+    }
+
+    fn explicit_path(self, binaries: &BinaryPaths) -> Option<&Path> {
+        match self {
+            Self::Bitcoind => binaries.bitcoind.as_deref(),
+            Self::Anvil => binaries.anvil.as_deref(),
+            Self::NearSandbox => binaries.near_sandbox.as_deref(),
+        }
+    }
+}
+
+/// Errors raised while looking for a node binary
+#[derive(Debug, Clone)]
+pub enum PrerequisiteError {
+    NotFound {
+        prerequisite: Prerequisite,
+        searched: String,
+    },
+    VersionUnreadable {
+        prerequisite: Prerequisite,
+        path: PathBuf,
+        reason: String,
+    },
+    UnsupportedVersion {
+        prerequisite: Prerequisite,
+        path: PathBuf,
+        version: String,
+    },
+}
+
+impl fmt::Display for PrerequisiteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound {
+                prerequisite,
+                searched,
+            } => write!(
+                f,
+                "{} not found ({}). {}",
+                prerequisite.name(),
+                searched,
+                prerequisite.install_hint()
+            ),
+            Self::VersionUnreadable {
+                prerequisite,
+                path,
+                reason,
+            } => write!(
+                f,
+                "Failed to read the version of {} at {}: {}. {}",
+                prerequisite.name(),
+                path.display(),
+                reason,
+                prerequisite.install_hint()
+            ),
+            Self::UnsupportedVersion {
+                prerequisite,
+                path,
+                version,
+            } => {
+                let (min, max) = prerequisite.supported_versions();
+                write!(
+                    f,
+                    "{} {} at {} is not supported, expected a version from {}.{}.{} up to {}.{}.{} excluded. {}",
+                    prerequisite.name(),
+                    version,
+                    path.display(),
+                    min.0,
+                    min.1,
+                    min.2,
+                    max.0,
+                    max.1,
+                    max.2,
+                    prerequisite.install_hint()
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PrerequisiteError {}
+
+/// Result of the check of a node binary
+#[derive(Debug, Clone)]
+pub struct BinaryCheck {
+    pub prerequisite: Prerequisite,
+    pub path: Option<PathBuf>, // None when near-workspaces manages the sandbox
+    pub version: Option<String>,
+    pub error: Option<PrerequisiteError>,
+}
+
+/// Report of the checks of every node binary
+#[derive(Debug, Clone)]
+pub struct DoctorReport {
+    pub checks: Vec<BinaryCheck>,
+}
+
+impl DoctorReport {
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(|check| check.error.is_none())
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match (&check.error, &check.path) {
+                (Some(error), _) => writeln!(f, "[fail] {}", error)?,
+                (None, Some(path)) => writeln!(
+                    f,
+                    "[ok]   {} {} at {}",
+                    check.prerequisite.name(),
+                    check.version.as_deref().unwrap_or("unknown version"),
+                    path.display()
+                )?,
+                (None, None) => writeln!(
+                    f,
+                    "[ok]   {} is downloaded by near-workspaces on first use",
+                    check.prerequisite.name()
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks every node binary and reports all the problems together
+pub fn doctor(binaries: &BinaryPaths) -> DoctorReport {
+    let checks = Prerequisite::ALL
+        .into_iter()
+        .map(|prerequisite| match find(prerequisite, binaries) {
+            Ok(None) => BinaryCheck {
+                prerequisite,
+                path: None,
+                version: None,
+                error: None,
+            },
+            Ok(Some(path)) => match check_version(prerequisite, &path) {
+                Ok(version) => BinaryCheck {
+                    prerequisite,
+                    path: Some(path),
+                    version: Some(version),
+                    error: None,
+                },
+                Err(error) => BinaryCheck {
+                    prerequisite,
+                    path: Some(path),
+                    version: None,
+                    error: Some(error),
+                },
+            },
+            Err(error) => BinaryCheck {
+                prerequisite,
+                path: None,
+                version: None,
+                error: Some(error),
+            },
+        })
+        .collect();
+
+    DoctorReport { checks }
+}
+
+/// Finds a node binary and checks its version
+///
+/// Returns `None` for the NEAR sandbox when near-workspaces downloads it.
+pub fn resolve(
+    prerequisite: Prerequisite,
+    binaries: &BinaryPaths,
+) -> Result<Option<PathBuf>, PrerequisiteError> {
+    let path = find(prerequisite, binaries)?;
+    if let Some(path) = &path {
+        check_version(prerequisite, path)?;
+    }
+    Ok(path)
+}
+
+fn find(
+    prerequisite: Prerequisite,
+    binaries: &BinaryPaths,
+) -> Result<Option<PathBuf>, PrerequisiteError> {
+    if let Some(path) = prerequisite.explicit_path(binaries) {
+        return existing(
+            prerequisite,
+            path.to_path_buf(),
+            "explicit path in the options",
+        );
+    }
+
+    if let Some(path) = env::var_os(prerequisite.env_var()) {
+        return existing(
+            prerequisite,
+            PathBuf::from(path),
+            &format!("{} environment variable", prerequisite.env_var()),
+        );
+    }
+
+    match prerequisite {
+        // near-workspaces downloads a sandbox matching its version
+        Prerequisite::NearSandbox => Ok(None),
+        _ => find_in_path(prerequisite.name())
+            .or_else(|| downloaded(prerequisite))
+            .map(Some)
+            .ok_or_else(|| PrerequisiteError::NotFound {
+                prerequisite,
+                searched: format!(
+                    "{} is not set and PATH has no {}",
+                    prerequisite.env_var(),
+                    prerequisite.name()
+                ),
+            }),
+    }
+}
+
+fn existing(
+    prerequisite: Prerequisite,
+    path: PathBuf,
+    source: &str,
+) -> Result<Option<PathBuf>, PrerequisiteError> {
+    if path.is_file() {
+        Ok(Some(path))
+    } else {
+        Err(PrerequisiteError::NotFound {
+            prerequisite,
+            searched: format!("{} points at missing file {}", source, path.display()),
+        })
+    }
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    env::var_os("PATH").and_then(|paths| {
+        env::split_paths(&paths)
+            .map(|directory| directory.join(name))
+            .find(|path| path.is_file())
+    })
+}
+
+/// Binary downloaded at build time, bitcoind comes with the `26_0` feature of its client
+#[cfg(feature = "bitcoin")]
+fn downloaded(prerequisite: Prerequisite) -> Option<PathBuf> {
+    match prerequisite {
+        Prerequisite::Bitcoind => bitcoind::exe_path()
+            .ok()
+            .map(PathBuf::from)
+            .filter(|path| path.is_file()),
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "bitcoin"))]
+const fn downloaded(_prerequisite: Prerequisite) -> Option<PathBuf> {
+    None
+}
+
+fn check_version(prerequisite: Prerequisite, path: &Path) -> Result<String, PrerequisiteError> {
+    let output = Command::new(path)
+        .arg("--version")
+        .output()
+        .map_err(|err| PrerequisiteError::VersionUnreadable {
+            prerequisite,
+            path: path.to_path_buf(),
+            reason: err.to_string(),
+        })?;
+    let output = String::from_utf8_lossy(&output.stdout);
+
+    let version = parse_version(&output).ok_or_else(|| PrerequisiteError::VersionUnreadable {
+        prerequisite,
+        path: path.to_path_buf(),
+        reason: format!(
+            "unexpected output {:?}",
+            output.lines().next().unwrap_or_default()
+        ),
+    })?;
+    let version_label = format!("{}.{}.{}", version.0, version.1, version.2);
+
+    let (min, max) = prerequisite.supported_versions();
+    if version < min || version >= max {
+        return Err(PrerequisiteError::UnsupportedVersion {
+            prerequisite,
+            path: path.to_path_buf(),
+            version: version_label,
+        });
+    }
+
+    Ok(version_label)
+}
+
+/// Finds the first `major.minor[.patch]` version in the output of `--version`
+fn parse_version(output: &str) -> Option<Version> {
+    output.split_whitespace().find_map(|word| {
+        let word = word
+            .trim_start_matches(['v', '('])
+            .trim_end_matches([')', ',']);
+        let numbers = word
+            .split(['.', '-'])
+            .take(3)
+            .map_while(|part| part.parse::<u64>().ok())
+            .collect::<Vec<_>>();
+
+        match numbers[..] {
+            [major, minor] => Some((major, minor, 0)),
+            [major, minor, patch] => Some((major, minor, patch)),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("Bitcoin Core version v26.0.0\nCopyright (C)"),
+            Some((26, 0, 0))
+        );
+        assert_eq!(
+            parse_version("anvil 0.2.0 (e10ab3d 2024-11-26T00:22:09.587153000Z)"),
+            Some((0, 2, 0))
+        );
+        assert_eq!(
+            parse_version("anvil Version: 1.0.0-stable"),
+            Some((1, 0, 0))
+        );
+        assert_eq!(
+            parse_version("neard (release 2.3.0) (build 2.3.0) (rustc 1.79.0)"),
+            Some((2, 3, 0))
+        );
+        assert_eq!(parse_version("no version here"), None);
+    }
+}
@@ -0,0 +1,27 @@
+//! Deterministic secrets derived from the `seed` option
+use sha3::{Digest, Sha3_256};
+
+/// Derives the 32 bytes secret of a generated account from the seed and a label naming it
+pub fn derive_secret(seed: u64, label: &str) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(format!("omni-box:{}:{}", seed, label));
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_secret() {
+        assert_eq!(
+            derive_secret(7, "near/alice"),
+            derive_secret(7, "near/alice")
+        );
+        assert_ne!(
+            derive_secret(7, "near/alice"),
+            derive_secret(8, "near/alice")
+        );
+        assert_ne!(derive_secret(7, "near/alice"), derive_secret(7, "near/bob"));
+    }
+}
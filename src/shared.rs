@@ -0,0 +1,325 @@
+//! Process wide OmniBox shared by the tests of a binary
+//!
+//! The nodes are started once, on a runtime that outlives the runtimes of the individual tests.
+//! Leases are set up on that runtime too, and the nodes are stopped when the process exits.
+use crate::contexts::ChainContext;
+#[cfg(feature = "bitcoin")]
+use crate::contexts::UserInfo;
+#[cfg(feature = "mpc-utils")]
+use crate::derived_accounts::DerivedAccounts;
+#[cfg(feature = "mpc-utils")]
+use crate::funding::DerivedFunding;
+use crate::omni_box::OmniBox;
+use crate::omni_box_options::OmniBoxOptions;
+use crate::snapshot::SnapshotId;
+#[cfg(feature = "evm")]
+use alloy::primitives::utils::parse_units;
+#[cfg(feature = "evm")]
+use alloy::signers::local::PrivateKeySigner;
+#[cfg(feature = "bitcoin")]
+use bitcoin::Amount;
+use near_workspaces::types::NearToken;
+use near_workspaces::Account;
+use std::collections::HashMap;
+use std::error::Error;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use tokio::runtime::Runtime;
+use tokio::sync::{Mutex, OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
+use tracing::{debug, info, warn};
+
+/// Balance of the NEAR account created for each lease
+const LEASE_NEAR_BALANCE: NearToken = NearToken::from_near(100);
+
+/// Balance of the BTC account created for each lease
+#[cfg(feature = "bitcoin")]
+const LEASE_BTC_BALANCE: Amount = Amount::from_sat(100_000_000);
+
+static SHARED: Mutex<Option<SharedOmniBox>> = Mutex::const_new(None);
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+static NEXT_LEASE_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone)]
+struct SharedOmniBox {
+    omni_box: Arc<OmniBox>,
+    #[cfg(feature = "mpc-utils")]
+    derived_funding: DerivedFunding,
+    baseline: SnapshotId, // State right after startup, restored by isolated leases
+    access: Arc<RwLock<()>>, // Shared by account leases, exclusive for isolated leases
+    options: Arc<str>,    // Options of the first call, compared with the later ones
+}
+
+/// How a lease is isolated from the other tests using the shared OmniBox
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaseIsolation {
+    /// Fresh accounts and derivation paths, leases run concurrently
+    FreshAccounts,
+    /// Exclusive access, starting from the state of the chains right after startup
+    Snapshot,
+}
+
+/// Access to the shared OmniBox handed out to a single test
+///
+/// Dereferences to the shared `OmniBox`.
+pub struct OmniBoxLease {
+    pub id: u64,
+    pub near_account: Option<Account>, // Sandbox account funded with 100 NEAR, if the near module is enabled
+    #[cfg(feature = "evm")]
+    pub evm_account: Option<PrivateKeySigner>, // Anvil account funded with 100 ETH, if the evm module is enabled
+    #[cfg(feature = "bitcoin")]
+    pub btc_account: Option<UserInfo>, // Segwit account holding a 1 BTC UTXO, if the bitcoin module is enabled
+    #[cfg(feature = "mpc-utils")]
+    pub derived_accounts: DerivedAccounts, // Derived addresses of the lease paths, funded as the default ones
+    omni_box: Arc<OmniBox>,
+    _shared_access: Option<OwnedRwLockReadGuard<()>>,
+    _exclusive_access: Option<OwnedRwLockWriteGuard<()>>,
+}
+
+impl Deref for OmniBoxLease {
+    type Target = OmniBox;
+
+    fn deref(&self) -> &OmniBox {
+        &self.omni_box
+    }
+}
+
+impl OmniBox {
+    /// OmniBox shared by the whole process, started with the default options on first use
+    pub async fn shared() -> Arc<Self> {
+        Self::shared_with_conf(OmniBoxOptions::default()).await
+    }
+
+    /// OmniBox shared by the whole process, the options of the first call are used
+    ///
+    /// Later calls with different options get the OmniBox of the first call and log a warning.
+    pub async fn shared_with_conf(options: OmniBoxOptions) -> Arc<Self> {
+        Self::shared_entry(options)
+            .await
+            .expect("Failed to start the shared OmniBox")
+            .omni_box
+    }
+
+    /// Leases the shared OmniBox, started with the default options on first use
+    pub async fn lease(
+        isolation: LeaseIsolation,
+    ) -> Result<OmniBoxLease, Box<dyn Error + Send + Sync>> {
+        Self::lease_with_conf(OmniBoxOptions::default(), isolation).await
+    }
+
+    /// Leases the shared OmniBox, the options of the first call are used
+    ///
+    /// Later calls with different options get the OmniBox of the first call and log a warning.
+    pub async fn lease_with_conf(
+        options: OmniBoxOptions,
+        isolation: LeaseIsolation,
+    ) -> Result<OmniBoxLease, Box<dyn Error + Send + Sync>> {
+        let shared = Self::shared_entry(options).await?;
+        let (shared_access, exclusive_access) = match isolation {
+            LeaseIsolation::FreshAccounts => (Some(shared.access.clone().read_owned().await), None),
+            LeaseIsolation::Snapshot => (None, Some(shared.access.clone().write_owned().await)),
+        };
+
+        let id = NEXT_LEASE_ID.fetch_add(1, Ordering::SeqCst);
+        debug!(id, ?isolation, "Leasing the shared OmniBox");
+
+        // The node clients belong to the shared runtime, the calling test may run on another one
+        shared_runtime()
+            .spawn(async move {
+                Self::new_lease(&shared, id, shared_access, exclusive_access)
+                    .await
+                    .map_err(|err| err.to_string())
+            })
+            .await?
+            .map_err(Into::into)
+    }
+
+    /// Reverts the chains for an exclusive lease, then creates and funds the accounts of the lease
+    async fn new_lease(
+        shared: &SharedOmniBox,
+        id: u64,
+        shared_access: Option<OwnedRwLockReadGuard<()>>,
+        exclusive_access: Option<OwnedRwLockWriteGuard<()>>,
+    ) -> Result<OmniBoxLease, Box<dyn Error + Send + Sync>> {
+        let omni_box = shared.omni_box.clone();
+        if exclusive_access.is_some() {
+            omni_box.revert(shared.baseline).await?;
+        }
+
+        let near_account = match &omni_box.near_context {
+            Some(near) => Some(near.create_funded_account(LEASE_NEAR_BALANCE).await?),
+            None => None,
+        };
+        #[cfg(feature = "evm")]
+        let evm_account = match &omni_box.evm_context {
+            Some(evm) => Some(
+                evm.create_funded_account(parse_units("100.0", "ether")?.into())
+                    .await?,
+            ),
+            None => None,
+        };
+        #[cfg(feature = "bitcoin")]
+        let btc_account = match &omni_box.btc_context {
+            Some(btc) => Some(btc.create_funded_account(LEASE_BTC_BALANCE).await?),
+            None => None,
+        };
+
+        // Each lease signs with its own derivation paths
+        #[cfg(feature = "mpc-utils")]
+        let derived_accounts = DerivedAccounts::new(
+            omni_box.derived_accounts.predecessor_id.clone(),
+            &omni_box.modules(),
+            &format!("{}-lease-{}", omni_box.derived_accounts.btc_path(), id),
+            &format!("{}-lease-{}", omni_box.derived_accounts.evm_path(), id),
+            &format!("{}-lease-{}", omni_box.derived_accounts.near_path(), id),
+        )?;
+        #[cfg(feature = "mpc-utils")]
+        omni_box
+            .fund_derived(&derived_accounts, &shared.derived_funding)
+            .await?;
+
+        Ok(OmniBoxLease {
+            id,
+            near_account,
+            #[cfg(feature = "evm")]
+            evm_account,
+            #[cfg(feature = "bitcoin")]
+            btc_account,
+            #[cfg(feature = "mpc-utils")]
+            derived_accounts,
+            omni_box,
+            _shared_access: shared_access,
+            _exclusive_access: exclusive_access,
+        })
+    }
+
+    /// Stops the nodes of the shared OmniBox, also done automatically when the process exits
+    ///
+    /// Leases still alive keep the nodes running until they are dropped.
+    pub fn shutdown_shared() {
+        // The node clients are dropped within the runtime they were created on
+        let _runtime = RUNTIME.get().map(Runtime::enter);
+        if let Ok(mut shared) = SHARED.try_lock() {
+            if shared.take().is_some() {
+                info!("Shared OmniBox stopped");
+            }
+        }
+    }
+
+    async fn shared_entry(
+        options: OmniBoxOptions,
+    ) -> Result<SharedOmniBox, Box<dyn Error + Send + Sync>> {
+        let mut shared = SHARED.lock().await;
+        let requested_options = describe_options(&options);
+
+        if let Some(shared) = shared.as_ref() {
+            if *shared.options != requested_options {
+                warn!(
+                    started_with = %shared.options,
+                    requested = %requested_options,
+                    "The shared OmniBox is already running with other options, ignoring the new ones"
+                );
+            }
+        } else {
+            #[cfg(feature = "mpc-utils")]
+            let derived_funding = options.derived_funding.clone();
+
+            // Start on the shared runtime, tasks spawned by the nodes outlive the calling test
+            let (omni_box, baseline) = shared_runtime()
+                .spawn(async move {
+                    let omni_box = Self::new_with_conf(Some(options)).await;
+                    let baseline = omni_box.snapshot().await.map_err(|err| err.to_string())?;
+                    Ok::<_, String>((omni_box, baseline))
+                })
+                .await??;
+
+            info!(baseline, "Shared OmniBox started");
+
+            *shared = Some(SharedOmniBox {
+                omni_box: Arc::new(omni_box),
+                #[cfg(feature = "mpc-utils")]
+                derived_funding,
+                baseline,
+                access: Arc::new(RwLock::new(())),
+                options: requested_options.into(),
+            });
+        }
+
+        // Cloned so the lock is released before the lease waits for its access
+        Ok(shared.clone().unwrap())
+    }
+}
+
+/// Debug form of the options, with the overrides in a stable order
+fn describe_options(options: &OmniBoxOptions) -> String {
+    let mut overrides: Vec<String> = options
+        .overrides
+        .iter()
+        .map(|(network, overrides)| format!("{:?}: {:?}", network, overrides))
+        .collect();
+    overrides.sort();
+
+    let options = OmniBoxOptions {
+        overrides: HashMap::new(),
+        ..options.clone()
+    };
+    format!("{:?}, overrides: {:?}", options, overrides)
+}
+
+fn shared_runtime() -> &'static Runtime {
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("omni-box-shared")
+            .build()
+            .expect("Failed to build the shared OmniBox runtime")
+    })
+}
+
+/// Registered once when the library is loaded, runs when the process exits
+#[ctor::dtor]
+fn shutdown_on_exit() {
+    // Dropping the contexts kills bitcoind, Anvil and the NEAR sandbox, a panic must not unwind out of the destructor
+    let _ = std::panic::catch_unwind(OmniBox::shutdown_shared);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_config::ChainOverrides;
+    use crate::network::Network;
+
+    fn with_overrides(networks: &[Network]) -> OmniBoxOptions {
+        let mut options = OmniBoxOptions::default();
+        for network in networks {
+            options.overrides.insert(
+                network.clone(),
+                ChainOverrides {
+                    node_url: Some(format!("http://{:?}", network)),
+                },
+            );
+        }
+        options
+    }
+
+    #[test]
+    fn test_describe_options_ignores_the_order_of_the_overrides() {
+        let mut networks = vec![
+            Network::Near,
+            #[cfg(feature = "bitcoin")]
+            Network::Bitcoin,
+            #[cfg(feature = "evm")]
+            Network::EVM,
+        ];
+        let options = describe_options(&with_overrides(&networks));
+        networks.reverse();
+        assert_eq!(describe_options(&with_overrides(&networks)), options);
+        assert_ne!(
+            describe_options(&with_overrides(&[Network::Near])),
+            describe_options(&OmniBoxOptions {
+                seed: Some(7),
+                ..with_overrides(&[Network::Near])
+            })
+        );
+    }
+}
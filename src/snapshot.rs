@@ -0,0 +1,69 @@
+//! Coordinated snapshots of every chain run by an OmniBox
+use crate::chain_module::ModuleSnapshot;
+use std::collections::BTreeMap;
+
+/// Identifier of a snapshot taken through `OmniBox::snapshot`
+pub type SnapshotId = u64;
+
+/// State of each chain module captured by a snapshot
+#[derive(Debug, Clone)]
+pub struct OmniBoxSnapshot {
+    pub modules: BTreeMap<String, ModuleSnapshot>, // Snapshot of each module, by name
+}
+
+/// Snapshots taken so far, in the order they were taken
+#[derive(Debug)]
+pub struct SnapshotRegistry<T> {
+    next_id: SnapshotId,
+    snapshots: BTreeMap<SnapshotId, T>,
+}
+
+impl<T: Clone> SnapshotRegistry<T> {
+    pub const fn new() -> Self {
+        Self {
+            next_id: 0,
+            snapshots: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, snapshot: T) -> SnapshotId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.snapshots.insert(id, snapshot);
+        id
+    }
+
+    pub fn get(&self, id: SnapshotId) -> Option<T> {
+        self.snapshots.get(&id).cloned()
+    }
+
+    /// Replaces a snapshot and forgets every snapshot taken after it
+    ///
+    /// Reverting to a snapshot invalidates the later ones, as Anvil does for its own snapshots.
+    pub fn reset_to(&mut self, id: SnapshotId, snapshot: T) {
+        self.snapshots.retain(|other_id, _| *other_id < id);
+        self.snapshots.insert(id, snapshot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reset_to_discards_later_snapshots() {
+        let mut registry = SnapshotRegistry::new();
+        let first = registry.insert("first");
+        let second = registry.insert("second");
+        let third = registry.insert("third");
+
+        registry.reset_to(second, "second again");
+
+        assert_eq!(registry.get(first), Some("first"));
+        assert_eq!(registry.get(second), Some("second again"));
+        assert_eq!(registry.get(third), None);
+
+        // Ids are never reused
+        assert_eq!(registry.insert("fourth"), third + 1);
+    }
+}
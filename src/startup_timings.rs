@@ -0,0 +1,79 @@
+//! Time spent in each phase of the startup of an OmniBox
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Duration of each startup phase
+///
+/// The chain modules and the contract compilation run concurrently, `concurrent_phases` is their wall clock time.
+#[derive(Debug, Clone, Default)]
+pub struct StartupTimings {
+    pub modules: BTreeMap<String, Duration>, // Start each chain module and set up its accounts, by name
+    pub compile: Duration,                   // Load or compile the contract
+    pub concurrent_phases: Duration,         // Wall clock time of the phases above
+    pub funding: Duration, // Fund the derived addresses and the configured accounts
+    pub deploy: Duration,  // Deploy the contract and run its hooks
+    pub workspace_contracts: Duration, // Compile and deploy the additional contracts
+    pub total: Duration,
+}
+
+impl fmt::Display for StartupTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "OmniBox started in {:.2?}", self.total)?;
+        for (module, duration) in &self.modules {
+            writeln!(f, "  {:<22}{:>10.2?}", module, duration)?;
+        }
+        for (phase, duration) in [
+            ("contract compilation", self.compile),
+            ("concurrent phases", self.concurrent_phases),
+            ("funding", self.funding),
+            ("contract deploy", self.deploy),
+            ("workspace contracts", self.workspace_contracts),
+        ] {
+            writeln!(f, "  {:<22}{:>10.2?}", phase, duration)?;
+        }
+        Ok(())
+    }
+}
+
+/// Awaits a future and measures how long it took
+pub async fn timed<F: Future>(future: F) -> (F::Output, Duration) {
+    let started_at = Instant::now();
+    let output = future.await;
+    (output, started_at.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_timed_measures_the_future() {
+        let (output, duration) = timed(async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            7
+        })
+        .await;
+
+        assert_eq!(output, 7);
+        assert!(duration >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_display_lists_the_modules_and_phases() {
+        let timings = StartupTimings {
+            modules: BTreeMap::from([("bitcoin".to_string(), Duration::from_millis(1500))]),
+            compile: Duration::from_secs(2),
+            total: Duration::from_secs(3),
+            ..StartupTimings::default()
+        };
+
+        let report = timings.to_string();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "OmniBox started in 3.00s");
+        assert!(lines[1].starts_with("  bitcoin") && lines[1].ends_with("1.50s"));
+        assert!(lines[2].starts_with("  contract compilation") && lines[2].ends_with("2.00s"));
+        assert_eq!(lines.len(), 7);
+    }
+}
@@ -0,0 +1,132 @@
+//! Runtime support of the `#[omni_box::test]` attribute
+#[cfg(feature = "bitcoin")]
+use crate::chain_modules::BitcoinModule;
+#[cfg(feature = "evm")]
+use crate::chain_modules::EvmModule;
+use crate::chain_modules::NearModule;
+use crate::omni_box::OmniBox;
+use crate::omni_box_options::OmniBoxOptions;
+use std::fmt::{Debug, Write as _};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use tracing::error;
+#[cfg(feature = "logs")]
+use tracing_subscriber::EnvFilter;
+
+/// Value returned by a test function
+pub trait TestOutcome {
+    fn into_result(self) -> Result<(), String>;
+}
+
+impl TestOutcome for () {
+    fn into_result(self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl<E: Debug> TestOutcome for Result<(), E> {
+    fn into_result(self) -> Result<(), String> {
+        self.map_err(|err| format!("{:?}", err))
+    }
+}
+
+/// Starts an OmniBox, runs the test against it and stops the nodes
+///
+/// When the test fails the state of the chains is logged at error level, shown by the default
+/// filter, before the failure is reported.
+pub fn run<F, Fut>(name: &str, options: OmniBoxOptions, test: F)
+where
+    F: FnOnce(Arc<OmniBox>) -> Fut,
+    Fut: Future,
+    Fut::Output: TestOutcome,
+{
+    // Only errors are logged unless RUST_LOG is set, a subscriber installed by the test binary wins
+    #[cfg(feature = "logs")]
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_test_writer()
+        .try_init();
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build the test runtime");
+
+    let omni_box = match panic::catch_unwind(AssertUnwindSafe(|| {
+        runtime.block_on(OmniBox::new_with_conf(Some(options)))
+    })) {
+        Ok(omni_box) => Arc::new(omni_box),
+        Err(panic) => {
+            error!(
+                test = name,
+                "OmniBox failed to start, check that bitcoind, anvil and near-sandbox are installed"
+            );
+            panic::resume_unwind(panic);
+        }
+    };
+
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        runtime.block_on(test(omni_box.clone())).into_result()
+    }));
+
+    if !matches!(outcome, Ok(Ok(()))) {
+        error!("{}", runtime.block_on(diagnostics(name, &omni_box)));
+    }
+
+    // Stop the nodes before reporting the result
+    drop(omni_box);
+    drop(runtime);
+
+    match outcome {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => panic!("Test `{}` failed: {}", name, err),
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
+
+/// Endpoints, heights and accounts of the chains, printed when a test fails
+async fn diagnostics(name: &str, omni_box: &OmniBox) -> String {
+    let mut report = format!("OmniBox state after the failure of `{}`:\n", name);
+
+    for (name, module) in &omni_box.chain_modules {
+        let endpoint = match name.as_str() {
+            #[cfg(feature = "bitcoin")]
+            BitcoinModule::NAME => omni_box
+                .btc_context()
+                .map(|btc| btc.bitcoind_instance.rpc_url()),
+            #[cfg(feature = "evm")]
+            EvmModule::NAME => omni_box.evm_context().map(|evm| evm.anvil.endpoint()),
+            NearModule::NAME => omni_box.near_context().map(|near| near.client.rpc_addr()),
+            _ => None,
+        }
+        .unwrap_or_else(|| module.manifest().to_string());
+        let height = module.height().await;
+        let _ = writeln!(report, "  {}: {} at height {:?}", name, endpoint, height);
+    }
+    let _ = writeln!(
+        report,
+        "  Deployer: {}",
+        omni_box.deployer_account.account_id
+    );
+
+    for contract in omni_box.contracts.values() {
+        let _ = writeln!(
+            report,
+            "  Contract {}: {} ({})",
+            contract.name,
+            contract.account_id(),
+            contract.code_hash
+        );
+    }
+    #[cfg(all(feature = "mpc-utils", feature = "evm"))]
+    if let Some(evm) = omni_box.derived_accounts.evm() {
+        let _ = writeln!(report, "  EVM derived address: {}", evm.address);
+    }
+    #[cfg(all(feature = "mpc-utils", feature = "bitcoin"))]
+    if let Some(segwit) = omni_box.derived_accounts.btc_segwit() {
+        let _ = writeln!(report, "  Segwit BTC derived address: {}", segwit.address);
+    }
+
+    report
+}
@@ -0,0 +1,171 @@
+//! Regression harness for contract upgrades
+//!
+//! Deploys version A of a contract to a fresh sandbox account, seeds its state, upgrades it to
+//! version B, runs the migration and hands the state before and after the upgrade to the assertions.
+use crate::contract_config::{deploy_to_sandbox, ContractCall, ContractSource, DeployHook};
+use near_primitives::hash::CryptoHash;
+use near_workspaces::network::Sandbox;
+use near_workspaces::{Contract, Worker};
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+
+/// Raw contract state, as returned by `view_state`
+pub type ContractState = HashMap<Vec<u8>, Vec<u8>>;
+
+/// State of the contract right before and right after the upgrade
+#[derive(Debug, Clone)]
+pub struct UpgradeStates {
+    pub before: ContractState,
+    pub after: ContractState,
+}
+
+impl UpgradeStates {
+    /// Keys present before the upgrade that are missing afterwards
+    pub fn removed_keys(&self) -> Vec<&Vec<u8>> {
+        self.before
+            .keys()
+            .filter(|key| !self.after.contains_key(*key))
+            .collect()
+    }
+
+    /// Keys present before and after the upgrade whose value changed
+    pub fn changed_keys(&self) -> Vec<&Vec<u8>> {
+        self.before
+            .iter()
+            .filter(|(key, value)| {
+                self.after
+                    .get(*key)
+                    .is_some_and(|after_value| after_value != *value)
+            })
+            .map(|(key, _)| key)
+            .collect()
+    }
+}
+
+/// Summary of a successful upgrade test
+#[derive(Debug, Clone)]
+pub struct UpgradeReport {
+    pub from_code_hash: CryptoHash,
+    pub to_code_hash: CryptoHash,
+    pub states: UpgradeStates,
+}
+
+/// Upgrade test from one version of a contract to another
+#[derive(Debug, Clone)]
+pub struct UpgradeTest {
+    pub from: ContractSource,
+    pub to: ContractSource,
+    pub init: Option<ContractCall>, // Called after deploying version A
+    pub migrate: Option<ContractCall>, // Called after deploying version B
+}
+
+impl UpgradeTest {
+    pub fn new(from: impl Into<ContractSource>, to: impl Into<ContractSource>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            init: None,
+            migrate: None,
+        }
+    }
+
+    pub fn with_init(mut self, init: ContractCall) -> Self {
+        self.init = Some(init);
+        self
+    }
+
+    pub fn with_migrate(mut self, migrate: ContractCall) -> Self {
+        self.migrate = Some(migrate);
+        self
+    }
+
+    /// Runs the upgrade in the given sandbox
+    ///
+    /// `seed` populates the state of version A, `assert` checks version B and the preserved state
+    pub async fn run<S, SF, A, AF>(
+        self,
+        worker: &Worker<Sandbox>,
+        seed: S,
+        assert: A,
+    ) -> Result<UpgradeReport, Box<dyn Error>>
+    where
+        S: FnOnce(Contract) -> SF,
+        SF: Future<Output = Result<(), Box<dyn Error>>>,
+        A: FnOnce(Contract, UpgradeStates) -> AF,
+        AF: Future<Output = Result<(), Box<dyn Error>>>,
+    {
+        let account = worker.dev_create_account().await?;
+
+        // Version A
+        let from_wasm = self.from.load().await?;
+        let from_code_hash = CryptoHash::hash_bytes(&from_wasm);
+        let contract = deploy_to_sandbox(&account, &from_wasm).await?;
+        if let Some(hook) = DeployHook::after_deploy(None, from_code_hash, self.init.as_ref(), None)
+        {
+            hook.call_sandbox(&contract).await?;
+        }
+
+        seed(contract.clone()).await?;
+        let before = contract.view_state().await?;
+
+        // Version B, migrated only when its code differs from version A
+        let to_wasm = self.to.load().await?;
+        let to_code_hash = CryptoHash::hash_bytes(&to_wasm);
+        let contract = deploy_to_sandbox(&account, &to_wasm).await?;
+        if let Some(hook) = DeployHook::after_deploy(
+            Some(from_code_hash),
+            to_code_hash,
+            None,
+            self.migrate.as_ref(),
+        ) {
+            hook.call_sandbox(&contract).await?;
+        }
+
+        let after = contract.view_state().await?;
+        let states = UpgradeStates { before, after };
+
+        assert(contract, states.clone()).await?;
+
+        Ok(UpgradeReport {
+            from_code_hash,
+            to_code_hash,
+            states,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(entries: &[(&[u8], &[u8])]) -> ContractState {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_removed_and_changed_keys() {
+        let states = UpgradeStates {
+            before: state(&[(b"kept", b"1"), (b"changed", b"1"), (b"removed", b"1")]),
+            after: state(&[(b"kept", b"1"), (b"changed", b"2"), (b"added", b"1")]),
+        };
+
+        assert_eq!(states.removed_keys(), vec![&b"removed".to_vec()]);
+        assert_eq!(states.changed_keys(), vec![&b"changed".to_vec()]);
+    }
+
+    #[test]
+    fn test_unchanged_state_has_no_removed_or_changed_keys() {
+        let before = state(&[(b"a", b"1"), (b"b", b"2")]);
+        let states = UpgradeStates {
+            after: before.clone(),
+            before,
+        };
+
+        assert!(states.removed_keys().is_empty());
+        assert!(states.changed_keys().is_empty());
+    }
+}
@@ -64,6 +64,7 @@ const ROOT_PUBLIC_KEY: &str = "secp256k1:4NfTiv3UsGahebgTaHyD9vF8KYKMBnfd6kh94mK
 
 /// Contains the derived address as string and the public key
 /// that was used to derive the address
+#[derive(Debug, Clone)]
 pub struct DerivedAddress {
     pub address: String,
     pub public_key: PublicKey,
@@ -73,7 +74,9 @@ pub struct DerivedAddress {
 ///
 /// Example:
 /// ```
-/// let derived_address = get_derived_address_for_segwit("omnitester.testnet".parse().unwrap(), "bitcoin-1")
+/// use omni_box::utils::address::get_derived_address_for_segwit;
+///
+/// let derived_address = get_derived_address_for_segwit(&"omnitester.testnet".parse().unwrap(), "bitcoin-1");
 /// ```
 pub fn get_derived_address_for_segwit(predecessor_id: &AccountId, path: &str) -> DerivedAddress {
     let epsilon = derive_epsilon(predecessor_id, path);
@@ -90,7 +93,9 @@ pub fn get_derived_address_for_segwit(predecessor_id: &AccountId, path: &str) ->
 ///
 /// Example:
 /// ```
-/// let derived_address = get_derived_address_for_btc_legacy("omnitester.testnet".parse().unwrap(), "bitcoin-1")
+/// use omni_box::utils::address::get_derived_address_for_btc_legacy;
+///
+/// let derived_address = get_derived_address_for_btc_legacy(&"omnitester.testnet".parse().unwrap(), "bitcoin-1");
 /// ```
 pub fn get_derived_address_for_btc_legacy(
     predecessor_id: &AccountId,
@@ -111,7 +116,10 @@ pub fn get_derived_address_for_btc_legacy(
 ///
 /// Example:
 /// ```
-/// let derived_address = get_derived_address_for_evm("omnitester.testnet".parse().unwrap(), "ethereum-1")
+/// use omni_box::utils::address::get_derived_address_for_evm;
+///
+/// let derived_address = get_derived_address_for_evm(&"omnitester.testnet".parse().unwrap(), "ethereum-1");
+/// assert!(derived_address.address.starts_with("0x"));
 /// ```
 pub fn get_derived_address_for_evm(predecessor_id: &AccountId, path: &str) -> DerivedAddress {
     let epsilon = derive_epsilon(predecessor_id, path);
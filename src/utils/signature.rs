@@ -2,20 +2,59 @@
 use bitcoin::secp256k1::ecdsa::Signature;
 use bitcoin::secp256k1::{self};
 use hex::FromHex;
+#[cfg(feature = "near-sandbox")]
 use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
-use near_primitives::views::{ExecutionStatusView, FinalExecutionStatus};
+use near_primitives::views::{
+    ExecutionStatusView, FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum,
+    FinalExecutionStatus,
+};
+
+/// Result of a transaction holding its final execution outcome, read by the helpers below
+///
+/// Implemented for the outcome views of `near-primitives`, and for the JSON RPC
+/// `RpcTransactionResponse` with the `near-sandbox` feature. An outcome with receipts has no
+/// final outcome for these helpers.
+pub trait FinalOutcome {
+    fn final_outcome(&self) -> Option<&FinalExecutionOutcomeView>;
+}
+
+impl FinalOutcome for FinalExecutionOutcomeView {
+    fn final_outcome(&self) -> Option<&FinalExecutionOutcomeView> {
+        Some(self)
+    }
+}
+
+impl FinalOutcome for FinalExecutionOutcomeViewEnum {
+    fn final_outcome(&self) -> Option<&FinalExecutionOutcomeView> {
+        match self {
+            Self::FinalExecutionOutcome(outcome) => Some(outcome),
+            // The helpers only read outcomes returned without receipts
+            Self::FinalExecutionOutcomeWithReceipt(_) => None,
+        }
+    }
+}
+
+#[cfg(feature = "near-sandbox")]
+impl FinalOutcome for RpcTransactionResponse {
+    fn final_outcome(&self) -> Option<&FinalExecutionOutcomeView> {
+        self.final_execution_outcome
+            .as_ref()
+            .and_then(FinalOutcome::final_outcome)
+    }
+}
 
 /// Utility function to extract the big_r and s values from a transaction response
 ///
 /// Example:
 /// ```
-/// let (big_r, s) = extract_big_r_and_s(&response).unwrap();
+/// # use omni_box::utils::signature::{extract_big_r_and_s, FinalOutcome};
+/// # fn example(response: &impl FinalOutcome) -> Result<(), String> {
+/// let (big_r, s) = extract_big_r_and_s(response)?;
+/// # Ok(())
+/// # }
 /// ```
-pub fn extract_big_r_and_s(response: &RpcTransactionResponse) -> Result<(String, String), String> {
-    if let Some(near_primitives::views::FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(
-        final_outcome,
-    )) = &response.final_execution_outcome
-    {
+pub fn extract_big_r_and_s(response: &impl FinalOutcome) -> Result<(String, String), String> {
+    if let Some(final_outcome) = response.final_outcome() {
         if let FinalExecutionStatus::SuccessValue(success_value) = &final_outcome.status {
             let success_value_str =
                 String::from_utf8(success_value.clone()).map_err(|e| e.to_string())?;
@@ -38,7 +77,12 @@ pub fn extract_big_r_and_s(response: &RpcTransactionResponse) -> Result<(String,
 ///
 /// Example:
 /// ```
-/// let signature = create_signature(&big_r, &s).unwrap();
+/// # use omni_box::utils::signature::{create_signature, extract_big_r_and_s, FinalOutcome};
+/// # fn example(response: &impl FinalOutcome) -> Result<(), String> {
+/// let (big_r, s) = extract_big_r_and_s(response)?;
+/// let signature = create_signature(&big_r, &s).map_err(|err| err.to_string())?;
+/// # Ok(())
+/// # }
 /// ```
 pub fn create_signature(big_r_hex: &str, s_hex: &str) -> Result<Signature, secp256k1::Error> {
     // Convert hex strings to byte arrays
@@ -68,17 +112,18 @@ pub fn create_signature(big_r_hex: &str, s_hex: &str) -> Result<Signature, secp2
 ///
 /// Example:
 /// ```
-/// let signatures = extract_multiple_signatures(&response).unwrap();
+/// # use omni_box::utils::signature::{extract_multiple_signatures, FinalOutcome};
+/// # fn example(response: &impl FinalOutcome) -> Result<(), String> {
+/// let signatures = extract_multiple_signatures(response)?;
+/// # Ok(())
+/// # }
 /// ```
 pub fn extract_multiple_signatures(
-    response: &RpcTransactionResponse,
+    response: &impl FinalOutcome,
 ) -> Result<Vec<(String, String)>, String> {
     let mut signatures = Vec::new();
 
-    if let Some(near_primitives::views::FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(
-        final_outcome,
-    )) = &response.final_execution_outcome
-    {
+    if let Some(final_outcome) = response.final_outcome() {
         for receipt in &final_outcome.receipts_outcome {
             if let ExecutionStatusView::SuccessValue(success_value) = &receipt.outcome.status {
                 if let Ok(success_value_str) = String::from_utf8(success_value.clone()) {
@@ -107,13 +152,14 @@ pub fn extract_multiple_signatures(
 ///
 /// Example:
 /// ```
-/// let signed_transaction = extract_signed_transaction(&response).unwrap();
+/// # use omni_box::utils::signature::{extract_signed_transaction, FinalOutcome};
+/// # fn example(response: &impl FinalOutcome) -> Result<(), String> {
+/// let signed_transaction = extract_signed_transaction(response)?;
+/// # Ok(())
+/// # }
 /// ```
-pub fn extract_signed_transaction(response: &RpcTransactionResponse) -> Result<Vec<u8>, String> {
-    if let Some(near_primitives::views::FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(
-        final_outcome,
-    )) = &response.final_execution_outcome
-    {
+pub fn extract_signed_transaction(response: &impl FinalOutcome) -> Result<Vec<u8>, String> {
+    if let Some(final_outcome) = response.final_outcome() {
         if let FinalExecutionStatus::SuccessValue(success_value) = &final_outcome.status {
             // Convert the success value to a string
             let success_value_str =
@@ -135,13 +181,14 @@ pub fn extract_signed_transaction(response: &RpcTransactionResponse) -> Result<V
 ///
 /// Example:
 /// ```
-/// let payload = extract_payload(&response).unwrap();
+/// # use omni_box::utils::signature::{extract_payload, FinalOutcome};
+/// # fn example(response: &impl FinalOutcome) -> Result<(), String> {
+/// let payload = extract_payload(response)?;
+/// # Ok(())
+/// # }
 /// ```
-pub fn extract_payload(response: &RpcTransactionResponse) -> Result<[u8; 32], String> {
-    if let Some(near_primitives::views::FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(
-        final_outcome,
-    )) = &response.final_execution_outcome
-    {
+pub fn extract_payload(response: &impl FinalOutcome) -> Result<[u8; 32], String> {
+    if let Some(final_outcome) = response.final_outcome() {
         if let FinalExecutionStatus::SuccessValue(success_value) = &final_outcome.status {
             // Convert the success value to a string
             let success_value_str =
@@ -0,0 +1,486 @@
+//! Warm start templates of the node data
+//!
+//! The first run of a configuration saves the bitcoind data directory, with mature coins in the
+//! node wallet, the Anvil state and the sandbox accounts of the contracts deployed to NEAR dev
+//! accounts. Later runs start from copies of them instead of genesis.
+//!
+//! Templates are stored in a directory named after a hash of the configuration and of the
+//! versions of the node binaries. Sandbox
+//! contracts are only restored while their code hash is unchanged, and are saved again otherwise.
+#[cfg(feature = "bitcoin")]
+use crate::clients::get_bitcoin_instance;
+#[cfg(feature = "evm")]
+use crate::contexts::EVMTestContext;
+use crate::contexts::NearTestContext;
+use crate::contract_config::DeployedContract;
+use crate::omni_box_options::OmniBoxOptions;
+use crate::prerequisites::{self, BinaryPaths};
+#[cfg(feature = "evm")]
+use alloy::primitives::Bytes;
+#[cfg(feature = "bitcoin")]
+use bitcoin::Network;
+#[cfg(feature = "bitcoin")]
+use bitcoind::{AddressType, BitcoinD};
+use near_primitives::hash::CryptoHash;
+use near_sdk::AccountId;
+use near_workspaces::network::Sandbox;
+use near_workspaces::types::{AccessKey, AccountDetailsPatch, NearToken, SecretKey};
+use near_workspaces::{Contract, Worker};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "bitcoin")]
+use tempfile::TempDir;
+use tracing::{debug, info};
+
+pub const DEFAULT_TEMPLATES_PATH: &str = "cache/templates";
+
+/// Bumped when the layout of the templates changes, invalidating the saved ones
+const TEMPLATE_VERSION: u32 = 3;
+
+#[cfg(feature = "bitcoin")]
+const BITCOIN_TEMPLATE: &str = "bitcoin";
+#[cfg(feature = "evm")]
+const EVM_TEMPLATE: &str = "anvil-state.hex";
+const NEAR_TEMPLATE: &str = "near-contracts.json";
+
+/// Sandbox account holding a contract deployed to a NEAR dev account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NearContractTemplate {
+    pub account_id: AccountId,
+    pub secret_key: String,
+    pub code_hash: CryptoHash,
+    pub balance: NearToken,
+    pub storage_usage: u64,
+    pub state: Vec<(String, String)>, // Hex encoded storage keys and values
+}
+
+/// bitcoind data directory prepared from the template of a configuration
+#[cfg(feature = "bitcoin")]
+#[derive(Debug, Clone)]
+pub struct BitcoinTemplate {
+    template: PathBuf,
+    datadir: PathBuf,
+    restored: bool, // The data directory is a copy of the template
+}
+
+#[cfg(feature = "bitcoin")]
+impl BitcoinTemplate {
+    /// Starts bitcoind from the template, saving the template first when it is missing
+    ///
+    /// Saving mines mature coins to the node wallet, then stops the node to flush its data.
+    pub fn start(&self, binaries: &BinaryPaths) -> Result<BitcoinD, Box<dyn Error + Send + Sync>> {
+        let mut bitcoind = get_bitcoin_instance(binaries, Some(&self.datadir))?;
+        if self.restored || self.template.exists() {
+            return Ok(bitcoind);
+        }
+
+        // Later runs then fund accounts without waiting for coinbase maturity
+        let address = bitcoind
+            .client
+            .get_new_address_with_type(AddressType::Bech32)?
+            .address()?
+            .require_network(Network::Regtest)?;
+        bitcoind.client.generate_to_address(101, &address)?;
+        bitcoind
+            .stop()
+            .map_err(|err| format!("Failed to stop bitcoind: {}", err))?;
+
+        // Another process building the same template may win the rename
+        let parent = self.template.parent().ok_or("Invalid template path")?;
+        fs::create_dir_all(parent)?;
+        let building = self.template.with_extension(std::process::id().to_string());
+        copy_dir(&self.datadir, &building)?;
+        if fs::rename(&building, &self.template).is_err() {
+            fs::remove_dir_all(&building).ok();
+        }
+        info!(path = %self.template.display(), "Saved the bitcoind template");
+
+        get_bitcoin_instance(binaries, Some(&self.datadir))
+    }
+}
+
+#[derive(Debug)]
+pub struct WarmStart {
+    dir: PathBuf,
+    #[cfg(feature = "bitcoin")]
+    bitcoin_datadir: TempDir, // Copy of the bitcoind template the node runs from
+    #[cfg(feature = "bitcoin")]
+    bitcoin_restored: bool,
+    near_contracts: BTreeMap<String, NearContractTemplate>,
+}
+
+impl WarmStart {
+    /// Prepares the bitcoind data directory from the template of the configuration, if saved
+    pub fn new(
+        root: &str,
+        options: &OmniBoxOptions,
+        deployer: &AccountId,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let dir = Path::new(root).join(config_hash(
+            options,
+            deployer,
+            &binary_versions(&options.binaries),
+        ));
+
+        #[cfg(feature = "bitcoin")]
+        let bitcoin_datadir = tempfile::Builder::new()
+            .prefix("omni-box-bitcoind-")
+            .tempdir()?;
+        #[cfg(feature = "bitcoin")]
+        let bitcoin_restored = {
+            let bitcoin_template = dir.join(BITCOIN_TEMPLATE);
+            let restored = bitcoin_template.is_dir();
+            if restored {
+                copy_dir(&bitcoin_template, bitcoin_datadir.path())?;
+            }
+            restored
+        };
+
+        let near_contracts = fs::read_to_string(dir.join(NEAR_TEMPLATE))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        info!(dir = %dir.display(), "Using warm start templates");
+
+        Ok(Self {
+            dir,
+            #[cfg(feature = "bitcoin")]
+            bitcoin_datadir,
+            #[cfg(feature = "bitcoin")]
+            bitcoin_restored,
+            near_contracts,
+        })
+    }
+
+    #[cfg(feature = "bitcoin")]
+    pub fn bitcoin_template(&self) -> BitcoinTemplate {
+        BitcoinTemplate {
+            template: self.dir.join(BITCOIN_TEMPLATE),
+            datadir: self.bitcoin_datadir.path().to_path_buf(),
+            restored: self.bitcoin_restored,
+        }
+    }
+
+    /// Path of the saved Anvil state
+    #[cfg(feature = "evm")]
+    pub fn evm_template(&self) -> PathBuf {
+        self.dir.join(EVM_TEMPLATE)
+    }
+
+    /// Patches the sandbox account of a contract into the sandbox, if saved with the same code
+    pub async fn restore_near_contract(
+        &self,
+        near: &NearTestContext,
+        name: &str,
+        wasm: &[u8],
+    ) -> Result<Option<Contract>, Box<dyn Error + Send + Sync>> {
+        let code_hash = CryptoHash::hash_bytes(wasm);
+        let template = match self.near_contracts.get(name) {
+            Some(template) if template.code_hash == code_hash => template,
+            _ => return Ok(None),
+        };
+
+        let secret_key: SecretKey = template.secret_key.parse()?;
+        let state = template
+            .state
+            .iter()
+            .map(|(key, value)| Ok((hex::decode(key)?, hex::decode(value)?)))
+            .collect::<Result<Vec<_>, hex::FromHexError>>()?;
+
+        near.client
+            .patch(&template.account_id)
+            .account(
+                AccountDetailsPatch::default()
+                    .balance(template.balance)
+                    .code_hash(near_workspaces::types::CryptoHash(code_hash.0))
+                    .storage_usage(template.storage_usage),
+            )
+            .access_key(secret_key.public_key(), AccessKey::full_access())
+            .code(wasm)
+            .states(
+                state
+                    .iter()
+                    .map(|(key, value)| (key.as_slice(), value.as_slice())),
+            )
+            .transact()
+            .await?;
+        near.register_restored_account(template.account_id.clone());
+        debug!(name, account_id = %template.account_id, "Restored the sandbox contract");
+
+        Ok(Some(Contract::from_secret_key(
+            template.account_id.clone(),
+            secret_key,
+            &near.client,
+        )))
+    }
+
+    /// Saves the Anvil template, if missing for this configuration
+    #[cfg(feature = "evm")]
+    pub async fn save_evm(&self, evm: &EVMTestContext) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let evm_template = self.dir.join(EVM_TEMPLATE);
+        if evm_template.exists() {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        write_atomically(&evm_template, &hex::encode(evm.dump_state().await?))?;
+        info!(path = %evm_template.display(), "Saved the Anvil template");
+
+        Ok(())
+    }
+
+    /// Saves the sandbox accounts of the dev account contracts missing from the template
+    pub async fn save_near(
+        &mut self,
+        near: &Worker<Sandbox>,
+        contracts: &HashMap<&'static str, DeployedContract>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut changed = false;
+        for (name, contract) in contracts {
+            let Some(sandbox_contract) = &contract.sandbox_contract else {
+                continue;
+            };
+            let up_to_date = self
+                .near_contracts
+                .get(*name)
+                .is_some_and(|template| template.code_hash == contract.code_hash);
+            if up_to_date {
+                continue;
+            }
+
+            let account_id = sandbox_contract.id();
+            let details = near.view_account(account_id).await?;
+            let state = near
+                .view_state(account_id)
+                .await?
+                .into_iter()
+                .map(|(key, value)| (hex::encode(key), hex::encode(value)))
+                .collect();
+
+            self.near_contracts.insert(
+                name.to_string(),
+                NearContractTemplate {
+                    account_id: account_id.clone(),
+                    secret_key: sandbox_contract.as_account().secret_key().to_string(),
+                    code_hash: contract.code_hash,
+                    balance: details.balance,
+                    storage_usage: details.storage_usage,
+                    state,
+                },
+            );
+            changed = true;
+        }
+        if changed {
+            fs::create_dir_all(&self.dir)?;
+            let near_template = self.dir.join(NEAR_TEMPLATE);
+            write_atomically(
+                &near_template,
+                &serde_json::to_string_pretty(&self.near_contracts)?,
+            )?;
+            info!(path = %near_template.display(), "Saved the sandbox contracts template");
+        }
+
+        Ok(())
+    }
+}
+
+/// Loads the Anvil state saved at `path`, returns whether there was one
+#[cfg(feature = "evm")]
+pub async fn restore_evm_state(
+    path: &Path,
+    evm: &EVMTestContext,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let Ok(state) = fs::read_to_string(path) else {
+        return Ok(false);
+    };
+
+    evm.load_state(Bytes::from(hex::decode(state.trim())?))
+        .await?;
+    debug!("Restored the Anvil state");
+
+    Ok(true)
+}
+
+/// Versions of the node binaries, a node may not read the data saved by another version
+fn binary_versions(binaries: &BinaryPaths) -> String {
+    prerequisites::doctor(binaries)
+        .checks
+        .iter()
+        .map(|check| {
+            format!(
+                "{}={}",
+                check.prerequisite.name(),
+                check.version.as_deref().unwrap_or("none")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Hash of the options and binary versions shaping the state saved in the templates
+fn config_hash(options: &OmniBoxOptions, deployer: &AccountId, binary_versions: &str) -> String {
+    let config = format!(
+        "{}|{:?}|{:?}|{}|{:?}|{:?}|{:?}|{}",
+        TEMPLATE_VERSION,
+        options.modules,
+        options.seed,
+        deployer,
+        options.funding,
+        options.contracts,
+        options.binaries,
+        binary_versions,
+    );
+    #[cfg(feature = "mpc-utils")]
+    let config = format!(
+        "{}|{}|{}|{}|{:?}",
+        config, options.btc_path, options.evm_path, options.near_path, options.derived_funding
+    );
+
+    hex::encode(Sha3_256::digest(config.as_bytes()))[..16].to_string()
+}
+
+#[cfg(feature = "bitcoin")]
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes through a temporary file, so concurrent runs never read a partial template
+fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    let partial = path.with_extension(format!("{}.partial", std::process::id()));
+    fs::write(&partial, contents)?;
+    fs::rename(partial, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "evm")]
+    use crate::contexts::ChainContext;
+    use crate::NearAccount;
+
+    #[test]
+    fn test_config_hash() {
+        let deployer: AccountId = "omni.testnet".parse().unwrap();
+        let versions = "bitcoind=28.0.0,anvil=1.0.0,near-sandbox=none";
+        let options = OmniBoxOptions::default();
+        let seeded = OmniBoxOptions {
+            seed: Some(1),
+            ..OmniBoxOptions::default()
+        };
+
+        assert_eq!(
+            config_hash(&options, &deployer, versions),
+            config_hash(&OmniBoxOptions::default(), &deployer, versions)
+        );
+        assert_ne!(
+            config_hash(&options, &deployer, versions),
+            config_hash(&seeded, &deployer, versions)
+        );
+        assert_ne!(
+            config_hash(&options, &deployer, versions),
+            config_hash(
+                &options,
+                &deployer,
+                "bitcoind=27.1.0,anvil=1.0.0,near-sandbox=none"
+            )
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs near-sandbox"]
+    async fn test_near_contract_template_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        let deployer: AccountId = "omni.testnet".parse().unwrap();
+        let options = OmniBoxOptions::default();
+        let wasm = b"\0asm\x01\0\0\0";
+
+        let near = NearTestContext::new().await;
+        let account = near.create_account().await;
+        near.client
+            .patch(account.id())
+            .code(wasm)
+            .state(b"counter", b"1")
+            .transact()
+            .await
+            .unwrap();
+        let private_key: near_crypto::SecretKey = account.secret_key().to_string().parse().unwrap();
+        let contract = DeployedContract {
+            name: "counter",
+            account: NearAccount {
+                account_id: account.id().clone(),
+                public_key: private_key.public_key(),
+                private_key,
+            },
+            code_hash: CryptoHash::hash_bytes(wasm),
+            sandbox_contract: Some(Contract::from_secret_key(
+                account.id().clone(),
+                account.secret_key().clone(),
+                &near.client,
+            )),
+        };
+        let mut warm_start = WarmStart::new(root, &options, &deployer).unwrap();
+        warm_start
+            .save_near(&near.client, &HashMap::from([("counter", contract)]))
+            .await
+            .unwrap();
+
+        // A fresh sandbox starts from the template, unless the code changed
+        let near = NearTestContext::new().await;
+        let warm_start = WarmStart::new(root, &options, &deployer).unwrap();
+        assert!(warm_start
+            .restore_near_contract(&near, "counter", b"\0asm\x01\0\0\0\0")
+            .await
+            .unwrap()
+            .is_none());
+        let restored = warm_start
+            .restore_near_contract(&near, "counter", wasm)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(restored.id(), account.id());
+        let state = near.client.view_state(restored.id()).await.unwrap();
+        assert_eq!(state.get(b"counter".as_slice()), Some(&b"1".to_vec()));
+    }
+
+    #[cfg(feature = "evm")]
+    #[tokio::test]
+    #[ignore = "needs anvil"]
+    async fn test_evm_template_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        let deployer: AccountId = "omni.testnet".parse().unwrap();
+        let options = OmniBoxOptions::default();
+        let balance = alloy::primitives::U256::from(1_234_567);
+
+        let evm = EVMTestContext::default();
+        let account = evm.create_funded_account(balance).await.unwrap();
+        let warm_start = WarmStart::new(root, &options, &deployer).unwrap();
+        assert!(!restore_evm_state(&warm_start.evm_template(), &evm)
+            .await
+            .unwrap());
+        warm_start.save_evm(&evm).await.unwrap();
+
+        let evm = EVMTestContext::default();
+        let warm_start = WarmStart::new(root, &options, &deployer).unwrap();
+        assert!(restore_evm_state(&warm_start.evm_template(), &evm)
+            .await
+            .unwrap());
+        assert_eq!(evm.balance(&account.address()).await.unwrap(), balance);
+    }
+}
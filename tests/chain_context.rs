@@ -0,0 +1,52 @@
+//! Operations of the `ChainContext` implementations, each test needs the binary of its node
+use alloy::primitives::U256;
+use bitcoin::Amount;
+use near_workspaces::types::NearToken;
+use omni_box::{BTCTestContext, ChainContext, EVMTestContext, NearTestContext};
+use std::fmt::Debug;
+
+/// Creates a funded account, mines on top of it, then reverts the blocks mined since
+async fn assert_chain_context<C>(context: &C, balance: C::Balance)
+where
+    C: ChainContext + Sync,
+    C::Account: Send,
+    C::Address: Send + Sync,
+    C::Balance: Clone + Debug + PartialEq + Send,
+    C::SnapshotId: Send,
+{
+    let account = context
+        .create_funded_account(balance.clone())
+        .await
+        .unwrap();
+    let address = context.account_address(&account);
+    assert_eq!(context.balance(&address).await.unwrap(), balance);
+
+    let snapshot = context.snapshot().await.unwrap();
+    let height = context.height().await.unwrap();
+    context.advance_blocks(3).await.unwrap();
+    assert!(context.height().await.unwrap() >= height + 3);
+
+    context.revert(snapshot).await.unwrap();
+    assert_eq!(context.balance(&address).await.unwrap(), balance);
+}
+
+#[tokio::test]
+#[ignore = "needs bitcoind"]
+async fn test_btc_chain_context() {
+    let btc = BTCTestContext::default();
+    assert_chain_context(&btc, Amount::from_sat(50_000)).await;
+}
+
+#[tokio::test]
+#[ignore = "needs anvil"]
+async fn test_evm_chain_context() {
+    let evm = EVMTestContext::default();
+    assert_chain_context(&evm, U256::from(1_000_000_000u64)).await;
+}
+
+#[tokio::test]
+#[ignore = "needs near-sandbox"]
+async fn test_near_chain_context() {
+    let near = NearTestContext::new().await;
+    assert_chain_context(&near, NearToken::from_near(5)).await;
+}
@@ -0,0 +1,126 @@
+//! Third-party chain modules plugged into an OmniBox, the contract is deployed with `deployer.json`
+use omni_box::{
+    ChainModule, ChainModuleFactory, Manifest, ManifestAccount, ModuleFuture, ModuleSnapshot,
+    OmniBox, OmniBoxOptions,
+};
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Chain kept in memory, its whole state is its height
+#[derive(Default)]
+struct MemoryChain {
+    started: AtomicBool,
+    height: AtomicU64,
+}
+
+impl ChainModule for MemoryChain {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn start(&mut self) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            self.started.store(true, Ordering::SeqCst);
+            Ok(())
+        })
+    }
+
+    fn is_ready(&self) -> ModuleFuture<'_, bool> {
+        Box::pin(async move { Ok(self.started.load(Ordering::SeqCst)) })
+    }
+
+    fn create_funded_account(&self, _balance: u128) -> ModuleFuture<'_, ManifestAccount> {
+        Box::pin(async move {
+            Ok(ManifestAccount {
+                address: format!("memory-{}", self.height.load(Ordering::SeqCst)),
+                private_key: "memory-key".to_string(),
+            })
+        })
+    }
+
+    fn fund<'a>(&'a self, _address: &'a str, _amount: u128) -> ModuleFuture<'a, ()> {
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn broadcast_raw_transaction<'a>(
+        &'a self,
+        _raw_transaction: &'a [u8],
+    ) -> ModuleFuture<'a, String> {
+        Box::pin(async move {
+            let height = self.height.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(format!("tx-{}", height))
+        })
+    }
+
+    fn height(&self) -> ModuleFuture<'_, u64> {
+        Box::pin(async move { Ok(self.height.load(Ordering::SeqCst)) })
+    }
+
+    fn timestamp(&self) -> ModuleFuture<'_, u64> {
+        Box::pin(async move { Ok(self.height.load(Ordering::SeqCst) * 10) })
+    }
+
+    fn advance_time(&self, duration: Duration) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            self.height
+                .fetch_add(duration.as_secs().div_ceil(10), Ordering::SeqCst);
+            Ok(())
+        })
+    }
+
+    fn advance_blocks(&self, blocks: u64) -> ModuleFuture<'_, ()> {
+        Box::pin(async move {
+            self.height.fetch_add(blocks, Ordering::SeqCst);
+            Ok(())
+        })
+    }
+
+    fn snapshot(&self) -> ModuleFuture<'_, ModuleSnapshot> {
+        Box::pin(async move {
+            let snapshot: ModuleSnapshot = Arc::new(self.height.load(Ordering::SeqCst));
+            Ok(snapshot)
+        })
+    }
+
+    fn revert<'a>(&'a self, snapshot: &'a ModuleSnapshot) -> ModuleFuture<'a, ()> {
+        Box::pin(async move {
+            let height = snapshot
+                .downcast_ref::<u64>()
+                .ok_or("Snapshot was not taken by the memory module")?;
+            self.height.store(*height, Ordering::SeqCst);
+            Ok(())
+        })
+    }
+
+    fn manifest(&self) -> serde_json::Value {
+        json!({ "height": self.height.load(Ordering::SeqCst) })
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs deployer.json and the NEAR testnet"]
+async fn test_third_party_module_snapshot_revert_and_manifest() {
+    let omni_box = OmniBox::new_with_conf(Some(OmniBoxOptions {
+        modules: vec![],
+        chain_modules: vec![ChainModuleFactory::new(|| Box::new(MemoryChain::default()))],
+        ..OmniBoxOptions::default()
+    }))
+    .await;
+    assert!(omni_box.near_context().is_none());
+
+    let times = omni_box.advance_blocks(5).await.unwrap();
+    assert_eq!(times["memory"].height, 5);
+
+    let id = omni_box.snapshot().await.unwrap();
+    omni_box.advance_blocks(3).await.unwrap();
+    omni_box.revert(id).await.unwrap();
+
+    let memory = omni_box.chain_module("memory").unwrap();
+    assert_eq!(memory.height().await.unwrap(), 5);
+
+    let manifest = Manifest::new(&omni_box);
+    assert_eq!(manifest.modules["memory"], json!({ "height": 5 }));
+    assert!(manifest.evm.is_none());
+}
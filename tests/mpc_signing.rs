@@ -0,0 +1,68 @@
+//! MPC signing flow replayed from a cassette, runs without network access
+//!
+//! The cassette holds the responses of a stand-in signer, signing with `SIGNER_PUBLIC_KEY`.
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1};
+use near_crypto::{KeyType, SecretKey};
+use near_primitives::action::FunctionCallAction;
+use omni_box::friendly_near_json_rpc_client::cassette::{Cassette, CassetteMode};
+use omni_box::friendly_near_json_rpc_client::FriendlyNearJsonRpcClient;
+use omni_box::utils::signature::{
+    create_signature, extract_big_r_and_s, extract_multiple_signatures,
+};
+use omni_box::NearAccount;
+use serde_json::json;
+use std::str::FromStr as _;
+
+const CASSETTE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/mpc_sign_request.json"
+);
+
+const SIGNER_PUBLIC_KEY: &str =
+    "034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa";
+
+const PAYLOAD: [u8; 32] = [7; 32];
+
+fn deployer() -> NearAccount {
+    let private_key = SecretKey::from_seed(KeyType::ED25519, "omnitester.testnet");
+    NearAccount {
+        account_id: "omnitester.testnet".parse().unwrap(),
+        public_key: private_key.public_key(),
+        private_key,
+    }
+}
+
+#[tokio::test]
+async fn test_sign_request_replayed_from_cassette() {
+    let cassette = Cassette::start(CassetteMode::Replay, CASSETTE, "")
+        .await
+        .unwrap();
+    let client = FriendlyNearJsonRpcClient::new_with_cassette(&cassette, deployer());
+
+    let response = client
+        .send_action(FunctionCallAction {
+            method_name: "sign_request".to_string(),
+            args: json!({ "payload": PAYLOAD, "path": "bitcoin-1", "key_version": 0 })
+                .to_string()
+                .into_bytes(),
+            gas: 300_000_000_000_000,
+            deposit: 1,
+        })
+        .await
+        .unwrap();
+
+    let (big_r, s) = extract_big_r_and_s(&response).unwrap();
+    assert_eq!(
+        extract_multiple_signatures(&response).unwrap(),
+        vec![(big_r.clone(), s.clone())]
+    );
+
+    let signature = create_signature(&big_r, &s).unwrap();
+    Secp256k1::verification_only()
+        .verify_ecdsa(
+            &Message::from_digest(PAYLOAD),
+            &signature,
+            &PublicKey::from_str(SIGNER_PUBLIC_KEY).unwrap(),
+        )
+        .unwrap();
+}
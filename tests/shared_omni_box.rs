@@ -0,0 +1,56 @@
+//! Leases of the shared OmniBox, the contract is deployed with `deployer.json`
+use near_workspaces::types::NearToken;
+use omni_box::{LeaseIsolation, Network, OmniBox, OmniBoxOptions};
+
+/// Options of every test, the shared OmniBox is started by the first lease
+fn options() -> OmniBoxOptions {
+    OmniBoxOptions {
+        modules: vec![Network::Near],
+        ..OmniBoxOptions::default()
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs deployer.json, the NEAR testnet and near-sandbox"]
+async fn test_fresh_accounts_leases_run_concurrently() {
+    let (first, second) = tokio::join!(
+        OmniBox::lease_with_conf(options(), LeaseIsolation::FreshAccounts),
+        OmniBox::lease_with_conf(options(), LeaseIsolation::FreshAccounts),
+    );
+    let (first, second) = (first.unwrap(), second.unwrap());
+
+    assert_ne!(first.id, second.id);
+    let first_account = first.near_account.as_ref().unwrap();
+    let second_account = second.near_account.as_ref().unwrap();
+    assert_ne!(first_account.id(), second_account.id());
+    assert_eq!(
+        first_account.view_account().await.unwrap().balance,
+        NearToken::from_near(100)
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs deployer.json, the NEAR testnet and near-sandbox"]
+async fn test_snapshot_leases_start_from_the_baseline() {
+    let lease = OmniBox::lease_with_conf(options(), LeaseIsolation::Snapshot)
+        .await
+        .unwrap();
+    let near = lease.near_context().unwrap();
+    let baseline_balance = near.alice().view_account().await.unwrap().balance;
+    near.alice()
+        .transfer_near(near.bob().id(), NearToken::from_near(1))
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    drop(lease);
+
+    let lease = OmniBox::lease_with_conf(options(), LeaseIsolation::Snapshot)
+        .await
+        .unwrap();
+    let near = lease.near_context().unwrap();
+    let balance = near.alice().view_account().await.unwrap().balance;
+    drop(lease);
+
+    assert_eq!(balance, baseline_balance);
+}
@@ -0,0 +1,107 @@
+//! Snapshot and revert of the contexts, each test needs the binary of its node
+use bitcoin::hex::FromHex as _;
+use bitcoin::{Address, Amount};
+use near_workspaces::types::{AccessKey, KeyType, NearToken, SecretKey};
+use omni_box::{BTCTestContext, ChainContext, NearTestContext};
+use serde_json::{json, Value};
+
+/// Amount sent by the test spends, the rest of the coin pays the fee
+const SPENT: Amount = Amount::from_sat(90_000_000);
+
+/// Signed transaction sending the coin `txid:vout` of the node wallet to `to`
+fn spend(btc: &BTCTestContext, txid: &str, vout: u32, to: &Address) -> Vec<u8> {
+    let raw_transaction: String = btc
+        .client()
+        .call(
+            "createrawtransaction",
+            &[
+                json!([{ "txid": txid, "vout": vout }]),
+                json!({ to.to_string(): SPENT.to_btc() }),
+            ],
+        )
+        .unwrap();
+    let signed: Value = btc
+        .client()
+        .call("signrawtransactionwithwallet", &[json!(raw_transaction)])
+        .unwrap();
+    assert_eq!(signed["complete"], true);
+
+    Vec::from_hex(signed["hex"].as_str().unwrap()).unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs bitcoind"]
+async fn test_btc_revert_lets_the_same_coin_be_spent_again() {
+    let btc = BTCTestContext::default();
+    let alice = btc
+        .create_funded_account(Amount::from_btc(1.0).unwrap())
+        .await
+        .unwrap();
+    let coin = &btc.get_utxo_for_address(&alice.address).unwrap()[0];
+    let wallet_balance: f64 = btc.client().call("getbalance", &[]).unwrap();
+    let snapshot = btc.snapshot().await.unwrap();
+
+    let bob = btc.create_funded_account(Amount::ZERO).await.unwrap();
+    btc.broadcast_raw_transaction(&spend(&btc, &coin.txid, coin.vout, &bob.address))
+        .await
+        .unwrap();
+    assert_eq!(btc.balance(&bob.address).await.unwrap(), SPENT);
+
+    btc.revert(snapshot).await.unwrap();
+    let mempool: Vec<String> = btc.client().call("getrawmempool", &[]).unwrap();
+    assert!(mempool.is_empty());
+    assert_eq!(btc.balance(&bob.address).await.unwrap(), Amount::ZERO);
+    let reverted_balance: f64 = btc.client().call("getbalance", &[]).unwrap();
+    assert_eq!(reverted_balance, wallet_balance);
+
+    // The spend of the reverted block is gone, it does not conflict with a new spend of the coin
+    let carol = btc.create_funded_account(Amount::ZERO).await.unwrap();
+    btc.broadcast_raw_transaction(&spend(&btc, &coin.txid, coin.vout, &carol.address))
+        .await
+        .unwrap();
+    assert_eq!(btc.balance(&carol.address).await.unwrap(), SPENT);
+    assert_eq!(btc.balance(&bob.address).await.unwrap(), Amount::ZERO);
+}
+
+#[tokio::test]
+#[ignore = "needs near-sandbox"]
+async fn test_near_revert_deletes_the_entries_added_since_the_snapshot() {
+    let near = NearTestContext::new().await;
+    let alice = near.alice().id();
+    near.client()
+        .patch(alice)
+        .state(b"kept", b"before")
+        .transact()
+        .await
+        .unwrap();
+    let snapshot = near.snapshot().await.unwrap();
+
+    near.client()
+        .patch(alice)
+        .state(b"kept", b"after")
+        .state(b"added", b"after")
+        .access_key(
+            SecretKey::from_random(KeyType::ED25519).public_key(),
+            AccessKey::full_access(),
+        )
+        .transact()
+        .await
+        .unwrap();
+
+    near.revert(snapshot).await.unwrap();
+    let state = near.client().view_state(alice).await.unwrap();
+    assert_eq!(state.get(b"kept".as_slice()), Some(&b"before".to_vec()));
+    assert!(!state.contains_key(b"added".as_slice()));
+    assert_eq!(
+        near.client().view_access_keys(alice).await.unwrap().len(),
+        1
+    );
+
+    // The recreated account still signs with its own key
+    near.alice()
+        .transfer_near(near.bob().id(), NearToken::from_near(1))
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+}
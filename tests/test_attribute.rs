@@ -0,0 +1,23 @@
+//! Tests written with the `#[omni_box::test]` attribute, and its compile errors
+use near_workspaces::types::NearToken;
+use omni_box::{NearTestContext, OmniBox};
+use std::error::Error;
+
+#[omni_box::test(modules = [near])]
+#[ignore = "needs deployer.json, the NEAR testnet and near-sandbox"]
+async fn test_injects_the_omni_box_and_its_contexts(omni_box: &OmniBox, near: &NearTestContext) {
+    assert!(omni_box.near_context().is_some());
+    assert!(near.alice().view_account().await.unwrap().balance > NearToken::from_near(0));
+}
+
+#[omni_box::test(modules = [near], force_redeploy = false)]
+#[ignore = "needs deployer.json, the NEAR testnet and near-sandbox"]
+async fn test_returns_a_result(near: &NearTestContext) -> Result<(), Box<dyn Error>> {
+    near.bob().view_account().await?;
+    Ok(())
+}
+
+#[test]
+fn test_attribute_compile_errors() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,57 @@
+//! Time control of the contexts, each test needs the binary of its node
+use omni_box::{BTCTestContext, ChainContext, EVMTestContext, NearTestContext};
+use std::time::Duration;
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+/// Advances the time twice, each advance must move the timestamp by at least an hour
+async fn assert_time_advances<C: ChainContext + Sync>(context: &C) {
+    for _ in 0..2 {
+        let before = context.timestamp().await.unwrap();
+        context.advance_time(HOUR).await.unwrap();
+        let after = context.timestamp().await.unwrap();
+
+        assert!(
+            after >= before + HOUR.as_secs(),
+            "timestamp moved from {} to {}",
+            before,
+            after
+        );
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs bitcoind"]
+async fn test_btc_advance_time() {
+    let btc = BTCTestContext::default();
+    assert_time_advances(&btc).await;
+
+    // Blocks keep being mined after the time was advanced
+    let height = btc.height().await.unwrap();
+    btc.advance_blocks(1).await.unwrap();
+    assert_eq!(btc.height().await.unwrap(), height + 1);
+}
+
+#[tokio::test]
+#[ignore = "needs anvil"]
+async fn test_evm_advance_time() {
+    let evm = EVMTestContext::default();
+    assert_time_advances(&evm).await;
+}
+
+#[tokio::test]
+#[ignore = "needs near-sandbox"]
+async fn test_near_advance_time() {
+    let near = NearTestContext::new().await;
+    assert_time_advances(&near).await;
+}
+
+#[tokio::test]
+#[ignore = "needs anvil"]
+async fn test_evm_advance_time_rounds_up() {
+    let evm = EVMTestContext::default();
+    let before = evm.timestamp().await.unwrap();
+    evm.advance_time(Duration::from_millis(1500)).await.unwrap();
+
+    assert!(evm.timestamp().await.unwrap() >= before + 2);
+}
@@ -0,0 +1,4 @@
+#[omni_box::test]
+fn test_not_async() {}
+
+fn main() {}
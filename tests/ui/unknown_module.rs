@@ -0,0 +1,4 @@
+#[omni_box::test(modules = [near, solana])]
+async fn test_unknown_module() {}
+
+fn main() {}
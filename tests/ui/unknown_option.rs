@@ -0,0 +1,4 @@
+#[omni_box::test(chains = [evm])]
+async fn test_unknown_option() {}
+
+fn main() {}
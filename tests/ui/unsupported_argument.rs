@@ -0,0 +1,6 @@
+#[omni_box::test]
+async fn test_unsupported_argument(account: &String) {
+    let _ = account;
+}
+
+fn main() {}
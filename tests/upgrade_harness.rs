@@ -0,0 +1,85 @@
+//! Contract upgrade from version A to version B in the NEAR sandbox
+use omni_box::upgrade_harness::UpgradeTest;
+use omni_box::{ContractCall, ContractSource, NearTestContext};
+use serde_json::json;
+
+/// Storage key written by both versions of the contract
+const STATE_KEY: &[u8] = b"state";
+
+/// Contract exporting `method`, which writes `value` under `STATE_KEY`
+///
+/// The module imports the memory and `storage_write` from the NEAR runtime, its data segment
+/// holds the key followed by the value.
+fn contract_writing(method: &str, value: u8) -> Vec<u8> {
+    fn section(id: u8, content: &[u8]) -> Vec<u8> {
+        [&[id, content.len() as u8], content].concat()
+    }
+    fn name(name: &str) -> Vec<u8> {
+        [&[name.len() as u8], name.as_bytes()].concat()
+    }
+
+    let types = [
+        &[2][..],
+        &[0x60, 5, 0x7e, 0x7e, 0x7e, 0x7e, 0x7e, 1, 0x7e], // (i64 x 5) -> i64
+        &[0x60, 0, 0],                                     // () -> ()
+    ]
+    .concat();
+    let imports = [
+        vec![2],
+        name("env"),
+        name("memory"),
+        vec![0x02, 0, 1],
+        name("env"),
+        name("storage_write"),
+        vec![0x00, 0],
+    ]
+    .concat();
+    let exports = [vec![1], name(method), vec![0x00, 1]].concat();
+    // storage_write(key_len, key_ptr, value_len, value_ptr, register_id)
+    let body = [
+        0x00, 0x42, 5, 0x42, 0, 0x42, 1, 0x42, 5, 0x42, 0, 0x10, 0, 0x1a, 0x0b,
+    ];
+    let code = [&[1, body.len() as u8][..], &body].concat();
+    let data = [&[1, 0, 0x41, 0, 0x0b, 6][..], STATE_KEY, &[value]].concat();
+
+    [
+        &b"\0asm\x01\0\0\0"[..],
+        &section(1, &types),
+        &section(2, &imports),
+        &section(3, &[1, 1]),
+        &section(7, &exports),
+        &section(10, &code),
+        &section(11, &data),
+    ]
+    .concat()
+}
+
+#[tokio::test]
+#[ignore = "needs near-sandbox"]
+async fn test_upgrade_runs_the_migration_of_version_b() {
+    let near = NearTestContext::new().await;
+
+    let report = UpgradeTest::new(
+        ContractSource::WasmBytes(contract_writing("seed", b'A')),
+        ContractSource::WasmBytes(contract_writing("migrate", b'B')),
+    )
+    .with_migrate(ContractCall::new("migrate", json!({})))
+    .run(
+        near.client(),
+        |contract| async move {
+            contract.call("seed").transact().await?.into_result()?;
+            Ok(())
+        },
+        |_contract, states| async move {
+            assert_eq!(states.after[STATE_KEY], b"B");
+            assert!(states.removed_keys().is_empty());
+            Ok(())
+        },
+    )
+    .await
+    .unwrap();
+
+    assert_ne!(report.from_code_hash, report.to_code_hash);
+    assert_eq!(report.states.before[STATE_KEY], b"A");
+    assert_eq!(report.states.changed_keys(), vec![&STATE_KEY.to_vec()]);
+}